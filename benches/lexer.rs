@@ -0,0 +1,65 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+// Reports time and count of heap allocations of compiling large sources:
+// cargo bench --bench lexer
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use ton_labs_assembler::{CompileOptions, compile_code_to_cell_with_options};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn source(functions: usize) -> String {
+    let mut code = String::new();
+    for i in 0..functions {
+        code += &format!("; function {}\n", i);
+        code += "PUSHINT 1\nPUSHINT 2\nadd\nDUP\nPUSHCONT {\n  XCHG s1, s2\n  DROP\n}\nIF\n";
+        code += "PUSHSLICE x{ABCDEF}\nNEWC\nSTSLICE\nENDC\nDROP\n";
+    }
+    code
+}
+
+fn bench(name: &str, code: &str, options: &CompileOptions) {
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    let time = Instant::now();
+    compile_code_to_cell_with_options(code, options).expect("code compiles");
+    let elapsed = time.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - start;
+    println!("{:<24} {:>8} bytes {:>10} allocations {:>10.2?}", name, code.len(), allocations, elapsed);
+}
+
+fn main() {
+    let options = CompileOptions::default();
+    for functions in [100, 1000] {
+        let code = source(functions);
+        bench(&format!("{} functions", functions), &code, &options);
+    }
+}
@@ -0,0 +1,65 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+// Reports time and count of heap allocations of writing commands with references
+// into cells filled with data: cargo bench --bench writer
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use ton_types::BuilderData;
+
+use ton_labs_assembler::{CodePage0, DbgNode, DbgPos, Writer};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn bench(name: &str, filler: usize, rounds: usize) {
+    let reference = BuilderData::with_raw(vec![0xAB; 32], 256).expect("reference fits");
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    let time = Instant::now();
+    for _ in 0..rounds {
+        let mut code = CodePage0::new();
+        // big cells, each taking commands with references at its end
+        for _ in 0..100 {
+            code.write_command(&vec![0; filler], DbgNode::new()).expect("command fits");
+            for _ in 0..2 {
+                // PUSHREF
+                code.write_composite_command(&[0x88], reference.clone(), DbgPos::default(), DbgNode::new()).expect("command fits");
+            }
+        }
+        code.finalize().expect("code finalizes");
+    }
+    let elapsed = time.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - start;
+    println!("{:<24} {:>10} allocations {:>10.2?}", name, allocations / rounds, elapsed);
+}
+
+fn main() {
+    bench("8 bytes per cell", 8, 100);
+    bench("120 bytes per cell", 120, 100);
+}
@@ -0,0 +1,465 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::{collections::HashMap, convert::TryFrom, ops::RangeInclusive};
+use ton_types::{BuilderData, Cell, HashmapE, HashmapType, SliceData, UInt256};
+
+use super::{
+    CompileError, CompileOptions, CompileResult, DbgInfo, DbgPos, Engine, Lines, compile_with_writer, debug_info, finalize_root, lines_to_string,
+    cache::{CachedCode, CodeCache, cache_key, cached_node},
+    debug::DbgNode,
+    macros::with_writer,
+    writer::Writer,
+};
+
+/// Exit code thrown by the dispatcher when no public procedure matches the function id
+pub const UNKNOWN_FUNCTION_ID: u8 = 60;
+
+/// Directive starting a procedure: `.pubproc name` of ABI function,
+/// `.public name, id` or `.internal name, id` of function with the id given
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    PubProc,
+    Public(u32),
+    Internal(i32),
+}
+
+struct Procedure {
+    name: String,
+    pos: DbgPos,
+    lines: Lines,
+    section: Section,
+}
+
+#[cfg(feature = "serde")]
+fn parse_function_id(value: &serde_json::Value) -> Option<u32> {
+    match value {
+        serde_json::Value::Number(number) => number.as_u64().filter(|id| *id <= u32::MAX as u64).map(|id| id as u32),
+        serde_json::Value::String(string) => {
+            if string.starts_with("0x") || string.starts_with("0X") {
+                u32::from_str_radix(&string[2..], 16).ok()
+            } else {
+                string.parse().ok()
+            }
+        }
+        _ => None
+    }
+}
+
+/// Line and column of every element of the `functions` array of the ABI JSON
+#[cfg(feature = "serde")]
+fn function_positions(abi: &str) -> Vec<(usize, usize)> {
+    let mut positions = Vec::new();
+    let (mut line, mut column) = (1, 0);
+    let (mut depth, mut string, mut escaped) = (0, None, false);
+    // last key of the root object and whether elements of functions are expected
+    let mut key = String::new();
+    let (mut functions, mut element) = (false, false);
+    for ch in abi.chars() {
+        if ch == '\n' {
+            line += 1;
+            column = 0;
+            continue
+        }
+        column += 1;
+        if let Some(text) = string.as_mut() {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    if depth == 1 {
+                        key = std::mem::take(text)
+                    }
+                    string = None
+                }
+                _ => text.push(ch)
+            }
+            continue
+        }
+        if ch.is_whitespace() {
+            continue
+        }
+        if element && depth == 2 && ch != ']' {
+            positions.push((line, column));
+            element = false
+        }
+        match ch {
+            '"' => string = Some(String::new()),
+            '{' | '[' => {
+                depth += 1;
+                if depth == 2 && ch == '[' && key == "functions" {
+                    functions = true;
+                    element = true
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                if depth < 2 {
+                    functions = false
+                }
+            }
+            ',' if functions && depth == 2 => element = true,
+            _ => ()
+        }
+    }
+    positions
+}
+
+/// Name, id and position of the entry of a function declared in ABI
+#[cfg(feature = "serde")]
+type AbiFunction = (String, u32, (usize, usize));
+
+/// Reads function names and ids from the ABI JSON along with positions of their entries
+#[cfg(feature = "serde")]
+fn parse_abi(abi: &str) -> Result<Vec<AbiFunction>, CompileError> {
+    let abi_json: serde_json::Value = serde_json::from_str(abi)
+        .map_err(|e| CompileError::syntax(e.line(), e.column(), format!("Invalid ABI: {}", e)))?;
+    let functions = abi_json.get("functions")
+        .and_then(|functions| functions.as_array())
+        .ok_or_else(|| CompileError::syntax(1, 1, "Invalid ABI: no functions array"))?;
+    let positions = function_positions(abi);
+    let mut result = Vec::new();
+    for (i, function) in functions.iter().enumerate() {
+        let (line, column) = positions.get(i).copied().unwrap_or((1, 1));
+        let name = function.get("name")
+            .and_then(|name| name.as_str())
+            .ok_or_else(|| CompileError::syntax(line, column, "Invalid ABI: function without name"))?;
+        let id = function.get("id")
+            .and_then(parse_function_id)
+            .ok_or_else(|| CompileError::syntax(line, column, format!("Invalid ABI: function {} has no valid id", name)))?;
+        result.push((name.to_string(), id, (line, column)));
+    }
+    Ok(result)
+}
+
+/// Id of `.public` or `.internal` function in the range given
+fn parse_id<T: TryFrom<i64>>(token: Option<&str>, range: RangeInclusive<i64>) -> Option<T> {
+    let token = token?;
+    let id = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => token.parse().ok()?
+    };
+    T::try_from(id).ok().filter(|_| range.contains(&id))
+}
+
+/// Splits code into the preamble and sections of procedures
+fn split_procedures(code: Lines) -> Result<(Lines, Vec<Procedure>), CompileError> {
+    let mut preamble = Lines::new();
+    let mut procedures: Vec<Procedure> = Vec::new();
+    for line in code {
+        let mut tokens = line.text.split(|ch: char| ch.is_whitespace() || (ch == ',')).filter(|token| !token.is_empty());
+        let directive = match tokens.next() {
+            Some(token) if [".pubproc", ".public", ".internal"].iter().any(|d| token.eq_ignore_ascii_case(d)) => token.to_ascii_uppercase(),
+            _ => {
+                match procedures.last_mut() {
+                    Some(procedure) => procedure.lines.push(line),
+                    None => preamble.push(line)
+                }
+                continue
+            }
+        };
+        let name = match tokens.next() {
+            Some(name) => name.to_string(),
+            None => return Err(CompileError::missing_params(line.pos.line_code, 1, directive)
+                .with_filename(line.pos.filename.clone()))
+        };
+        let section = match directive.as_str() {
+            ".PUBPROC" => Some(Section::PubProc),
+            ".PUBLIC" => parse_id(tokens.next(), 0..=u32::MAX as i64).map(Section::Public),
+            // ids of dictionary with 19-bit signed keys
+            _ => parse_id(tokens.next(), -(1 << 18)..=(1 << 18) - 1).map(Section::Internal),
+        };
+        let section = match section {
+            Some(section) if tokens.next().is_none() => section,
+            _ => return Err(CompileError::syntax(line.pos.line_code, 1, format!("Bad {} directive", directive))
+                .with_filename(line.pos.filename.clone()))
+        };
+        if procedures.iter().any(|p| p.name == name) {
+            return Err(CompileError::syntax(line.pos.line_code, 1, format!("Procedure {} is defined twice", name))
+                .with_filename(line.pos.filename.clone()))
+        }
+        if procedures.iter().any(|p| p.section == section && section != Section::PubProc) {
+            return Err(CompileError::syntax(line.pos.line_code, 1, format!("Id of procedure {} is already used", name))
+                .with_filename(line.pos.filename.clone()))
+        }
+        procedures.push(Procedure { name, pos: line.pos, lines: Lines::new(), section });
+    }
+    Ok((preamble, procedures))
+}
+
+#[cfg(feature = "serde")]
+fn compile_lines<T: Writer>(lines: Lines, options: &CompileOptions) -> Result<T, CompileError> {
+    let source = lines_to_string(&lines);
+    let mut engine = Engine::<T>::sharing_blocks(lines, options, |engine| engine.compile(&source).map(|_| ()))?;
+    let code = engine.compile(&source)?;
+    engine.take_diagnostics()?;
+    Ok(code)
+}
+
+fn into_cell(builder: BuilderData) -> Result<Cell, CompileError> {
+    builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))
+}
+
+/// Mirrors the dictionary cell tree with debug nodes, attaching procedure bodies at their cells
+pub(crate) fn dictionary_dbg(cell: &Cell, bodies: &HashMap<UInt256, DbgNode>) -> DbgNode {
+    if let Some(node) = bodies.get(&cell.repr_hash()) {
+        return node.clone()
+    }
+    let mut node = DbgNode::new();
+    for i in 0..cell.references_count() {
+        if let Ok(child) = cell.reference(i) {
+            node.append_node(dictionary_dbg(&child, bodies));
+        }
+    }
+    node
+}
+
+fn compile_body(lines: Lines, options: &CompileOptions) -> Result<(Cell, DbgNode), CompileError> {
+    let source = lines_to_string(&lines);
+    let (body, dbg, _) = compile_with_writer(lines, &source, options, false)?;
+    Ok((into_cell(body)?, dbg))
+}
+
+/// Options of procedure bodies, metadata goes to the root cell of the code only
+fn body_options(options: &CompileOptions) -> CompileOptions {
+    CompileOptions { metadata: None, ..options.clone() }
+}
+
+/// Compiles procedure bodies on all available threads, results follow the order of procedures.
+/// Bodies are independent of each other: every one is compiled by its own engine.
+fn compile_bodies(procedures: Vec<Lines>, options: &CompileOptions) -> Result<Vec<(Cell, DbgNode)>, CompileError> {
+    // there are no threads e.g. in WebAssembly
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let options = body_options(options);
+    if threads < 2 || procedures.len() < 2 {
+        return procedures.into_iter().map(|lines| compile_body(lines, &options)).collect()
+    }
+    let size = procedures.len().div_ceil(threads).max(1);
+    let mut chunks: Vec<Vec<Lines>> = Vec::new();
+    for (i, lines) in procedures.into_iter().enumerate() {
+        if i % size == 0 {
+            chunks.push(Vec::new())
+        }
+        chunks.last_mut().unwrap().push(lines)
+    }
+    std::thread::scope(|scope| {
+        let handles = chunks.into_iter()
+            .map(|chunk| scope.spawn(|| {
+                chunk.into_iter().map(|lines| compile_body(lines, &options)).collect::<Result<Vec<_>, _>>()
+            }))
+            .collect::<Vec<_>>();
+        let mut bodies = Vec::new();
+        for handle in handles {
+            bodies.append(&mut handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e))?);
+        }
+        Ok(bodies)
+    })
+}
+
+/// Compiles procedure bodies taking ones of the same source and options from the cache if any,
+/// bodies compiled are stored in the cache. Answers of callbacks are not known in advance,
+/// so the cache is not used with a resolver or a visitor.
+fn compile_bodies_cached(procedures: Vec<Lines>, cache: Option<&mut dyn CodeCache>, options: &CompileOptions) -> Result<Vec<(Cell, DbgNode)>, CompileError> {
+    let cache = match cache {
+        Some(cache) if options.resolver.is_none() && options.visitor.is_none() => cache,
+        _ => return compile_bodies(procedures, options)
+    };
+    let key_options = body_options(options);
+    let mut bodies = Vec::with_capacity(procedures.len());
+    let mut missed = Vec::new();
+    for lines in procedures {
+        let key = cache_key(&lines, &key_options);
+        match cache.load(&key) {
+            Some(cached) => bodies.push(Some((cached_node(&cached.code, &cached.dbg), cached.code))),
+            None => {
+                bodies.push(None);
+                missed.push((key, lines))
+            }
+        }
+    }
+    let (keys, lines): (Vec<_>, Vec<_>) = missed.into_iter().unzip();
+    let mut compiled = keys.into_iter().zip(compile_bodies(lines, options)?);
+    bodies.into_iter().map(|body| match body {
+        Some((dbg, code)) => Ok((code, dbg)),
+        None => {
+            let (key, (code, dbg)) = compiled.next()
+                .ok_or_else(|| CompileError::unknown(0, 0, "failure while compile procedure bodies"))?;
+            let info = DbgInfo::from(&code, &dbg).map_err(|_| CompileError::unknown(0, 0, "failure while collect debug info"))?;
+            cache.store(key, CachedCode { code: code.clone(), dbg: info });
+            Ok((code, dbg))
+        }
+    }).collect()
+}
+
+/// Dictionary of procedure bodies by their keys along with debug info of its cells, None if empty
+fn procedures_dictionary(
+    procedures: Vec<(SliceData, Lines)>,
+    width: usize,
+    cache: Option<&mut dyn CodeCache>,
+    options: &CompileOptions,
+) -> Result<Option<(Cell, DbgNode)>, CompileError> {
+    let mut dict = HashmapE::with_bit_len(width);
+    let mut bodies = HashMap::new();
+    let (keys, lines): (Vec<_>, Vec<_>) = procedures.into_iter().unzip();
+    for (key, (body, dbg)) in keys.into_iter().zip(compile_bodies_cached(lines, cache, options)?) {
+        bodies.insert(body.repr_hash(), dbg);
+        // the value holds no bits and a single reference: TVM jumps to it implicitly
+        let mut value = BuilderData::new();
+        value.append_reference_cell(body);
+        dict.set(key, &into_cell(value)?.into())
+            .map_err(|_| CompileError::unknown(0, 0, "failure while building dispatch dictionary"))?;
+    }
+    Ok(dict.data().map(|root| (root.clone(), dictionary_dbg(root, &bodies))))
+}
+
+/// Writes jump to the procedure of the dictionary by the key on the stack,
+/// throwing `UNKNOWN_FUNCTION_ID` if there is none
+fn write_dispatch<T: Writer>(code: &mut T, dictionary: (Cell, DbgNode), width: usize, jump: &[u8], pos: &DbgPos) -> CompileResult {
+    let (root, dbg) = dictionary;
+    // DICTPUSHCONST width
+    code.write_composite_command(&[0xF4, 0xA4 | (width >> 8) as u8, width as u8], BuilderData::from(&root), pos.clone(), dbg)?;
+    code.write_command(jump, DbgNode::from(pos.clone()))?;
+    // THROW UNKNOWN_FUNCTION_ID
+    code.write_command(&[0xF2, UNKNOWN_FUNCTION_ID], DbgNode::from(pos.clone()))?;
+    Ok(())
+}
+
+fn finalize<T: Writer>(code: T, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    let (builder, dbg) = finalize_root(code, options)?;
+    let cell = into_cell(builder)?;
+    let dbg_info = debug_info(&cell, &dbg, options)?;
+    Ok((cell.into(), dbg_info))
+}
+
+/// Compiles code split into `.pubproc <name>` procedures, generating a selector
+/// dispatching on the function ids declared in the ABI. The function id is expected
+/// on the top of the stack after the preamble (the code before the first `.pubproc`).
+/// Every ABI function must have a procedure and vice versa.
+#[cfg(feature = "serde")]
+pub fn compile_abi_dispatch(abi: &str, code: Lines) -> Result<(SliceData, DbgInfo), CompileError> {
+    abi_dispatch(abi, code, None, &CompileOptions::default())
+}
+
+/// Compiles code like `compile_abi_dispatch` with the options given
+#[cfg(feature = "serde")]
+pub fn compile_abi_dispatch_with_options(abi: &str, code: Lines, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    abi_dispatch(abi, code, None, options)
+}
+
+/// Compiles code like `compile_abi_dispatch_with_options` reusing procedures of the same source
+/// and options from the cache
+#[cfg(feature = "serde")]
+pub fn compile_abi_dispatch_cached(abi: &str, code: Lines, cache: &mut dyn CodeCache, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    abi_dispatch(abi, code, Some(cache), options)
+}
+
+#[cfg(feature = "serde")]
+fn abi_dispatch(abi: &str, code: Lines, cache: Option<&mut dyn CodeCache>, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    with_writer!(options, |T| abi_dispatch_with::<T>(abi, code, cache, options))
+}
+
+#[cfg(feature = "serde")]
+fn abi_dispatch_with<T: Writer>(abi: &str, code: Lines, cache: Option<&mut dyn CodeCache>, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    let functions = parse_abi(abi)?;
+    let (preamble, procedures) = split_procedures(code)?;
+    for (name, _, (line, column)) in &functions {
+        if !procedures.iter().any(|p| &p.name == name) {
+            return Err(CompileError::syntax(*line, *column, format!("Function {} declared in ABI has no .pubproc", name)))
+        }
+    }
+    let dispatch_pos = procedures.first().map(|p| p.pos.clone()).unwrap_or_default();
+    let mut public = Vec::new();
+    for procedure in procedures {
+        let id = match functions.iter().find(|(name, _, _)| name == &procedure.name) {
+            Some((_, id, _)) if procedure.section == Section::PubProc => *id,
+            _ => return Err(CompileError::syntax(
+                procedure.pos.line_code, 1, format!("Procedure {} is not declared in ABI", procedure.name)
+            ).with_filename(procedure.pos.filename))
+        };
+        public.push((SliceData::from_raw(id.to_be_bytes().to_vec(), 32), procedure.lines));
+    }
+    let public = procedures_dictionary(public, 32, cache, options)?;
+    let mut code = compile_lines::<T>(preamble, options)?;
+    if let Some(dictionary) = public {
+        // DICTUGETJMPZ
+        write_dispatch(&mut code, dictionary, 32, &[0xF4, 0xBD], &dispatch_pos)
+            .map_err(|e| CompileError::operation(dispatch_pos.line_code, 1, ".PUBPROC", e)
+            .with_filename(dispatch_pos.filename.clone()))?;
+    }
+    finalize(code, options)
+}
+
+/// Compiles code split into `.public name, id` and `.internal name, id` procedures.
+/// Internal procedures make up the dictionary with 19-bit signed keys set as c3 before
+/// the preamble, so `CALL id` and `JMPDICT id` reach them. Public ones are dispatched after
+/// the preamble on the 32-bit function id expected on the top of the stack, see `compile_abi_dispatch`.
+/// Procedure bodies are compiled on all available threads, blocks within a body and the preamble are not.
+pub fn compile_dispatch(code: Lines) -> Result<(SliceData, DbgInfo), CompileError> {
+    dispatch(code, None, &CompileOptions::default())
+}
+
+/// Compiles code like `compile_dispatch` with the options given
+pub fn compile_dispatch_with_options(code: Lines, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    dispatch(code, None, options)
+}
+
+/// Compiles code like `compile_dispatch_with_options` reusing procedures of the same source
+/// and options from the cache, so recompiling code with a few procedures changed compiles only them
+pub fn compile_dispatch_cached(code: Lines, cache: &mut dyn CodeCache, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    dispatch(code, Some(cache), options)
+}
+
+fn dispatch(code: Lines, cache: Option<&mut dyn CodeCache>, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    with_writer!(options, |T| dispatch_with::<T>(code, cache, options))
+}
+
+fn dispatch_with<T: Writer>(code: Lines, mut cache: Option<&mut dyn CodeCache>, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    let (preamble, procedures) = split_procedures(code)?;
+    let dispatch_pos = procedures.first().map(|p| p.pos.clone()).unwrap_or_default();
+    let (mut public, mut internal) = (Vec::new(), Vec::new());
+    for procedure in procedures {
+        match procedure.section {
+            Section::Public(id) => public.push((SliceData::from_raw(id.to_be_bytes().to_vec(), 32), procedure.lines)),
+            Section::Internal(id) => {
+                let key = (id as u32 & 0x7FFFF) << 13;
+                internal.push((SliceData::from_raw(key.to_be_bytes()[..3].to_vec(), 19), procedure.lines))
+            }
+            Section::PubProc => return Err(CompileError::syntax(
+                procedure.pos.line_code, 1, format!("Procedure {} requires ABI", procedure.name)
+            ).with_filename(procedure.pos.filename))
+        }
+    }
+    let error = |e| CompileError::operation(dispatch_pos.line_code, 1, "dispatch", e).with_filename(dispatch_pos.filename.clone());
+    let mut code = T::new();
+    code.set_layout(options.layout);
+    let cache_ref = cache.as_mut().map(|cache| &mut **cache as &mut dyn CodeCache);
+    if let Some(dictionary) = procedures_dictionary(internal, 19, cache_ref, options)? {
+        let mut dispatcher = T::new();
+        dispatcher.set_layout(options.layout);
+        // DICTIGETJMPZ
+        write_dispatch(&mut dispatcher, dictionary, 19, &[0xF4, 0xBC], &dispatch_pos).map_err(error)?;
+        let (dispatcher, dbg) = dispatcher.finalize().map_err(error)?;
+        // PUSHREFCONT, POPCTR c3
+        code.write_composite_command(&[0x8A], dispatcher, dispatch_pos.clone(), dbg).map_err(error)?;
+        code.write_command(&[0xED, 0x53], DbgNode::from(dispatch_pos.clone())).map_err(error)?;
+    }
+    let source = lines_to_string(&preamble);
+    let mut engine = Engine::<T>::with_options(preamble, options);
+    let mut code = engine.compile_into(&source, code)?;
+    engine.take_diagnostics()?;
+    if let Some(dictionary) = procedures_dictionary(public, 32, cache, options)? {
+        // DICTUGETJMPZ
+        write_dispatch(&mut code, dictionary, 32, &[0xF4, 0xBD], &dispatch_pos).map_err(error)?;
+    }
+    finalize(code, options)
+}
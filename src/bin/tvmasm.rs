@@ -0,0 +1,293 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::{io::{IsTerminal, Write}, path::PathBuf, process::exit};
+use ton_labs_assembler::{
+    CompileOptions, DbgInfo, DiagnosticKind, Disassembler, Layout, Line, Lines, SourceMap, compile_program_with_diagnostics,
+    format_code, state_init,
+};
+use ton_types::{Cell, SliceData};
+
+const USAGE: &str = "\
+Usage: tvmasm [OPTIONS] <FILE>...
+       tvmasm disasm [DISASM OPTIONS] <FILE>
+       tvmasm fmt [-o <PATH>] <FILE>
+
+Compiles TVM assembler files into code, files are concatenated in the given order.
+
+Options:
+  -o <PATH>             write output to the file instead of stdout
+      --hex             output code as hex string of bag of cells (default)
+      --boc             output code as binary bag of cells
+      --tvc             output StateInit with the code as binary bag of cells
+      --debug-map <PATH> write debug info of the code as JSON
+      --metadata <PATH> attach the cell of bag of cells, e.g. salt, as the last
+                        reference of the root cell of the code
+  -I, --include <DIR>   directory to search for included files, may be repeated
+  -D, --define <NAME=VALUE>
+                        define constant visible to the code, also substituted
+                        for ${NAME} placeholders in operands, may be repeated
+  -O, --optimize        apply peephole optimizations
+      --deny <KIND>     fail on warnings of the kind: unreachable-code,
+                        or warnings for all of them, may be repeated
+      --layout <LAYOUT> join cells of the code: greedy, chained, or latest
+                        (default) which may change in later versions
+  -h, --help            print this help
+
+Disassembles code of bag of cells, or of StateInit in .tvc file, into assembler
+with the cell and bit offset of every instruction in comments.
+
+Disasm options:
+  -o <PATH>             write output to the file instead of stdout
+      --tvc             take the code from StateInit, default for .tvc files
+      --debug-map <PATH> add source positions from debug info written by --debug-map
+
+Formats assembler file indenting blocks, aligning operands and writing mnemonics in upper case.";
+
+/// Exit code of compilation errors
+const EXIT_COMPILE: i32 = 1;
+/// Exit code of wrong arguments and IO failures
+const EXIT_USAGE: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Hex,
+    Boc,
+    Tvc,
+}
+
+struct Args {
+    files: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    format: Format,
+    debug_map: Option<PathBuf>,
+    options: CompileOptions,
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("tvmasm: {}", message);
+    exit(EXIT_USAGE)
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        files: Vec::new(),
+        output: None,
+        format: Format::Hex,
+        debug_map: None,
+        options: CompileOptions::default(),
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().unwrap_or_else(|| fail(format!("option {} requires a value", name)));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0)
+            }
+            "-o" => args.output = Some(value(&arg).into()),
+            "--hex" => args.format = Format::Hex,
+            "--boc" => args.format = Format::Boc,
+            "--tvc" => args.format = Format::Tvc,
+            "--debug-map" => args.debug_map = Some(value(&arg).into()),
+            "--metadata" => {
+                let path = PathBuf::from(value(&arg));
+                let bytes = std::fs::read(&path).unwrap_or_else(|e| fail(format!("cannot read {}: {}", path.display(), e)));
+                let cell = ton_types::deserialize_tree_of_cells(&mut bytes.as_slice())
+                    .unwrap_or_else(|e| fail(format!("cannot read bag of cells from {}: {}", path.display(), e)));
+                args.options.metadata = Some(cell)
+            }
+            "-I" | "--include" => args.options.include_dirs.push(value(&arg).into()),
+            "-D" | "--define" => {
+                let define = value(&arg);
+                match define.split_once('=') {
+                    Some((name, text)) if !name.is_empty() => {
+                        args.options.defines.insert(name.to_string(), text.to_string());
+                    }
+                    _ => fail(format!("definition {} is not in form NAME=VALUE", define))
+                }
+            }
+            "-O" | "--optimize" => args.options.optimize = true,
+            "--layout" => {
+                let name = value(&arg);
+                args.options.layout = Layout::from_name(&name).unwrap_or_else(|| fail(format!("unknown layout {}, see --help", name)))
+            }
+            "--deny" => match value(&arg).as_str() {
+                "warnings" => args.options.deny.extend(DiagnosticKind::WARNINGS),
+                kind => match DiagnosticKind::from_name(kind).filter(|kind| *kind != DiagnosticKind::Error) {
+                    Some(kind) => {
+                        args.options.deny.insert(kind);
+                    }
+                    None => fail(format!("unknown warning kind {}, see --help", kind))
+                }
+            }
+            "-" => args.files.push(arg.into()),
+            _ if arg.starts_with('-') => fail(format!("unknown option {}, see --help", arg)),
+            _ => args.files.push(arg.into()),
+        }
+    }
+    if args.files.is_empty() {
+        fail("no input files, see --help")
+    }
+    args
+}
+
+struct DisasmArgs {
+    file: PathBuf,
+    output: Option<PathBuf>,
+    tvc: bool,
+    debug_map: Option<PathBuf>,
+}
+
+fn parse_disasm_args(mut iter: impl Iterator<Item = String>) -> DisasmArgs {
+    let mut file = None;
+    let mut args = DisasmArgs { file: PathBuf::new(), output: None, tvc: false, debug_map: None };
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().unwrap_or_else(|| fail(format!("option {} requires a value", name)));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0)
+            }
+            "-o" => args.output = Some(value(&arg).into()),
+            "--tvc" => args.tvc = true,
+            "--debug-map" => args.debug_map = Some(value(&arg).into()),
+            _ if arg.starts_with('-') && arg != "-" => fail(format!("unknown option {}, see --help", arg)),
+            _ if file.is_some() => fail("disasm takes a single input file"),
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+    args.file = file.unwrap_or_else(|| fail("no input file, see --help"));
+    args.tvc |= args.file.extension().is_some_and(|ext| ext == "tvc");
+    args
+}
+
+/// Code of StateInit: `_ split_depth:(Maybe (## 5)) special:(Maybe TickTock) code:(Maybe ^Cell) ...`
+fn state_init_code(cell: &Cell) -> Option<Cell> {
+    let mut slice = SliceData::from(cell);
+    if slice.get_next_bit().ok()? {
+        slice.move_by(5).ok()?;
+    }
+    if slice.get_next_bit().ok()? {
+        slice.move_by(2).ok()?;
+    }
+    match slice.get_next_bit().ok()? {
+        true => slice.checked_drain_reference().ok(),
+        false => None
+    }
+}
+
+fn disasm(args: DisasmArgs) {
+    let bytes = match args.file.to_str() {
+        Some("-") => {
+            let mut bytes = Vec::new();
+            std::io::Read::read_to_end(&mut std::io::stdin(), &mut bytes).map(|_| bytes)
+        }
+        _ => std::fs::read(&args.file),
+    }.unwrap_or_else(|e| fail(format!("cannot read {}: {}", args.file.display(), e)));
+    let mut cell = ton_types::deserialize_tree_of_cells(&mut bytes.as_slice())
+        .unwrap_or_else(|e| fail(format!("cannot read bag of cells from {}: {}", args.file.display(), e)));
+    if args.tvc {
+        cell = state_init_code(&cell).unwrap_or_else(|| fail(format!("no code in StateInit of {}", args.file.display())));
+    }
+    let file = &args.file;
+    let dbg = args.debug_map.map(|path| {
+        let json = read_file(&path);
+        serde_json::from_str::<DbgInfo>(&json)
+            .unwrap_or_else(|e| fail(format!("cannot read debug info from {}: {}", path.display(), e)))
+    });
+    let text = Disassembler::new().disassemble_annotated(&SliceData::from(cell), dbg.as_ref())
+        .unwrap_or_else(|e| fail(format!("cannot disassemble {}: {}", file.display(), e)));
+    write_output(args.output.as_ref(), text.as_bytes());
+}
+
+fn read_file(path: &PathBuf) -> String {
+    let result = match path.to_str() {
+        Some("-") => std::io::read_to_string(std::io::stdin()),
+        _ => std::fs::read_to_string(path),
+    };
+    result.unwrap_or_else(|e| fail(format!("cannot read {}: {}", path.display(), e)))
+}
+
+fn write_output(path: Option<&PathBuf>, bytes: &[u8]) {
+    let result = match path {
+        Some(path) => std::fs::write(path, bytes),
+        None => std::io::stdout().write_all(bytes),
+    };
+    if let Err(e) = result {
+        fail(format!("cannot write {}: {}", path.map_or("stdout".to_string(), |path| path.display().to_string()), e))
+    }
+}
+
+fn format(mut iter: impl Iterator<Item = String>) {
+    let mut file = None;
+    let mut output = None;
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0)
+            }
+            "-o" => output = Some(PathBuf::from(iter.next().unwrap_or_else(|| fail("option -o requires a value")))),
+            _ if arg.starts_with('-') && arg != "-" => fail(format!("unknown option {}, see --help", arg)),
+            _ if file.is_some() => fail("fmt takes a single input file"),
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+    let file = file.unwrap_or_else(|| fail("no input file, see --help"));
+    let text = read_file(&file);
+    let formatted = format_code(&text).unwrap_or_else(|e| {
+        let filename = file.display().to_string();
+        let mut sources = SourceMap::new().with_colors(std::io::stderr().is_terminal());
+        sources.add(&filename, &text);
+        eprint!("{}", e.with_filename(filename).render(&sources));
+        exit(EXIT_COMPILE)
+    });
+    write_output(output.as_ref(), formatted.as_bytes());
+}
+
+fn main() {
+    match std::env::args().nth(1).as_deref() {
+        Some("disasm") => return disasm(parse_disasm_args(std::env::args().skip(2))),
+        Some("fmt") => return format(std::env::args().skip(2)),
+        _ => ()
+    }
+    let args = parse_args();
+    let mut sources = SourceMap::new().with_colors(std::io::stderr().is_terminal());
+    let files = args.files.iter().map(|path| {
+        let text = read_file(path);
+        let filename = path.display().to_string();
+        sources.add(&filename, &text);
+        text.split_inclusive('\n').enumerate().map(|(i, line)| Line::new(line, &filename, i + 1)).collect()
+    }).collect::<Vec<Lines>>();
+    let (code, dbg, diagnostics) = compile_program_with_diagnostics(files, &args.options).unwrap_or_else(|e| {
+        eprint!("{}", e.render(&sources));
+        exit(EXIT_COMPILE)
+    });
+    for diagnostic in diagnostics {
+        eprint!("{}", diagnostic.render(&sources));
+    }
+    let mut cell = code.cell().clone();
+    if args.format == Format::Tvc {
+        cell = state_init(cell, None, None).unwrap_or_else(|e| fail(e));
+    }
+    let bytes = ton_types::serialize_toc(&cell).unwrap_or_else(|e| fail(format!("cannot serialize code: {}", e)));
+    match args.format {
+        Format::Hex => write_output(args.output.as_ref(), format!("{}\n", hex::encode(bytes)).as_bytes()),
+        Format::Boc | Format::Tvc => write_output(args.output.as_ref(), &bytes),
+    }
+    if let Some(path) = args.debug_map {
+        let json = serde_json::to_string_pretty(&dbg).unwrap_or_else(|e| fail(format!("cannot serialize debug info: {}", e)));
+        write_output(Some(&path), json.as_bytes());
+    }
+}
@@ -0,0 +1,185 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use num::BigInt;
+use ton_types::SliceData;
+
+use super::{
+    CompileError, DbgInfo, DbgPos, Engine, OperationError, finalize_code,
+    complex::pushint_bytecode,
+    debug::{DbgNode, no_filename},
+    instruction::{Instruction, Operand},
+    writer::{CodePage0, Writer},
+};
+
+// shortcuts for commands without operands
+macro_rules! builder_commands {
+    ($($method:ident => $mnemonic:expr),* $(,)?) => {
+        $(
+            pub fn $method(&mut self) -> Result<&mut Self, CompileError> {
+                self.command($mnemonic, vec![])
+            }
+        )*
+    };
+}
+
+/// Builds code from typed instructions, encoding each one right away
+/// with the handlers of the compilation engine
+pub struct CodeBuilder {
+    engine: Engine<CodePage0>,
+    code: CodePage0,
+    count: usize,
+}
+
+impl Default for CodeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CodeBuilder {
+    pub fn new() -> Self {
+        Self {
+            engine: Engine::new(vec![]),
+            code: CodePage0::new(),
+            count: 0,
+        }
+    }
+
+    /// Encodes instruction without source position
+    pub fn push(&mut self, instruction: &Instruction) -> Result<&mut Self, CompileError> {
+        self.push_at(instruction, DbgPos::default())
+    }
+
+    /// Encodes instruction mapping it to the given source position in debug info.
+    /// Errors are reported at the position if set, at the instruction index otherwise.
+    pub fn push_at(&mut self, instruction: &Instruction, pos: DbgPos) -> Result<&mut Self, CompileError> {
+        self.count += 1;
+        let (line, filename) = match pos.line {
+            0 => (self.count, no_filename()),
+            line => (line, pos.filename.clone())
+        };
+        let mut code = std::mem::replace(&mut self.code, CodePage0::new());
+        let result = self.encode(instruction, line, &pos, &mut code);
+        self.code = code;
+        result.map_err(|e| e.with_filename(filename))?;
+        Ok(self)
+    }
+
+    // encodes operands as they are: integers of PUSHINT directly, blocks
+    // recursively, the rest in their source syntax for the handler
+    fn encode(&mut self, instruction: &Instruction, line: usize, pos: &DbgPos, destination: &mut CodePage0) -> Result<(), CompileError> {
+        let mnemonic = instruction.mnemonic.to_ascii_uppercase();
+        if let ("PUSHINT", [Operand::Integer(value)]) = (mnemonic.as_str(), instruction.operands.as_slice()) {
+            return pushint_bytecode(value, self.engine.options.optimize)
+                .and_then(|bytecode| destination.write_command(&bytecode, DbgNode::from(pos.clone())))
+                .map(|_| ())
+                .map_err(|e| CompileError::operation(line, 1, &mnemonic, e))
+        }
+        let handler = match self.engine.COMPILE_ROOT.get(mnemonic.as_str()) {
+            Some(handler) => *handler,
+            None => return Err(CompileError::unknown(line, 1, &mnemonic).with_suggestions(self.engine.suggestions(&mnemonic)))
+        };
+        let mut blocks = vec![];
+        let mut par = vec![];
+        for operand in &instruction.operands {
+            match operand {
+                Operand::Block(code) => {
+                    let mut block = self.engine.writer();
+                    for instruction in code {
+                        self.encode(instruction, line, pos, &mut block).map_err(|e| {
+                            CompileError::operation(line, 1, &mnemonic, OperationError::Nested(Box::new(e)))
+                        })?;
+                    }
+                    blocks.push(block);
+                    par.push(String::new());
+                }
+                operand => par.push(operand.to_string())
+            }
+        }
+        let par = par.iter().map(|p| p.as_str()).collect::<Vec<_>>();
+        self.engine.encode(handler, &par, blocks, destination, pos.clone())
+            .map_err(|e| CompileError::operation(line, 1, &mnemonic, e))
+    }
+
+    /// Encodes command given by its mnemonic and operands
+    pub fn command(&mut self, mnemonic: &str, operands: Vec<Operand>) -> Result<&mut Self, CompileError> {
+        self.push(&Instruction::with_operands(mnemonic, operands))
+    }
+
+    pub fn pushint<I: Into<BigInt>>(&mut self, value: I) -> Result<&mut Self, CompileError> {
+        self.command("PUSHINT", vec![Operand::Integer(value.into())])
+    }
+
+    pub fn pushslice(&mut self, slice: SliceData) -> Result<&mut Self, CompileError> {
+        self.command("PUSHSLICE", vec![Operand::Slice(slice)])
+    }
+
+    pub fn pushcont(&mut self, code: Vec<Instruction>) -> Result<&mut Self, CompileError> {
+        self.command("PUSHCONT", vec![Operand::Block(code)])
+    }
+
+    pub fn push_s(&mut self, index: isize) -> Result<&mut Self, CompileError> {
+        self.command("PUSH", vec![Operand::StackRegister(index)])
+    }
+
+    pub fn pop_s(&mut self, index: isize) -> Result<&mut Self, CompileError> {
+        self.command("POP", vec![Operand::StackRegister(index)])
+    }
+
+    pub fn push_c(&mut self, index: u8) -> Result<&mut Self, CompileError> {
+        self.command("PUSHCTR", vec![Operand::ControlRegister(index)])
+    }
+
+    pub fn pop_c(&mut self, index: u8) -> Result<&mut Self, CompileError> {
+        self.command("POPCTR", vec![Operand::ControlRegister(index)])
+    }
+
+    builder_commands! {
+        nop => "NOP",
+        swap => "SWAP",
+        dup => "DUP",
+        drop => "DROP",
+        over => "OVER",
+        add => "ADD",
+        sub => "SUB",
+        mul => "MUL",
+        div => "DIV",
+        negate => "NEGATE",
+        inc => "INC",
+        dec => "DEC",
+        equal => "EQUAL",
+        less => "LESS",
+        greater => "GREATER",
+        not => "NOT",
+        and => "AND",
+        or => "OR",
+        if_ => "IF",
+        ifelse => "IFELSE",
+        execute => "EXECUTE",
+        ret => "RET",
+        newc => "NEWC",
+        endc => "ENDC",
+        ctos => "CTOS",
+        ends => "ENDS",
+        accept => "ACCEPT",
+    }
+
+    /// Completes the code returning it along with debug info
+    pub fn finalize(self) -> Result<(SliceData, DbgInfo), CompileError> {
+        let (builder, dbg) = finalize_code(self.code)?;
+        let cell = builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))?;
+        let dbg_info = DbgInfo::from(&cell, &dbg).map_err(|_| CompileError::unknown(0, 0, "failure while collect debug info"))?;
+        Ok((cell.into(), dbg_info))
+    }
+}
@@ -0,0 +1,89 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use ton_types::{Cell, UInt256};
+
+use super::{CompileOptions, DbgInfo, DbgNode, Lines};
+
+/// Compiled code of a block along with debug info of its cells
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CachedCode {
+    #[cfg_attr(feature = "serde", serde(with = "crate::object::boc"))]
+    pub code: Cell,
+    pub dbg: DbgInfo,
+}
+
+/// Storage of compiled blocks by hash of their source and options, see `compile_dispatch_cached`.
+/// Build tools may implement it to keep results between runs.
+pub trait CodeCache {
+    fn load(&mut self, key: &UInt256) -> Option<CachedCode>;
+    fn store(&mut self, key: UInt256, code: CachedCode);
+}
+
+/// Cache kept in memory, it may be saved and loaded with serde
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MemoryCache {
+    #[cfg_attr(feature = "serde", serde(with = "crate::debug::hash_keys"))]
+    entries: BTreeMap<UInt256, CachedCode>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl CodeCache for MemoryCache {
+    fn load(&mut self, key: &UInt256) -> Option<CachedCode> {
+        self.entries.get(key).cloned()
+    }
+    fn store(&mut self, key: UInt256, code: CachedCode) {
+        self.entries.insert(key, code);
+    }
+}
+
+/// Key of the block: its text along with source positions, since they get into debug info,
+/// version of the assembler and the options the block is compiled with
+pub(crate) fn cache_key(lines: &Lines, options: &CompileOptions) -> UInt256 {
+    // every field of the options is written, so new ones get into the key as well
+    let mut data = format!("{}\n{:?}\n", env!("CARGO_PKG_VERSION"), options);
+    for line in lines {
+        data += &format!("{}:{}:{}\n{}", line.pos.filename, line.pos.line, line.pos.line_code, line.text);
+    }
+    UInt256::calc_file_hash(data.as_bytes())
+}
+
+/// Debug node of the cell tree rebuilt from debug info of its cells
+pub(crate) fn cached_node(cell: &Cell, dbg: &DbgInfo) -> DbgNode {
+    let mut node = DbgNode::new();
+    if let Some(offsets) = dbg.get(&cell.repr_hash()) {
+        node.offsets = offsets.clone();
+    }
+    for i in 0..cell.references_count() {
+        if let Ok(child) = cell.reference(i) {
+            node.append_node(cached_node(&child, dbg));
+        }
+    }
+    node
+}
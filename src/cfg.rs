@@ -0,0 +1,349 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::{collections::HashSet, fmt::{self, Write}};
+
+use crate::{Instruction, Operand, canonical_mnemonic};
+
+/// Kind of transfer of control between basic blocks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// to the next command, unconditionally or when a condition does not hold
+    Next,
+    /// into a continuation taken on a condition, e.g. by IF or WHILE
+    Branch,
+    /// into a continuation returning to the next command, e.g. by CALLX or loops
+    Call,
+    /// into a continuation returning to the caller of the current one, e.g. by JMPX
+    Jump,
+    /// from the end of a continuation to the command following its call
+    Return,
+    /// from the end of a loop body to the start of the next iteration
+    Loop,
+}
+
+impl fmt::Display for EdgeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            EdgeKind::Next => "next",
+            EdgeKind::Branch => "branch",
+            EdgeKind::Call => "call",
+            EdgeKind::Jump => "jump",
+            EdgeKind::Return => "return",
+            EdgeKind::Loop => "loop",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// Commands running one after another, the last one may transfer control
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicBlock {
+    /// indexes of the instruction and its block operand leading to the code of the block at every nesting level
+    pub path: Vec<(usize, usize)>,
+    /// index of the first command in that code
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+}
+
+/// Control flow graph of the code, see `control_flow_graph`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+    /// blocks returning from the code: by RET, by jumps to unknown continuations or at its end
+    pub exits: Vec<usize>,
+}
+
+impl ControlFlowGraph {
+    /// Index of the block the code starts with
+    pub fn entry(&self) -> usize {
+        0
+    }
+    /// Blocks starting the code and the continuations no edge enters, e.g. functions kept in dictionaries
+    pub fn entries(&self) -> Vec<usize> {
+        let entered = self.edges.iter().map(|edge| edge.to).collect::<HashSet<_>>();
+        (0..self.blocks.len())
+            .filter(|i| *i == self.entry() || self.blocks[*i].start == 0 && !entered.contains(i))
+            .collect()
+    }
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+    /// Graph in Graphviz DOT language, blocks are labeled by their commands
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph code {\n  node [shape=box, fontname=monospace];\n");
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for instruction in &block.instructions {
+                let _ = write!(label, "{}\\l", instruction.to_string().replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\l"));
+            }
+            let _ = writeln!(dot, "  b{} [label=\"{}\"];", i, label);
+        }
+        for edge in &self.edges {
+            let _ = writeln!(dot, "  b{} -> b{} [label=\"{}\"];", edge.from, edge.to, edge.kind);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Entry of the code of a continuation and its blocks returning from it
+struct Continuation {
+    entry: usize,
+    exits: Vec<usize>,
+}
+
+/// Loop made of the rest of the code by WHILEEND, UNTILEND, REPEATEND or AGAINEND
+struct LoopEnd {
+    // exits of the continuation before the loop
+    exits: usize,
+    // start of the next iteration
+    target: usize,
+    // whether the end of the body may leave the loop
+    leaves: bool,
+}
+
+#[derive(Default)]
+struct Builder {
+    graph: ControlFlowGraph,
+}
+
+impl Builder {
+    fn block(&mut self, path: &[(usize, usize)], start: usize) -> usize {
+        self.graph.blocks.push(BasicBlock { path: path.to_vec(), start, instructions: Vec::new() });
+        self.graph.blocks.len() - 1
+    }
+    fn edge(&mut self, from: usize, to: usize, kind: EdgeKind) {
+        self.graph.edges.push(Edge { from, to, kind })
+    }
+    fn returns(&mut self, from: &Continuation, to: usize, kind: EdgeKind) {
+        for exit in &from.exits {
+            self.edge(*exit, to, kind)
+        }
+    }
+
+    /// Builds blocks of the code; continuations pushed right before the commands
+    /// taking them are linked, ones passed otherwise are left without entering edges
+    fn code(&mut self, code: &[Instruction], path: &mut Vec<(usize, usize)>) -> Continuation {
+        let entry = self.block(path, 0);
+        let mut current = entry;
+        let mut exits = Vec::new();
+        let mut pushed: Vec<Continuation> = Vec::new();
+        let mut ends = Vec::new();
+        // whether the end of the code is reached by the last block
+        let mut open = true;
+        for (i, instruction) in code.iter().enumerate() {
+            let mut blocks = Vec::new();
+            for (j, operand) in instruction.operands.iter().enumerate() {
+                if let Operand::Block(block) = operand {
+                    path.push((i, j));
+                    blocks.push(self.code(block, path));
+                    path.pop();
+                }
+            }
+            self.graph.blocks[current].instructions.push(instruction.clone());
+            let upper = instruction.mnemonic.to_ascii_uppercase();
+            let mnemonic = canonical_mnemonic(&upper);
+            if matches!(mnemonic, "PUSHCONT" | "PUSHREFCONT") {
+                pushed.append(&mut blocks);
+                continue
+            }
+            // continuations taken from the stack, the last pushed goes last
+            let mut take = |count: usize| match pushed.len().checked_sub(count) {
+                Some(start) => pushed.drain(start..).map(Some).collect(),
+                None => (0..count).map(|_| None).collect::<Vec<_>>(),
+            };
+            let mut operand = || match blocks.is_empty() {
+                true => None,
+                false => Some(blocks.remove(0)),
+            };
+            let mnemonic = mnemonic.strip_suffix("BRK").filter(|_| mnemonic != "BRK").unwrap_or(mnemonic);
+            let next = i + 1;
+            match mnemonic {
+                "IF" | "IFNOT" | "IFREF" | "IFNOTREF" => {
+                    let cont = if mnemonic.ends_with("REF") { operand() } else { take(1).remove(0) };
+                    let next = self.block(path, next);
+                    if let Some(cont) = cont {
+                        self.edge(current, cont.entry, EdgeKind::Branch);
+                        self.returns(&cont, next, EdgeKind::Return);
+                    }
+                    self.edge(current, next, EdgeKind::Next);
+                    current = next
+                }
+                "IFJMP" | "IFNOTJMP" | "IFJMPREF" | "IFNOTJMPREF" | "IFBITJMP" | "IFNBITJMP" | "IFBITJMPREF" | "IFNBITJMPREF" => {
+                    let cont = if mnemonic.ends_with("REF") { operand() } else { take(1).remove(0) };
+                    let next = self.block(path, next);
+                    match cont {
+                        Some(cont) => {
+                            self.edge(current, cont.entry, EdgeKind::Branch);
+                            exits.extend(cont.exits)
+                        }
+                        None => exits.push(current)
+                    }
+                    self.edge(current, next, EdgeKind::Next);
+                    current = next
+                }
+                "IFELSE" | "IFREFELSE" | "IFELSEREF" | "IFREFELSEREF" => {
+                    let branches = match mnemonic {
+                        "IFELSE" => take(2),
+                        "IFREFELSE" => vec![operand(), take(1).remove(0)],
+                        "IFELSEREF" => vec![take(1).remove(0), operand()],
+                        _ => vec![operand(), operand()],
+                    };
+                    let next = self.block(path, next);
+                    for branch in &branches {
+                        match branch {
+                            Some(branch) => {
+                                self.edge(current, branch.entry, EdgeKind::Branch);
+                                self.returns(branch, next, EdgeKind::Return)
+                            }
+                            None => self.edge(current, next, EdgeKind::Next)
+                        }
+                    }
+                    current = next
+                }
+                "CALLX" | "CALLXARGS" | "CALLXVARARGS" | "CALLREF" => {
+                    let cont = if mnemonic == "CALLREF" { operand() } else { take(1).remove(0) };
+                    let next = self.block(path, next);
+                    match cont {
+                        Some(cont) => {
+                            self.edge(current, cont.entry, EdgeKind::Call);
+                            self.returns(&cont, next, EdgeKind::Return)
+                        }
+                        None => self.edge(current, next, EdgeKind::Next)
+                    }
+                    current = next
+                }
+                "JMPX" | "JMPXARGS" | "JMPXVARARGS" | "JMPXDATA" | "JMPREF" | "JMPREFDATA" | "JMPDICT" => {
+                    let cont = if mnemonic.starts_with("JMPREF") { operand() } else if mnemonic == "JMPDICT" { None } else { take(1).remove(0) };
+                    match cont {
+                        Some(cont) => {
+                            self.edge(current, cont.entry, EdgeKind::Jump);
+                            exits.extend(cont.exits)
+                        }
+                        None => exits.push(current)
+                    }
+                    open = next < code.len();
+                    current = if open { self.block(path, next) } else { current }
+                }
+                "RET" | "RETALT" | "RETBOOL" | "RETARGS" | "RETVARARGS" | "RETDATA" => {
+                    exits.push(current);
+                    open = next < code.len();
+                    current = if open { self.block(path, next) } else { current }
+                }
+                "IFRET" | "IFNOTRET" | "IFRETALT" | "IFNOTRETALT" => {
+                    exits.push(current);
+                    let next = self.block(path, next);
+                    self.edge(current, next, EdgeKind::Next);
+                    current = next
+                }
+                "THROW" | "THROWANY" | "THROWARG" | "THROWARGANY" => {
+                    open = next < code.len();
+                    current = if open { self.block(path, next) } else { current }
+                }
+                "WHILE" => {
+                    let mut taken = take(2);
+                    let next = self.block(path, next);
+                    match (taken.remove(0), taken.remove(0)) {
+                        (Some(condition), Some(body)) => {
+                            self.edge(current, condition.entry, EdgeKind::Call);
+                            self.returns(&condition, body.entry, EdgeKind::Branch);
+                            self.returns(&condition, next, EdgeKind::Return);
+                            self.returns(&body, condition.entry, EdgeKind::Loop)
+                        }
+                        _ => self.edge(current, next, EdgeKind::Next)
+                    }
+                    current = next
+                }
+                "UNTIL" | "REPEAT" | "AGAIN" => {
+                    let next = self.block(path, next);
+                    match take(1).remove(0) {
+                        Some(body) => {
+                            self.edge(current, body.entry, EdgeKind::Call);
+                            self.returns(&body, body.entry, EdgeKind::Loop);
+                            if mnemonic != "AGAIN" {
+                                self.returns(&body, next, EdgeKind::Return)
+                            }
+                        }
+                        None => self.edge(current, next, EdgeKind::Next)
+                    }
+                    // REPEAT may run the body no times
+                    if mnemonic == "REPEAT" {
+                        self.edge(current, next, EdgeKind::Next)
+                    }
+                    current = next
+                }
+                "WHILEEND" => {
+                    let next = self.block(path, next);
+                    match take(1).remove(0) {
+                        Some(condition) => {
+                            self.edge(current, condition.entry, EdgeKind::Call);
+                            self.returns(&condition, next, EdgeKind::Branch);
+                            exits.extend(condition.exits.iter().copied());
+                            ends.push(LoopEnd { exits: exits.len(), target: condition.entry, leaves: false })
+                        }
+                        None => self.edge(current, next, EdgeKind::Next)
+                    }
+                    current = next
+                }
+                "UNTILEND" | "REPEATEND" | "AGAINEND" => {
+                    let next = self.block(path, next);
+                    self.edge(current, next, EdgeKind::Next);
+                    if mnemonic == "REPEATEND" {
+                        exits.push(current)
+                    }
+                    ends.push(LoopEnd { exits: exits.len(), target: next, leaves: mnemonic != "AGAINEND" });
+                    current = next
+                }
+                _ => ()
+            }
+            pushed.clear()
+        }
+        if open {
+            exits.push(current)
+        }
+        // the rest of the code after the last loop end is its body, and so on back
+        while let Some(end) = ends.pop() {
+            let body = exits.split_off(end.exits.min(exits.len()));
+            for exit in &body {
+                self.edge(*exit, end.target, EdgeKind::Loop)
+            }
+            if end.leaves {
+                exits.extend(body)
+            }
+        }
+        Continuation { entry, exits }
+    }
+}
+
+/// Splits parsed code into basic blocks linked by transfers of control. Continuations
+/// pushed by PUSHCONT right before IF, IFELSE, CALLX, JMPX or loops taking them are linked,
+/// as well as blocks of IFREF, CALLREF and other commands; unknown ones are taken to return
+/// right away. Blocks ending with THROW have no successors. See `parse_code`.
+pub fn control_flow_graph(code: &[Instruction]) -> ControlFlowGraph {
+    let mut builder = Builder::default();
+    let root = builder.code(code, &mut Vec::new());
+    builder.graph.exits = root.exits;
+    builder.graph
+}
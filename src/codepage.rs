@@ -0,0 +1,36 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::HashMap;
+
+use super::{CompileHandler, Engine, Writer};
+
+/// Set of commands of a TVM codepage
+pub trait CodePage<T: Writer> {
+    /// Argument of SETCP selecting the codepage
+    fn number(&self) -> i16;
+    /// Mnemonics of the commands along with their handlers
+    fn commands(&self) -> HashMap<&'static str, CompileHandler<T>>;
+}
+
+/// Codepage 0 with commands built into the engine
+pub struct StandardCodePage;
+
+impl<T: Writer> CodePage<T> for StandardCodePage {
+    fn number(&self) -> i16 {
+        0
+    }
+    fn commands(&self) -> HashMap<&'static str, CompileHandler<T>> {
+        Engine::<T>::new(vec![]).COMPILE_ROOT
+    }
+}
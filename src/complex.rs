@@ -11,8 +11,8 @@
 * limitations under the License.
 */
 
-use std::{marker::PhantomData, ops::Range};
-use ton_types::SliceData;
+use std::{collections::{HashMap, HashSet}, marker::PhantomData, ops::Range, path::PathBuf};
+use ton_types::{BuilderData, Cell, CellType, HashmapE, HashmapType, MAX_DATA_BITS, SliceData, UInt256, deserialize_tree_of_cells};
 
 use super::errors::{
     OperationError, ParameterError,
@@ -20,12 +20,14 @@ use super::errors::{
 
 use super::{
     CompileResult, Engine, EnsureParametersCountInRange,
-    convert::to_big_endian_octet_string,
+    abi::dictionary_dbg,
+    convert::{to_big_endian_octet_string, to_pow2_command},
     errors::ToOperationParameterError,
+    include::{read_file, resolve},
     parse::*,
     writer::Writer,
 };
-use num::{BigInt, Num};
+use num::{BigInt, ToPrimitive};
 use crate::debug::{DbgPos, DbgNode};
 
 trait CommandBehaviourModifier {
@@ -58,7 +60,8 @@ fn compile_with_register<T: Writer>(
     let reg = parse_register(register, symbol, range).parameter("arg 0")? as u8;
     let mut ret = code.to_vec();
     ret[code.len() - 1] |= reg;
-    destination.write_command(ret.as_slice(), DbgNode::from(pos))
+    destination.write_command(ret.as_slice(), DbgNode::from(pos))?;
+    Ok(())
 }
 
 fn compile_with_any_register<T: Writer>(
@@ -84,27 +87,74 @@ fn compile_call<T: Writer>(_engine: &mut Engine<T>,  par: &Vec<&str>, destinatio
     par.assert_len(1)?;
     let number = parse_const_u14(par[0]).parameter("Number")?;
     if number < 256 {
-        destination.write_command(&[0xF0, number as u8], DbgNode::from(pos))
+        destination.write_command(&[0xF0, number as u8], DbgNode::from(pos))?;
+        Ok(())
     } else if number < 16384 {
         let hi = 0x3F & ((number / 256) as u8);
         let lo = (number % 256) as u8;
-        destination.write_command(&[0xF1, hi, lo], DbgNode::from(pos))
+        destination.write_command(&[0xF1, hi, lo], DbgNode::from(pos))?;
+        Ok(())
     } else {
         Err(ParameterError::OutOfRange.parameter("Number"))
     }
 }
 
+/// Cell of `boc("path")` operand deserialized from the bag of cells file or of `library(hash)`
+/// operand referring to library code by its hash, None for other operands.
+/// Relative paths are looked up like ones of `.include` directives.
+fn cell_operand(par: &str, pos: &DbgPos, dirs: &[PathBuf]) -> Option<Result<Cell, OperationError>> {
+    let (name, arg) = par.strip_suffix(')')?.split_once('(')?;
+    let arg = arg.trim();
+    if name.eq_ignore_ascii_case("library") {
+        return Some(library_cell(arg))
+    }
+    if !name.eq_ignore_ascii_case("boc") {
+        return None
+    }
+    let path = arg.strip_prefix('"').and_then(|path| path.strip_suffix('"')).unwrap_or(arg);
+    let error = |message: String| OperationError::ExternalCell(format!("{}: {}", path, message));
+    let cell = resolve(path, &pos.filename, dirs).ok_or_else(|| error("file not found".to_string())).and_then(|file| {
+        let data = read_file(&file).map_err(|e| error(e.to_string()))?;
+        deserialize_tree_of_cells(&mut data.as_slice()).map_err(|e| error(e.to_string()))
+    });
+    Some(cell)
+}
+
+/// Cell hash in hex with optional `x` or `0x` prefix or in braces
+fn parse_hash(hash: &str) -> Option<UInt256> {
+    let hash = unbrace(hash);
+    let digits = hash.strip_prefix("0x").or_else(|| hash.strip_prefix(['x', 'X'])).unwrap_or(&hash);
+    hex::decode(digits).ok().filter(|hash| hash.len() == 32).map(|hash| UInt256::from_slice(&hash))
+}
+
+/// Library reference exotic cell of the code hash given in hex
+fn library_cell(hash: &str) -> Result<Cell, OperationError> {
+    let hash = parse_hash(hash).ok_or_else(|| ParameterError::UnexpectedType.parameter("hash"))?;
+    let mut data = vec![2];
+    data.extend_from_slice(hash.as_slice());
+    let mut builder = BuilderData::with_raw(data, 8 + 256).map_err(|_| OperationError::NotFitInSlice)?;
+    builder.set_type(CellType::LibraryReference);
+    builder.into_cell().map_err(|_| OperationError::NotFitInSlice)
+}
+
 fn compile_ref<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, command: &[u8], pos: DbgPos) -> CompileResult {
+    if let Some(cell) = par.first().and_then(|par| cell_operand(par, &pos, &engine.options.include_dirs)) {
+        par.assert_len(1)?;
+        destination.write_composite_command(command, BuilderData::from(&cell?), pos, DbgNode::new())?;
+        return Ok(())
+    }
     if engine.line_no == 0 && engine.char_no == 0 {
         // the case of instruction form without an argument
-        return destination.write_command(command, DbgNode::from(pos));
+        destination.write_command(command, DbgNode::from(pos))?;
+        return Ok(());
     }
     par.assert_len(1)?;
     let (cont, dbg) = engine
         .compile(par[0])
         .map_err(|e| OperationError::Nested(Box::new(e)))?
-        .finalize();
-    destination.write_composite_command(command, cont, pos, dbg)
+        .finalize()?;
+    destination.write_composite_command(command, cont, pos, dbg)?;
+    Ok(())
 }
 
 fn compile_callref<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
@@ -151,6 +201,32 @@ fn compile_pushrefcont<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, desti
     return compile_ref(engine, par, destination, &[0x8A], pos);
 }
 
+fn compile_strefconst<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
+    compile_ref(engine, par, destination, &[0xCF, 0x20], pos)
+}
+
+fn compile_bitjmpref<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, code: u8, pos: DbgPos) -> CompileResult {
+    if engine.line_no == 0 && engine.char_no == 0 {
+        return Err(OperationError::MissingBlock)
+    }
+    par.assert_len(2)?;
+    let n = parse_const_u5(par[1]).parameter("arg 1")?;
+    let (cont, dbg) = engine
+        .compile(par[0])
+        .map_err(|e| OperationError::Nested(Box::new(e)))?
+        .finalize()?;
+    destination.write_composite_command(&[0xE3, code | n], cont, pos, dbg)?;
+    Ok(())
+}
+
+fn compile_ifbitjmpref<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
+    compile_bitjmpref(engine, par, destination, 0xC0, pos)
+}
+
+fn compile_ifnbitjmpref<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
+    compile_bitjmpref(engine, par, destination, 0xE0, pos)
+}
+
 fn compile_pop<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
     par.assert_len(1)?;
     compile_with_any_register(par[0], &[0x30], &[0x57, 0x00], &[0xED, 0x50], destination, pos)
@@ -169,31 +245,40 @@ fn compile_pushcont<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destinat
     let (cont, dbg) = engine
         .compile(par[0])
         .map_err(|e| OperationError::Nested(Box::new(e)))?
-        .finalize();
-    if cont.references_used() > 0 {
-        destination.write_composite_command(&[0x8E, 0x80], cont, pos, dbg)
+        .finalize()?;
+    if engine.blocks.as_mut().is_some_and(|blocks| blocks.block(&cont)) {
+        // the same code is referenced by other blocks
+        destination.write_composite_command(&[0x8A], cont, pos, dbg)?;
+        Ok(())
+    } else if cont.references_used() > 0 {
+        destination.write_composite_command(&[0x8E, 0x80], cont, pos, dbg)?;
+        Ok(())
     } else {
         let n = cont.data().len();
         if n <= 15 {
             let mut command = vec![0x90 | n as u8];
             let mut dbg2 = DbgNode::from(pos);
-            dbg2.inline_node(command.len() * 8, dbg);
+            dbg2.checked_inline_node(command.len() * 8, dbg)?;
             command.extend_from_slice(cont.data());
-            destination.write_command(command.as_slice(), dbg2)
+            destination.write_command(command.as_slice(), dbg2)?;
+            Ok(())
         } else if n <= 125 {
             let mut command = vec![0x8E, n as u8];
             let mut dbg2 = DbgNode::from(pos);
-            dbg2.inline_node(command.len() * 8, dbg);
+            dbg2.checked_inline_node(command.len() * 8, dbg)?;
             command.extend_from_slice(cont.data());
-            destination.write_command(command.as_slice(), dbg2)
-        } else if n <= 127 {
+            destination.write_command(command.as_slice(), dbg2)?;
+            Ok(())
+        } else if n > 127 && engine.options.auto_pushrefcont {
+            // too long for any in-cell encoding: promote to PUSHREFCONT
+            destination.write_composite_command(&[0x8A], cont, pos, dbg)?;
+            Ok(())
+        } else {
             //We cannot put command and code in one cell, because it will 
             //be more than 1023 bits: 127 bytes (pushcont data) + 2 bytes(opcode).
-            //Write as r = 1 and xx = 0x00.
-            destination.write_composite_command(&[0x8E, 0x80], cont, pos, dbg)
-        } else {
-            log::error!(target: "compile", "Maybe cell longer than 1024 bit?");
-            Err(OperationError::NotFitInSlice)
+            //Write as r = 1 and xx = 0x00, the body is chained into the reference.
+            destination.write_composite_command(&[0x8E, 0x80], cont, pos, dbg)?;
+            Ok(())
         }
     }
 }
@@ -202,10 +287,12 @@ fn compile_callxargs<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destin
     par.assert_len(2)?;
     let pargs = parse_const_u4(par[0]).parameter("pargs")?;
     if par[1] == "-1" {
-        destination.write_command(&[0xDB, pargs & 0x0F], DbgNode::from(pos))
+        destination.write_command(&[0xDB, pargs & 0x0F], DbgNode::from(pos))?;
+        Ok(())
     } else {
         let rargs = parse_const_i4(par[1]).parameter("rargs")?;
-        destination.write_command(&[0xDA, ((pargs & 0x0F) << 4) | (rargs & 0x0F)], DbgNode::from(pos))
+        destination.write_command(&[0xDA, ((pargs & 0x0F) << 4) | (rargs & 0x0F)], DbgNode::from(pos))?;
+        Ok(())
     }
 }
 
@@ -232,7 +319,8 @@ macro_rules! div_variant {
                         }
                     }),
                     DbgNode::from(pos)
-                )
+                )?;
+                Ok(())
             }
         }
     };
@@ -285,7 +373,8 @@ impl<M: CommandBehaviourModifier> Div<M> {
                 }
             }),
             DbgNode::from(pos)
-        )
+        )?;
+        Ok(())
     }
 
     fn rshift<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
@@ -295,7 +384,8 @@ impl<M: CommandBehaviourModifier> Div<M> {
         } else {
             vec![0xAD]
         };
-        destination.write_command(&M::modify(command), DbgNode::from(pos))
+        destination.write_command(&M::modify(command), DbgNode::from(pos))?;
+        Ok(())
     }
 
 }
@@ -308,41 +398,44 @@ fn compile_setcontargs<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, dest
     } else {
         0x0F
     };
-    destination.write_command(&[0xEC, ((rargs & 0x0F) << 4) | (nargs & 0x0F)], DbgNode::from(pos))
+    destination.write_command(&[0xEC, ((rargs & 0x0F) << 4) | (nargs & 0x0F)], DbgNode::from(pos))?;
+    Ok(())
 }
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
-fn compile_pushint<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
+fn compile_pushint<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
     par.assert_len(1)?;
-    let (sub_str, radix) = if par[0].len() > 2 && (par[0][0..2].eq("0x") || par[0][0..2].eq("0X")) {
-        (par[0][2..].to_string(), 16)
-    } else if par[0].len() > 3 && (par[0][0..3].eq("-0x") || par[0][0..3].eq("-0X")) {
-        let mut sub_str = par[0].to_string();
-        sub_str.replace_range(1..3, "");
-        (sub_str, 16)
-    } else {
-        (par[0].to_string(), 10)
-    };
-    destination.write_command(match i32::from_str_radix(sub_str.as_str(), radix) {
-        Ok(number @ -5..=10) =>
-            Ok(vec![0x70 | ((number & 0x0F) as u8)]),
-        Ok(number @ -128..=127) =>
-            Ok(vec![0x80, (number & 0xFF) as u8]),
-        Ok(number @ -32768..=32767) =>
-            Ok(vec![0x81, ((number >> 8) & 0xFF) as u8, (number & 0xFF) as u8]),
-        _ => if let Ok(int) = BigInt::from_str_radix(sub_str.as_str(), radix) {
-            if let Some(mut int_bytes) = to_big_endian_octet_string(&int) {
-                let mut bytecode = vec![0x82];
-                bytecode.append(&mut int_bytes);
-                Ok(bytecode)
-            } else {
-                Err(ParameterError::OutOfRange.parameter("arg 0"))
+    if par[0].eq_ignore_ascii_case("NaN") {
+        destination.write_command(&[0x83, 0xFF], DbgNode::from(pos))?;
+        return Ok(())
+    }
+    let int = parse_integer(par[0]).parameter("arg 0")?;
+    let bytecode = pushint_bytecode(&int, engine.options.optimize)?;
+    destination.write_command(bytecode.as_slice(), DbgNode::from(pos))?;
+    Ok(())
+}
+
+/// Shortest encoding of PUSHINT of the integer
+pub(crate) fn pushint_bytecode(int: &BigInt, optimize: bool) -> Result<Vec<u8>, OperationError> {
+    let bytecode = match int.to_i8() {
+        Some(number @ -5..=10) => vec![0x70 | ((number & 0x0F) as u8)],
+        Some(number) => vec![0x80, number as u8],
+        // 16-bit special forms are shorter than the rest
+        None => match to_pow2_command(int).filter(|_| optimize) {
+            Some(bytecode) => bytecode,
+            None => match int.to_i16() {
+                Some(number) => vec![0x81, (number >> 8) as u8, (number & 0xFF) as u8],
+                None => {
+                    let mut bytecode = vec![0x82];
+                    bytecode.append(&mut to_big_endian_octet_string(int)
+                        .ok_or_else(|| ParameterError::OutOfRange.parameter("arg 0"))?);
+                    bytecode
+                }
             }
-        } else {
-            Err(ParameterError::OutOfRange.parameter("arg 0"))
         }
-    }?.as_slice(), DbgNode::from(pos))
-} 
+    };
+    Ok(bytecode)
+}
 
 fn compile_bchkbits<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
     destination.write_command({
@@ -351,7 +444,8 @@ fn compile_bchkbits<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destina
         } else {
             Ok(vec![0xCF, 0x39])
         }
-    }?.as_slice(), DbgNode::from(pos))
+    }?.as_slice(), DbgNode::from(pos))?;
+    Ok(())
 }
 
 fn compile_bchkbitsq<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
@@ -359,9 +453,11 @@ fn compile_bchkbitsq<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destin
         destination.write_command(
             vec![0xCF, 0x3C, parse_const_u8_plus_one(par[0]).parameter("value")?].as_slice(),
             DbgNode::from(pos)
-        )
+        )?;
+        Ok(())
     } else {
-        destination.write_command(&[0xCF, 0x3D], DbgNode::from(pos))
+        destination.write_command(&[0xCF, 0x3D], DbgNode::from(pos))?;
+        Ok(())
     }
 }
 
@@ -374,15 +470,16 @@ fn compile_dumpstr<T: Writer>(
     pos: DbgPos,
 ) -> CompileResult {
     par.assert_len(1)?;
-    let string = parse_string(par[0]);
+    let string = parse_string(par[0]).parameter("arg 0")?;
     let string = string.as_slice();
     let len = string.len();
-    if len > max_len {
+    if len == 0 || len > max_len {
         return Err(ParameterError::OutOfRange.parameter(par[0]))
     }
     buffer[1] |= (len - 1 + 16 - max_len) as u8;
     buffer.extend_from_slice(string);
-    destination.write_command(buffer.as_slice(), DbgNode::from(pos))
+    destination.write_command(buffer.as_slice(), DbgNode::from(pos))?;
+    Ok(())
 }
 
 fn compile_dumptosfmt<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
@@ -400,12 +497,15 @@ fn compile_printstr<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destinat
 fn compile_stsliceconst<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos) -> CompileResult {
     par.assert_len(1)?;
     if par[0] == "0" {
-        destination.write_command(&[0xCF, 0x81], DbgNode::from(pos))
+        destination.write_command(&[0xCF, 0x81], DbgNode::from(pos))?;
+        Ok(())
     } else if par[0] == "1" {
-        destination.write_command(&[0xCF, 0x83], DbgNode::from(pos))
+        destination.write_command(&[0xCF, 0x83], DbgNode::from(pos))?;
+        Ok(())
     } else {
         let buffer = compile_slice(par[0], vec![0xCF, 0x80], 9, 2, 3).parameter("arg 0")?;
-        destination.write_command(buffer.as_slice(), DbgNode::from(pos))
+        destination.write_command(buffer.as_slice(), DbgNode::from(pos))?;
+        Ok(())
     }
 }
 
@@ -413,10 +513,31 @@ fn compile_pushslice<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destin
 -> CompileResult {
     par.assert_len(1)?;
     let buffer = match compile_slice(par[0], vec![0x8B, 0], 8, 0, 4) {
-        Ok(buffer) => buffer,
-        Err(_) => compile_slice(par[0], vec![0x8D, 0], 8, 3, 7).parameter("arg 0")?
+        Ok(buffer) => Ok(buffer),
+        Err(_) => compile_slice(par[0], vec![0x8D, 0], 8, 3, 7)
     };
-    destination.write_command(buffer.as_slice(), DbgNode::from(pos))
+    match buffer {
+        Ok(buffer) if buffer.len() * 8 <= MAX_DATA_BITS => {
+            destination.write_command(buffer.as_slice(), DbgNode::from(pos))?;
+        }
+        Ok(_) | Err(ParameterError::OutOfRange) => {
+            // too long for inline encodings: promote to PUSHREFSLICE
+            let data = parse_slice(par[0], 0).parameter("arg 0")?;
+            let slice = bitstring_builder(data).ok_or_else(|| ParameterError::OutOfRange.parameter("arg 0"))?;
+            destination.write_composite_command(&[0x89], slice, pos, DbgNode::new())?;
+        }
+        Err(e) => return Err(e.parameter("arg 0"))
+    }
+    Ok(())
+}
+
+/// Builder of bits terminated with completion tag, None if they do not fit in a cell
+fn bitstring_builder(mut data: Vec<u8>) -> Option<BuilderData> {
+    while data.last() == Some(&0) {
+        data.pop();
+    }
+    let bits = data.len() * 8 - data.last()?.trailing_zeros() as usize - 1;
+    BuilderData::with_raw(data, bits).ok()
 }
 
 #[allow(dead_code)]
@@ -458,7 +579,8 @@ fn compile_xchg<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination
 -> CompileResult {
     par.assert_len_in(0..=2)?;
     if par.len() == 0 {
-        destination.write_command(&[0x01], DbgNode::from(pos))
+        destination.write_command(&[0x01], DbgNode::from(pos))?;
+        Ok(())
     } else if par.len() == 1 {
         compile_with_register(par[0], 'S', 1..16, &[0x00], destination, pos)
     } else {
@@ -472,13 +594,16 @@ fn compile_xchg<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination
         } else if reg1 == 0 {
             if reg2 <= 15 {
                 // XCHG s0, si == XCHG si
-                destination.write_command(&[reg2 as u8], DbgNode::from(pos))
+                destination.write_command(&[reg2 as u8], DbgNode::from(pos))?;
+                Ok(())
             } else {
-                destination.write_command(&[0x11, reg2 as u8], DbgNode::from(pos))
+                destination.write_command(&[0x11, reg2 as u8], DbgNode::from(pos))?;
+                Ok(())
             }
         } else if reg1 == 1 {
             if (reg2 >= 2) && (reg2 <= 15) {
-                destination.write_command(&[0x10 | reg2 as u8], DbgNode::from(pos))
+                destination.write_command(&[0x10 | reg2 as u8], DbgNode::from(pos))?;
+                Ok(())
             } else {
                 Err(ParameterError::OutOfRange.parameter("Register 2"))
             }
@@ -486,7 +611,8 @@ fn compile_xchg<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination
             if reg2 > 15 {
                 Err(ParameterError::OutOfRange.parameter("Register 2"))
             } else {
-                destination.write_command(&[0x10, (((reg1 << 4) & 0xF0) | (reg2 & 0x0F)) as u8], DbgNode::from(pos))
+                destination.write_command(&[0x10, (((reg1 << 4) & 0xF0) | (reg2 & 0x0F)) as u8], DbgNode::from(pos))?;
+                Ok(())
             }
         }
     }
@@ -507,7 +633,8 @@ fn compile_throw_helper<T: Writer>(par: &Vec<&str>, short_opcode: u8, long_opcod
         } else {
             Err(ParameterError::OutOfRange.parameter("Number"))
         }
-    }?.as_slice(), DbgNode::from(pos))
+    }?.as_slice(), DbgNode::from(pos))?;
+    Ok(())
 }
 
 pub(super) fn compile_slice(par: &str, mut prefix: Vec<u8>, offset: usize, r: usize, x: usize)
@@ -539,12 +666,15 @@ fn compile_sdbegins<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destina
     par.assert_len(1)?;
     // Regular version have special two aliaces: SDBEGINS '0', SDBEGINS '1'
     if par[0] == "0" {
-        destination.write_command(&[0xD7, 0x28, 0x02], DbgNode::from(pos))
+        destination.write_command(&[0xD7, 0x28, 0x02], DbgNode::from(pos))?;
+        Ok(())
     } else if par[0] == "1" {
-        destination.write_command(&[0xD7, 0x28, 0x06], DbgNode::from(pos))
+        destination.write_command(&[0xD7, 0x28, 0x06], DbgNode::from(pos))?;
+        Ok(())
     } else {
         let buffer = compile_slice(par[0], vec![0xD7, 0x28], 14, 0, 7).parameter("arg 0")?;
-        destination.write_command(buffer.as_slice(), DbgNode::from(pos))
+        destination.write_command(buffer.as_slice(), DbgNode::from(pos))?;
+        Ok(())
     }
 }
 
@@ -552,7 +682,8 @@ fn compile_sdbeginsq<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destin
 -> CompileResult {
     par.assert_len(1)?;
     let buffer = compile_slice(par[0], vec![0xD7, 0x2C], 14, 0, 7).parameter("arg 0")?;
-    destination.write_command(buffer.as_slice(), DbgNode::from(pos))
+    destination.write_command(buffer.as_slice(), DbgNode::from(pos))?;
+    Ok(())
 }
 
 fn compile_throw<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos)
@@ -572,20 +703,197 @@ fn compile_throwifnot<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, desti
 
 fn compile_blob<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos)
 -> CompileResult {
-    let data = par[0];
-    if !data.to_ascii_lowercase().starts_with('x') {
-        return Err(ParameterError::UnexpectedType.parameter("parameter"))
+    par.assert_len(1)?;
+    let data = parse_slice(par[0], 0).parameter("parameter")?;
+    let blob = bitstring_builder(data).ok_or_else(|| ParameterError::OutOfRange.parameter("parameter"))?;
+    destination.write_command_bitstring(blob.data(), blob.length_in_bits(), DbgNode::from(pos))?;
+    Ok(())
+}
+
+fn compile_byte<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos)
+-> CompileResult {
+    par.assert_len_in(1..=MAX_DATA_BITS / 8)?;
+    let data = par.iter().enumerate()
+        .map(|(i, byte)| parse_const_u8(byte).parameter(&format!("arg {}", i)))
+        .collect::<Result<Vec<_>, _>>()?;
+    destination.write_command_bitstring(&data, data.len() * 8, DbgNode::from(pos))?;
+    Ok(())
+}
+
+/// Bits of the width given by integer or slice literal of exactly that width
+fn parse_bits(par: &str, width: usize) -> Result<Vec<u8>, ParameterError> {
+    if !par.starts_with(['x', 'X', 'b', 'B']) {
+        return parse_integer_bits(par, width)
+    }
+    match bitstring_builder(parse_slice(par, 0)?) {
+        Some(bits) if bits.length_in_bits() == width => Ok(bits.data().to_vec()),
+        _ => Err(ParameterError::OutOfRange)
+    }
+}
+
+fn compile_bits<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos)
+-> CompileResult {
+    par.assert_len(2)?;
+    let width = parse_const_u10(par[0]).parameter("arg 0")? as usize;
+    let data = parse_bits(par[1], width).parameter("arg 1")?;
+    destination.write_command_bitstring(&data, width, DbgNode::from(pos))?;
+    Ok(())
+}
+
+/// `DICTPUSHCONST { key => { ... } ... }, n` pushes dictionary with n-bit keys built of the entries,
+/// every value holds the code or data of its block in a reference
+fn compile_dictpushconst<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos)
+-> CompileResult {
+    par.assert_len_in(1..=2)?;
+    let width = parse_const_u10(par[par.len() - 1]).parameter(&format!("arg {}", par.len() - 1))?;
+    let command = [0xF4, 0xA4 | (width >> 8) as u8, width as u8];
+    if par.len() == 1 {
+        // the dictionary is the next reference
+        destination.write_command(&command, DbgNode::from(pos))?;
+        return Ok(())
+    }
+    let entries = parse_dictionary(par[0]).parameter("arg 0")?;
+    let mut dict = HashmapE::with_bit_len(width as usize);
+    let mut bodies = HashMap::new();
+    let (line, column) = (engine.line_no, engine.char_no);
+    for (key, offset, block) in entries {
+        let key_bits = parse_bits(key, width as usize).parameter(key)?;
+        let key_bits = SliceData::from_raw(key_bits, width as usize);
+        if dict.get(key_bits.clone()).ok().flatten().is_some() {
+            return Err(OperationError::LogicErrorInParameters("duplicate dictionary key"))
+        }
+        // the block is compiled at its position in the source
+        let before = &par[0][..offset];
+        let (block_line, block_column) = match before.rfind('\n') {
+            Some(n) => (line + before.matches('\n').count(), 1 + before[n + 1..].chars().count()),
+            None => (line, column + before.chars().count())
+        };
+        engine.set_pos(block_line, block_column);
+        let compiled = engine.compile(block);
+        engine.set_pos(line, column);
+        let (body, dbg) = compiled.map_err(|e| OperationError::Nested(Box::new(e)))?.finalize()?;
+        let body = body.into_cell().map_err(|_| OperationError::NotFitInSlice)?;
+        bodies.insert(body.repr_hash(), dbg);
+        let mut value = BuilderData::new();
+        value.append_reference_cell(body);
+        let value = value.into_cell().map_err(|_| OperationError::NotFitInSlice)?;
+        dict.set(key_bits, &value.into()).map_err(|_| OperationError::NotFitInSlice)?;
+    }
+    let root = dict.data().ok_or(OperationError::LogicErrorInParameters("dictionary is empty"))?;
+    let dbg = dictionary_dbg(root, &bodies);
+    destination.write_composite_command(&command, BuilderData::from(root), pos, dbg)?;
+    Ok(())
+}
+
+/// Hashes of cells to prune given by the parameters
+fn prune_set(par: &[&str]) -> Result<Vec<UInt256>, OperationError> {
+    par.iter().enumerate()
+        .map(|(i, hash)| parse_hash(hash).ok_or_else(|| ParameterError::UnexpectedType.parameter(&format!("arg {}", i))))
+        .collect()
+}
+
+/// Copy of the cell with subtrees of the hashes replaced by pruned branch cells,
+/// hashes pruned are collected
+fn prune(cell: &Cell, hashes: &[UInt256], pruned: &mut HashSet<UInt256>) -> Result<Cell, OperationError> {
+    let hash = cell.repr_hash();
+    let builder = if hashes.contains(&hash) {
+        pruned.insert(hash);
+        let mut data = vec![1, 1];
+        data.extend_from_slice(hash.as_slice());
+        data.extend_from_slice(&cell.repr_depth().to_be_bytes());
+        let mut builder = BuilderData::with_raw(data, 8 + 8 + 256 + 16).map_err(|_| OperationError::NotFitInSlice)?;
+        builder.set_type(CellType::PrunedBranch);
+        builder
+    } else {
+        let references = (0..cell.references_count())
+            .map(|i| cell.reference(i).map_err(|_| OperationError::NotFitInSlice).and_then(|child| prune(&child, hashes, pruned)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut builder = BuilderData::with_raw_and_refs(cell.data().to_vec(), cell.bit_length(), references)
+            .map_err(|_| OperationError::NotFitInSlice)?;
+        builder.set_type(cell.cell_type());
+        builder
+    };
+    builder.into_cell().map_err(|_| OperationError::NotFitInSlice)
+}
+
+/// Cell described by the block
+fn block_cell<T: Writer>(engine: &mut Engine<T>, block: &str) -> Result<Cell, OperationError> {
+    let (builder, _) = engine
+        .compile(block)
+        .map_err(|e| OperationError::Nested(Box::new(e)))?
+        .finalize()?;
+    builder.into_cell().map_err(|_| OperationError::NotFitInSlice)
+}
+
+/// `.merkleproof hash, ... { ... }` appends Merkle proof of the cell described by the block
+/// with subtrees of the hashes pruned
+fn compile_merkleproof<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos)
+-> CompileResult {
+    if engine.line_no == 0 && engine.char_no == 0 {
+        return Err(OperationError::MissingBlock)
+    }
+    par.assert_len_in(1..=usize::MAX)?;
+    let hashes = prune_set(&par[..par.len() - 1])?;
+    let cell = block_cell(engine, par[par.len() - 1])?;
+    let mut pruned = HashSet::new();
+    let proof = prune(&cell, &hashes, &mut pruned)?;
+    if hashes.iter().any(|hash| !pruned.contains(hash)) {
+        return Err(OperationError::LogicErrorInParameters("cell to prune is not found"))
     }
-    let res = SliceData::from_string(&data[1..]);
-    if res.is_err() {
-        return Err(ParameterError::UnexpectedType.parameter("parameter"))
+    let mut data = vec![3];
+    data.extend_from_slice(cell.repr_hash().as_slice());
+    data.extend_from_slice(&cell.repr_depth().to_be_bytes());
+    let mut builder = BuilderData::with_raw_and_refs(data, 8 + 256 + 16, vec![proof])
+        .map_err(|_| OperationError::NotFitInSlice)?;
+    builder.set_type(CellType::MerkleProof);
+    destination.write_composite_command(&[], builder, pos, DbgNode::new())?;
+    Ok(())
+}
+
+/// `.merkleupdate hash, ... { ... } { ... }` appends Merkle update from the old cell
+/// to the new one described by the blocks with subtrees of the hashes pruned in both
+fn compile_merkleupdate<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos)
+-> CompileResult {
+    if engine.line_no == 0 && engine.char_no == 0 {
+        return Err(OperationError::MissingBlock)
     }
-    let slice = res.unwrap();
-    destination.write_command_bitstring(slice.storage(), slice.remaining_bits(), DbgNode::from(pos))
+    par.assert_len_in(2..=usize::MAX)?;
+    let hashes = prune_set(&par[..par.len() - 2])?;
+    let old = block_cell(engine, par[par.len() - 2])?;
+    let new = block_cell(engine, par[par.len() - 1])?;
+    let mut pruned = HashSet::new();
+    let old_pruned = prune(&old, &hashes, &mut pruned)?;
+    let new_pruned = prune(&new, &hashes, &mut pruned)?;
+    if hashes.iter().any(|hash| !pruned.contains(hash)) {
+        return Err(OperationError::LogicErrorInParameters("cell to prune is not found"))
+    }
+    let mut data = vec![4];
+    data.extend_from_slice(old.repr_hash().as_slice());
+    data.extend_from_slice(new.repr_hash().as_slice());
+    data.extend_from_slice(&old.repr_depth().to_be_bytes());
+    data.extend_from_slice(&new.repr_depth().to_be_bytes());
+    let mut builder = BuilderData::with_raw_and_refs(data, 8 + 2 * 256 + 2 * 16, vec![old_pruned, new_pruned])
+        .map_err(|_| OperationError::NotFitInSlice)?;
+    builder.set_type(CellType::MerkleUpdate);
+    destination.write_composite_command(&[], builder, pos, DbgNode::new())?;
+    Ok(())
+}
+
+fn compile_library<T: Writer>(_engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos)
+-> CompileResult {
+    par.assert_len(1)?;
+    let cell = library_cell(par[0])?;
+    destination.write_composite_command(&[], BuilderData::from(&cell), pos, DbgNode::new())?;
+    Ok(())
 }
 
 fn compile_cell<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination: &mut T, pos: DbgPos)
 -> CompileResult {
+    if let Some(cell) = par.first().and_then(|par| cell_operand(par, &pos, &engine.options.include_dirs)) {
+        par.assert_len(1)?;
+        destination.write_composite_command(&[], BuilderData::from(&cell?), pos, DbgNode::new())?;
+        return Ok(())
+    }
     if engine.line_no == 0 && engine.char_no == 0 {
         return Err(OperationError::MissingBlock)
     }
@@ -593,8 +901,9 @@ fn compile_cell<T: Writer>(engine: &mut Engine<T>, par: &Vec<&str>, destination:
     let (cont, dbg) = engine
         .compile(par[0])
         .map_err(|e| OperationError::Nested(Box::new(e)))?
-        .finalize();
-    destination.write_composite_command(&[], cont, pos, dbg)
+        .finalize()?;
+    destination.write_composite_command(&[], cont, pos, dbg)?;
+    Ok(())
 }
 
 // Compilation engine *********************************************************
@@ -605,14 +914,6 @@ impl<T: Writer> Engine<T> {
     #[cfg_attr(rustfmt, rustfmt_skip)]
     pub fn add_complex_commands(&mut self) {
         // Alphabetically sorted
-        self.COMPILE_ROOT.insert("-ROLL",          Engine::ROLLREV);
-        self.COMPILE_ROOT.insert("-ROLLX",         Engine::ROLLREVX);
-        self.COMPILE_ROOT.insert("-ROT",           Engine::ROTREV);
-        self.COMPILE_ROOT.insert("2DROP",          Engine::DROP2);
-        self.COMPILE_ROOT.insert("2DUP",           Engine::DUP2);
-        self.COMPILE_ROOT.insert("2OVER",          Engine::OVER2);
-        self.COMPILE_ROOT.insert("2ROT",           Engine::ROT2);
-        self.COMPILE_ROOT.insert("2SWAP",          Engine::SWAP2);
         self.COMPILE_ROOT.insert("CALL",           compile_call);
         self.COMPILE_ROOT.insert("CALLDICT",       compile_call);
         self.COMPILE_ROOT.insert("CALLREF",        compile_callref);
@@ -621,13 +922,14 @@ impl<T: Writer> Engine<T> {
         self.COMPILE_ROOT.insert("BCHKBITSQ",      compile_bchkbitsq);
         self.COMPILE_ROOT.insert("DEBUGSTR",       compile_dumptosfmt);
         self.COMPILE_ROOT.insert("DUMPTOSFMT",     compile_dumptosfmt);
+        self.COMPILE_ROOT.insert("IFBITJMPREF",    compile_ifbitjmpref);
+        self.COMPILE_ROOT.insert("IFNBITJMPREF",   compile_ifnbitjmpref);
         self.COMPILE_ROOT.insert("IFREF",          compile_ifref);
         self.COMPILE_ROOT.insert("IFNOTREF",       compile_ifnotref);
         self.COMPILE_ROOT.insert("IFJMPREF",       compile_ifjmpref);
         self.COMPILE_ROOT.insert("IFNOTJMPREF",    compile_ifnotjmpref);
         self.COMPILE_ROOT.insert("IFREFELSE",      compile_ifrefelse);
         self.COMPILE_ROOT.insert("IFELSEREF",      compile_ifelseref);
-        self.COMPILE_ROOT.insert("JMPDICT",        Engine::JMP);
         self.COMPILE_ROOT.insert("JMPREF",         compile_jmpref);
         self.COMPILE_ROOT.insert("LOGSTR",         compile_logstr);
         self.COMPILE_ROOT.insert("LSHIFT",         Div::<Signaling>::lshift);
@@ -655,6 +957,7 @@ impl<T: Writer> Engine<T> {
         self.COMPILE_ROOT.insert("POP",            compile_pop);
         self.COMPILE_ROOT.insert("PRINTSTR",       compile_printstr);
         self.COMPILE_ROOT.insert("PUSH",           compile_push);
+        self.COMPILE_ROOT.insert("DICTPUSHCONST",  compile_dictpushconst);
         self.COMPILE_ROOT.insert("PUSHCONT",       compile_pushcont);
         self.COMPILE_ROOT.insert("PUSHINT",        compile_pushint);
         self.COMPILE_ROOT.insert("PUSHREF",        compile_pushref);
@@ -662,6 +965,7 @@ impl<T: Writer> Engine<T> {
         self.COMPILE_ROOT.insert("PUSHSLICE",      compile_pushslice);
         self.COMPILE_ROOT.insert("PUSHREFSLICE",   compile_pushrefslice);
         self.COMPILE_ROOT.insert("SETCONTARGS",    compile_setcontargs);
+        self.COMPILE_ROOT.insert("STREFCONST",     compile_strefconst);
         self.COMPILE_ROOT.insert("SWAP",           compile_xchg);
         self.COMPILE_ROOT.insert("QLSHIFT",        Div::<Quiet>::lshift);
         self.COMPILE_ROOT.insert("QLSHIFTDIV",     Div::<Quiet>::lshiftdiv);
@@ -707,6 +1011,12 @@ impl<T: Writer> Engine<T> {
         self.COMPILE_ROOT.insert("XCHG",           compile_xchg);
         // Add automatic commands
         self.COMPILE_ROOT.insert(".BLOB",          compile_blob);
+        self.COMPILE_ROOT.insert(".BYTE",          compile_byte);
+        self.COMPILE_ROOT.insert(".BITS",          compile_bits);
         self.COMPILE_ROOT.insert(".CELL",          compile_cell);
+        self.COMPILE_ROOT.insert(".REF",           compile_cell);
+        self.COMPILE_ROOT.insert(".LIBRARY",       compile_library);
+        self.COMPILE_ROOT.insert(".MERKLEPROOF",   compile_merkleproof);
+        self.COMPILE_ROOT.insert(".MERKLEUPDATE",  compile_merkleupdate);
     }
 }
@@ -82,6 +82,36 @@ pub fn to_big_endian_octet_string(value: &BigInt) -> Option<Vec<u8>> {
     Some(ret)
 }
 
+/// Exponent k of value equal to 2^k
+fn pow2_exponent(value: &BigInt) -> Option<usize> {
+    if value.sign() != Sign::Plus {
+        return None
+    }
+    let k = value.bits() - 1;
+    if value == &(BigInt::from(1) << k) {
+        Some(k)
+    } else {
+        None
+    }
+}
+
+/// Encodes value as PUSHPOW2 (2^(xx+1)), PUSHPOW2DEC (2^(xx+1) - 1) or PUSHNEGPOW2 (-2^(xx+1))
+/// if it has such form, 0x83FF is PUSHNAN
+pub fn to_pow2_command(value: &BigInt) -> Option<Vec<u8>> {
+    let one = BigInt::from(1);
+    let (code, k) = if let Some(k) = pow2_exponent(value).filter(|k| *k < 256) {
+        (0x83, k)
+    } else if let Some(k) = pow2_exponent(&(value + &one)) {
+        (0x84, k)
+    } else {
+        (0x85, pow2_exponent(&-value)?)
+    };
+    match k {
+        1..=256 => Some(vec![code, (k - 1) as u8]),
+        _ => None
+    }
+}
+
 // /// Constructs new BigInt value from the little-endian slice of u32
 // /// with overflow checking.
 // #[inline]
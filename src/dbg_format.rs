@@ -0,0 +1,127 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+// Binary format of debug info, numbers are LEB128 encoded:
+//   magic, version,
+//   file names: count, then length and UTF-8 bytes of each,
+//   cells: count, then for each cell its hash and count of entries,
+//     for each entry: bit offset, file name index, line, column, span start and end
+
+use std::{collections::{BTreeMap, HashMap}, io::{Error, ErrorKind, Read, Result, Write}, sync::Arc};
+use ton_types::UInt256;
+
+use crate::{DbgInfo, DbgPos};
+
+const MAGIC: &[u8; 4] = b"TDBG";
+const VERSION: u8 = 1;
+
+fn write_number<W: Write>(writer: &mut W, mut value: usize) -> Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte])
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_number<R: Read>(reader: &mut R) -> Result<usize> {
+    let mut value = 0usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7F) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value)
+        }
+    }
+    Err(invalid("number is too long"))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("bad debug info: {}", message))
+}
+
+impl DbgInfo {
+    /// Writes debug info in compact binary format
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut files = HashMap::new();
+        let mut names = Vec::new();
+        for pos in self.map.values().flat_map(|offsets| offsets.values()) {
+            if !files.contains_key(&*pos.filename) {
+                files.insert(&*pos.filename, names.len());
+                names.push(&*pos.filename);
+            }
+        }
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        write_number(writer, names.len())?;
+        for name in names {
+            write_number(writer, name.len())?;
+            writer.write_all(name.as_bytes())?;
+        }
+        write_number(writer, self.map.len())?;
+        for (hash, offsets) in &self.map {
+            writer.write_all(hash.as_slice())?;
+            write_number(writer, offsets.len())?;
+            for (offset, pos) in offsets {
+                write_number(writer, *offset)?;
+                write_number(writer, files[&*pos.filename])?;
+                write_number(writer, pos.line)?;
+                write_number(writer, pos.column)?;
+                write_number(writer, pos.span.start)?;
+                write_number(writer, pos.span.end)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads debug info written by `write_to`
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("wrong magic"))
+        }
+        if header[4] != VERSION {
+            return Err(invalid(&format!("unsupported version {}", header[4])))
+        }
+        let mut names = Vec::new();
+        for _ in 0..read_number(reader)? {
+            let len = read_number(reader)?;
+            let mut name = Vec::new();
+            reader.take(len as u64).read_to_end(&mut name)?;
+            if name.len() != len {
+                return Err(Error::from(ErrorKind::UnexpectedEof))
+            }
+            names.push(Arc::<str>::from(String::from_utf8(name).map_err(|_| invalid("file name is not UTF-8"))?));
+        }
+        let mut info = DbgInfo::new();
+        for _ in 0..read_number(reader)? {
+            let mut hash = [0u8; 32];
+            reader.read_exact(&mut hash)?;
+            let mut offsets = BTreeMap::new();
+            for _ in 0..read_number(reader)? {
+                let offset = read_number(reader)?;
+                let filename = names.get(read_number(reader)?).ok_or_else(|| invalid("wrong file name index"))?.clone();
+                let line = read_number(reader)?;
+                let column = read_number(reader)?;
+                let span = read_number(reader)?..read_number(reader)?;
+                offsets.insert(offset, DbgPos { filename, line, line_code: 0, column, span });
+            }
+            info.map.insert(UInt256::from_slice(&hash), offsets);
+        }
+        Ok(info)
+    }
+}
@@ -11,10 +11,16 @@
 * limitations under the License.
 */
 
+#[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
-use std::collections::BTreeMap;
+use std::{collections::{BTreeMap, HashMap}, ops::Range, sync::{Arc, OnceLock}};
 use ton_types::{Cell, UInt256};
 
+use crate::OperationError;
+
+// a node has a child per reference of its cell
+pub(crate) const MAX_CHILDREN: usize = 4;
+
 pub type Lines = Vec<Line>;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Line {
@@ -24,15 +30,19 @@ pub struct Line {
 
 impl Line {
     pub fn new(text: &str, filename: &str, line: usize) -> Self {
+        Self::new_shared(text, &Arc::from(filename), line)
+    }
+    pub fn new_extended(text: &str, filename: &str, line: usize, line_code: usize) -> Self {
         Line {
             text: String::from(text),
-            pos: DbgPos { filename: String::from(filename), line, line_code: line }
+            pos: DbgPos { filename: Arc::from(filename), line, line_code, column: 0, span: 0..0 }
         }
     }
-    pub fn new_extended(text: &str, filename: &str, line: usize, line_code: usize) -> Self {
+    /// Line sharing the filename with other lines of the file
+    pub fn new_shared(text: &str, filename: &Arc<str>, line: usize) -> Self {
         Line {
             text: String::from(text),
-            pos: DbgPos { filename: String::from(filename), line, line_code }
+            pos: DbgPos { filename: filename.clone(), line, line_code: line, column: 0, span: 0..0 }
         }
     }
 }
@@ -43,12 +53,20 @@ pub fn lines_to_string(lines: &Lines) -> String {
         .fold(String::new(), |result, line| result + line.text.as_str())
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DbgPos {
-    pub filename: String,
+    /// shared by positions of the file, serialized as a string
+    pub filename: Arc<str>,
     pub line: usize,
-    #[serde(skip)]
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub line_code: usize,
+    /// column of the command, 0 if unknown
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub column: usize,
+    /// byte offsets of the command within its line
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub span: Range<usize>,
 }
 
 impl std::fmt::Display for DbgPos {
@@ -56,18 +74,24 @@ impl std::fmt::Display for DbgPos {
         let filename = if self.filename.is_empty() {
             "<none>"
         } else {
-            self.filename.as_str()
+            &self.filename
         };
         write!(f, "{}:{}", filename, self.line)
     }
 }
 
+/// Empty filename of positions without file, allocated once
+pub(crate) fn no_filename() -> Arc<str> {
+    static EMPTY: OnceLock<Arc<str>> = OnceLock::new();
+    EMPTY.get_or_init(|| Arc::from("")).clone()
+}
+
 impl Default for DbgPos {
     fn default() -> Self {
-        Self { filename: String::new(), line: 0, line_code: 0 }
+        Self { filename: no_filename(), line: 0, line_code: 0, column: 0, span: 0..0 }
     }
 }
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct DbgNode {
     pub offsets: BTreeMap<usize, DbgPos>,
     pub children: Vec<DbgNode>,
@@ -88,6 +112,25 @@ impl DbgNode {
     pub fn append(self: &mut Self, offset: usize, pos: DbgPos) {
         self.offsets.insert(offset, pos);
     }
+    /// Binds position to the offset failing if it is bound already
+    pub fn checked_append(&mut self, offset: usize, pos: DbgPos) -> Result<(), OperationError> {
+        if self.offsets.contains_key(&offset) {
+            return Err(OperationError::DebugOffsetClash(offset))
+        }
+        self.offsets.insert(offset, pos);
+        Ok(())
+    }
+    /// Inlines node at the offset failing without changes if any of its offsets is bound already
+    pub fn checked_inline_node(&mut self, offset: usize, dbg: DbgNode) -> Result<(), OperationError> {
+        if self.children.len() + dbg.children.len() > MAX_CHILDREN {
+            return Err(OperationError::Internal("debug node has more children than cell references"))
+        }
+        if let Some(clash) = dbg.offsets.keys().map(|key| key + offset).find(|key| self.offsets.contains_key(key)) {
+            return Err(OperationError::DebugOffsetClash(clash))
+        }
+        self.inline_node(offset, dbg);
+        Ok(())
+    }
     pub fn inline_node(self: &mut Self, offset: usize, dbg: DbgNode) {
         for entry in dbg.offsets {
             self.offsets.insert(entry.0 + offset, entry.1);
@@ -97,9 +140,16 @@ impl DbgNode {
         }
     }
     pub fn append_node(self: &mut Self, dbg: DbgNode) {
-        assert!(self.children.len() <= 4);
         self.children.push(dbg)
     }
+    /// Appends node of the next reference failing without changes if the cell has no more ones
+    pub fn checked_append_node(&mut self, dbg: DbgNode) -> Result<(), OperationError> {
+        if self.children.len() >= MAX_CHILDREN {
+            return Err(OperationError::Internal("debug node has more children than cell references"))
+        }
+        self.children.push(dbg);
+        Ok(())
+    }
 }
 
 impl std::fmt::Display for DbgNode {
@@ -111,19 +161,44 @@ impl std::fmt::Display for DbgNode {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Keys are serialized as hex strings
+#[cfg(feature = "serde")]
+pub(crate) mod hash_keys {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+    use std::collections::BTreeMap;
+    use ton_types::UInt256;
+
+    pub fn serialize<V: Serialize, S: Serializer>(map: &BTreeMap<UInt256, V>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(key, value)| (key.to_hex_string(), value)))
+    }
+
+    pub fn deserialize<'de, V: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<UInt256, V>, D::Error> {
+        let map = BTreeMap::<String, V>::deserialize(deserializer)?;
+        map.into_iter().map(|(key, value)| {
+            match hex::decode(&key) {
+                Ok(bytes) if bytes.len() == 32 => Ok((UInt256::from_slice(&bytes), value)),
+                _ => Err(D::Error::custom(format!("bad cell hash {}", key)))
+            }
+        }).collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DbgInfo {
-    pub map: BTreeMap<String, BTreeMap<usize, DbgPos>>
+    #[cfg_attr(feature = "serde", serde(with = "hash_keys"))]
+    pub map: BTreeMap<UInt256, BTreeMap<usize, DbgPos>>
 }
 
 impl DbgInfo {
     pub fn new() -> Self {
         DbgInfo { map: BTreeMap::new() }
     }
-    pub fn from(cell: &Cell, node: &DbgNode) -> Self {
+    /// Collects positions of the cell tree, missing child nodes give cells without positions
+    pub fn from(cell: &Cell, node: &DbgNode) -> ton_types::Result<Self> {
         let mut info = DbgInfo { map: BTreeMap::new() };
-        info.collect(&cell, &node);
-        info
+        info.collect(cell, node)?;
+        Ok(info)
     }
     pub fn len(&self) -> usize {
         self.map.len()
@@ -135,27 +210,55 @@ impl DbgInfo {
         self.map.append(&mut other.map);
     }
     pub fn insert(&mut self, key: UInt256, tree: BTreeMap<usize, DbgPos>) {
-        self.map.entry(key.to_hex_string()).or_insert(tree);
+        self.map.entry(key).or_insert(tree);
     }
     pub fn remove(&mut self, key: &UInt256) -> Option<BTreeMap<usize, DbgPos>> {
-        self.map.remove(&key.to_hex_string())
+        self.map.remove(key)
     }
     pub fn get(&self, key: &UInt256) -> Option<&BTreeMap<usize, DbgPos>> {
-        self.map.get(&key.to_hex_string())
+        self.map.get(key)
+    }
+    /// Position of the command covering the bit offset in the cell
+    pub fn find(&self, hash: &UInt256, offset: usize) -> Option<&DbgPos> {
+        self.map.get(hash)?.range(..=offset).next_back().map(|(_, pos)| pos)
+    }
+    /// Index of code positions by source lines
+    pub fn source_index(&self) -> SourceIndex {
+        let mut index = SourceIndex::default();
+        for (hash, offsets) in &self.map {
+            for (offset, pos) in offsets {
+                index.files.entry(pos.filename.to_string()).or_default()
+                    .entry(pos.line).or_default()
+                    .push((*hash, *offset));
+            }
+        }
+        index
     }
     pub fn first_entry(&self) -> Option<&BTreeMap<usize, DbgPos>> {
         self.map.iter().next().map(|k_v| k_v.1)
     }
-    fn collect(self: &mut Self, cell: &Cell, dbg: &DbgNode) {
-        let hash = cell.repr_hash().to_hex_string();
-        // note existence of identical cells in a tree is normal
-        if !self.map.contains_key(&hash) {
-            self.map.insert(hash, dbg.offsets.clone());
-        }
-        for i in 0..cell.references_count() {
-            let child_cell = cell.reference(i).unwrap();
-            let child_dbg = dbg.children[i].clone();
-            self.collect(&child_cell, &child_dbg);
+    fn collect(&mut self, cell: &Cell, dbg: &DbgNode) -> ton_types::Result<()> {
+        let empty = DbgNode::new();
+        let mut stack = vec![(cell.clone(), dbg)];
+        while let Some((cell, dbg)) = stack.pop() {
+            // note existence of identical cells in a tree is normal
+            self.map.entry(cell.repr_hash()).or_insert_with(|| dbg.offsets.clone());
+            for i in (0..cell.references_count()).rev() {
+                stack.push((cell.reference(i)?, dbg.children.get(i).unwrap_or(&empty)));
+            }
         }
+        Ok(())
+    }
+}
+
+/// Code positions as cell hash and bit offset of commands compiled from source lines
+#[derive(Debug, Default)]
+pub struct SourceIndex {
+    files: HashMap<String, BTreeMap<usize, Vec<(UInt256, usize)>>>,
+}
+
+impl SourceIndex {
+    pub fn locate(&self, filename: &str, line: usize) -> &[(UInt256, usize)] {
+        self.files.get(filename).and_then(|lines| lines.get(&line)).map(|positions| positions.as_slice()).unwrap_or_default()
     }
 }
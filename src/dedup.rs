@@ -0,0 +1,64 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::{HashMap, HashSet};
+use ton_types::{BuilderData, UInt256};
+
+/// Bits of a reference to a cell in a bag of cells, roughly
+const REFERENCE_BITS: usize = 8;
+/// Bits of descriptors of a cell in a bag of cells
+const CELL_BITS: usize = 16;
+
+/// Blocks of PUSHCONT numbered in order of compilation, which is the same every time
+/// the code is compiled. The first pass finds blocks written inline with identical code,
+/// the second one puts them into a single cell taken by PUSHREFCONT.
+#[derive(Default)]
+pub(crate) struct SharedBlocks {
+    next: usize,
+    // first pass: hash and bytes of blocks fitting inline
+    found: Vec<Option<(UInt256, usize)>>,
+    // second pass: numbers of blocks put into references
+    shared: Option<HashSet<usize>>,
+}
+
+impl SharedBlocks {
+    /// Notes the block compiled, returns whether it is to be put into a reference
+    pub(crate) fn block(&mut self, cont: &BuilderData) -> bool {
+        let number = self.next;
+        self.next += 1;
+        match self.shared.as_ref() {
+            Some(shared) => shared.contains(&number),
+            None => {
+                let inline = cont.references_used() == 0 && cont.data().len() <= 125;
+                let found = cont.clone().into_cell().ok().filter(|_| inline).map(|cell| (cell.repr_hash(), cont.data().len()));
+                self.found.push(found);
+                false
+            }
+        }
+    }
+    /// Blocks for the second pass: ones with code met several times, if references make the code smaller
+    pub(crate) fn share(self) -> Self {
+        let mut counts = HashMap::new();
+        for (hash, bytes) in self.found.iter().flatten() {
+            counts.entry(hash).or_insert((0, *bytes)).0 += 1;
+        }
+        let shared = self.found.iter().enumerate().filter_map(|(number, found)| {
+            let (count, bytes) = counts.get(&found.as_ref()?.0)?;
+            let opcode = if *bytes <= 15 { 8 } else { 16 };
+            let inline = count * (bytes * 8 + opcode);
+            let referenced = count * (8 + REFERENCE_BITS) + CELL_BITS + bytes * 8;
+            Some(number).filter(|_| *count > 1 && referenced < inline)
+        }).collect();
+        Self { next: 0, found: Vec::new(), shared: Some(shared) }
+    }
+}
@@ -0,0 +1,262 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::{collections::HashMap, fmt::{self, Write}};
+
+use super::{CompileError, CompileOptions, DeadCode, Lines, Position, StackWarning, StackWarningKind};
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Source texts by file name, unnamed source has empty name
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    files: HashMap<String, Vec<String>>,
+    colors: bool,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Source map of single unnamed text
+    pub fn from_code(code: &str) -> Self {
+        let mut map = Self::new();
+        map.add("", code);
+        map
+    }
+    pub fn add(&mut self, filename: &str, text: &str) -> &mut Self {
+        let lines = text.lines().map(String::from).collect();
+        self.files.insert(filename.to_string(), lines);
+        self
+    }
+    /// Enables ANSI colors in rendered diagnostics
+    pub fn with_colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+    pub fn line(&self, filename: &str, line: usize) -> Option<&str> {
+        let index = line.checked_sub(1)?;
+        self.files.get(filename)?.get(index).map(|text| text.as_str())
+    }
+    fn paint(&self, text: &str, color: &str) -> String {
+        match self.colors {
+            true => format!("{}{}{}", color, text, RESET),
+            false => text.to_string()
+        }
+    }
+}
+
+impl From<&Lines> for SourceMap {
+    fn from(lines: &Lines) -> Self {
+        let mut map = Self::new();
+        for line in lines {
+            let file = map.files.entry(line.pos.filename.to_string()).or_default();
+            let index = line.pos.line_code.saturating_sub(1);
+            if file.len() <= index {
+                file.resize(index + 1, String::new());
+            }
+            file[index] = line.text.trim_end_matches(&['\r', '\n'][..]).to_string();
+        }
+        map
+    }
+}
+
+impl CompileError {
+    pub fn position(&self) -> &Position {
+        match self {
+            CompileError::Syntax(position, _) => position,
+            CompileError::UnknownOperation(position, _, _) => position,
+            CompileError::Operation(position, _, _) => position,
+            CompileError::Denied(position, _, _) => position,
+        }
+    }
+
+    /// Error description without position
+    fn message(&self) -> String {
+        match self {
+            CompileError::Syntax(_, explanation) => format!("Syntax error: {}", explanation),
+            CompileError::UnknownOperation(_, name, suggestions) => {
+                let mut message = format!("Unknown operation {}", name);
+                if !suggestions.is_empty() {
+                    let _ = write!(message, ", did you mean {}?", suggestions.join(" or "));
+                }
+                message
+            }
+            CompileError::Operation(_, name, error) => format!("Instruction {}: {}", name, error),
+            CompileError::Denied(_, kind, explanation) => format!("{} [denied {}]", explanation, kind),
+        }
+    }
+
+    /// Renders error with the offending source line and a caret under its span
+    pub fn render(&self, source: &SourceMap) -> String {
+        render(source, Severity::Error, &self.message(), self.position())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Note => GREEN,
+            Severity::Warning => YELLOW,
+            Severity::Error => RED,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Analysis a diagnostic comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DiagnosticKind {
+    /// compilation failure
+    Error,
+    /// commands following unconditional RET, JMPX or THROW, see `find_dead_code`
+    UnreachableCode,
+    /// command taking more values than there are on the stack, see `check_stack`
+    StackUnderflow,
+    /// block breaking its declared stack signature
+    StackSignature,
+}
+
+impl DiagnosticKind {
+    /// Kinds of warnings
+    pub const WARNINGS: &'static [DiagnosticKind] = &[
+        DiagnosticKind::UnreachableCode,
+        DiagnosticKind::StackUnderflow,
+        DiagnosticKind::StackSignature,
+    ];
+    /// Name in kebab case, such as `unreachable-code`
+    pub fn name(self) -> &'static str {
+        match self {
+            DiagnosticKind::Error => "error",
+            DiagnosticKind::UnreachableCode => "unreachable-code",
+            DiagnosticKind::StackUnderflow => "stack-underflow",
+            DiagnosticKind::StackSignature => "stack-signature",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::WARNINGS.iter().copied().chain(std::iter::once(DiagnosticKind::Error)).find(|kind| kind.name() == name)
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Finding of compilation or of an analysis of the code, only errors are fatal
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub position: Position,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new<S: ToString>(severity: Severity, kind: DiagnosticKind, position: Position, message: S) -> Self {
+        Self { severity, kind, position, message: message.to_string() }
+    }
+    /// Warning of unreachable code, noting whether it is removed
+    pub(crate) fn dead_code(dead: DeadCode, removed: bool) -> Self {
+        let mut message = format!("Unreachable code: {} commands after {} up to line {}", dead.commands, dead.after, dead.end_line);
+        if removed {
+            message += ", removed";
+        }
+        Self::new(Severity::Warning, DiagnosticKind::UnreachableCode, dead.position, message)
+    }
+    /// Error of the warning if options deny its kind
+    pub(crate) fn denied(&self, options: &CompileOptions) -> Option<CompileError> {
+        match self.severity {
+            Severity::Error => None,
+            _ if options.deny.contains(&self.kind) => Some(CompileError::Denied(self.position.clone(), self.kind, self.message.clone())),
+            _ => None
+        }
+    }
+    /// Renders diagnostic with the source line and a caret under its span
+    pub fn render(&self, source: &SourceMap) -> String {
+        render(source, self.severity, &self.message, &self.position)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: {}", self.position, self.severity, self.message)
+    }
+}
+
+impl From<CompileError> for Diagnostic {
+    fn from(error: CompileError) -> Self {
+        let message = error.message();
+        Self::new(Severity::Error, DiagnosticKind::Error, error.position().clone(), message)
+    }
+}
+
+impl From<StackWarning> for Diagnostic {
+    fn from(warning: StackWarning) -> Self {
+        let (kind, message) = match warning.kind {
+            StackWarningKind::Underflow => (DiagnosticKind::StackUnderflow,
+                format!("Stack underflow: {} takes {} values while {} available", warning.command, warning.needed, warning.available)),
+            StackWarningKind::Signature => (DiagnosticKind::StackSignature,
+                format!("Stack signature mismatch: block leaves {} values while {} declared", warning.available, warning.needed)),
+        };
+        Self::new(Severity::Warning, kind, warning.position, message)
+    }
+}
+
+/// Renders message with the source line at the position and a caret under its span
+fn render(source: &SourceMap, severity: Severity, message: &str, position: &Position) -> String {
+    let color = severity.color();
+    let mut result = format!("{}{}\n", source.paint(&severity.to_string(), color), source.paint(&format!(": {}", message.trim_end()), BOLD));
+    let line = match source.line(&position.filename, position.line) {
+        Some(line) => line,
+        None => {
+            let _ = writeln!(result, "{} {}", source.paint("-->", BLUE), position);
+            return result
+        }
+    };
+    let number = position.line.to_string();
+    let gutter = " ".repeat(number.len());
+    let _ = writeln!(result, "{}{} {}", gutter, source.paint("-->", BLUE), position);
+    let _ = writeln!(result, "{} {}", gutter, source.paint("|", BLUE));
+    let _ = writeln!(result, "{} {}", source.paint(&format!("{} |", number), BLUE), line);
+    let (start, width) = match line.get(..position.span.start).zip(line.get(position.span.clone())) {
+        Some((prefix, token)) if !position.span.is_empty() => (prefix.to_string(), token.chars().count()),
+        _ => (line.chars().take(position.column.saturating_sub(1)).collect(), 1)
+    };
+    // keep tabs so that the caret is aligned with the line above
+    let indent = start.chars().map(|ch| if ch == '\t' { '\t' } else { ' ' }).collect::<String>();
+    let _ = writeln!(result, "{} {} {}", gutter, source.paint("|", BLUE), indent + &source.paint(&"^".repeat(width), color));
+    result
+}
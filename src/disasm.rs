@@ -0,0 +1,508 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use num::BigInt;
+use std::{collections::HashMap, fmt::Write, sync::OnceLock};
+use ton_types::{BuilderData, Cell, SliceData, UInt256};
+
+use super::{
+    DbgInfo, Engine, OperationError, DbgPos,
+    debug::DbgNode,
+    info::{ALIASES, instructions_info},
+    instruction::{Instruction, Operand, instructions_to_string},
+    writer::{Writer, Written},
+};
+
+// longest fixed length encoding in bytes
+const MAX_FIXED_LEN: usize = 4;
+
+/// Writer capturing the bytecode of a single plain command
+struct Capture {
+    code: Vec<u8>,
+    plain: bool,
+}
+
+impl Writer for Capture {
+    fn new() -> Self {
+        Self { code: vec![], plain: true }
+    }
+    fn write_command(&mut self, command: &[u8], _dbg: DbgNode) -> Result<Written, OperationError> {
+        let offset = self.code.len() * 8;
+        self.code.extend_from_slice(command);
+        Ok(Written { cell: 0, offset, bits: command.len() * 8 })
+    }
+    fn write_command_bitstring(&mut self, _command: &[u8], _bits: usize, _dbg: DbgNode) -> Result<Written, OperationError> {
+        self.plain = false;
+        Ok(Written::default())
+    }
+    fn write_composite_command(&mut self, _code: &[u8], _reference: BuilderData, _pos: DbgPos, _dbg: DbgNode) -> Result<Written, OperationError> {
+        self.plain = false;
+        Ok(Written::default())
+    }
+    fn finalize(self) -> Result<(BuilderData, DbgNode), OperationError> {
+        Ok((BuilderData::new(), DbgNode::new()))
+    }
+}
+
+/// Enumerates values of an operand kind as described by `InstructionInfo`,
+/// None for kinds with too many values or not encoded in place
+fn operand_domain(kind: &str) -> Option<Vec<(String, Operand)>> {
+    let integers = |range: std::ops::RangeInclusive<i64>| {
+        range.map(|i| (i.to_string(), Operand::Integer(i.into()))).collect::<Vec<_>>()
+    };
+    let registers = |prefix: &str, range: std::ops::RangeInclusive<isize>| {
+        range.map(|i| (format!("{}{}", prefix, i), match prefix {
+            "c" => Operand::ControlRegister(i as u8),
+            _ => Operand::StackRegister(i),
+        })).collect::<Vec<_>>()
+    };
+    let range = |text: &str| {
+        let mut bounds = text.splitn(2, "..");
+        let start = bounds.next()?.parse::<i64>().ok()?;
+        let end = bounds.next()?.parse::<i64>().ok()?;
+        Some(start..=end)
+    };
+    match kind {
+        "s(i)" => Some(registers("s", 0..=15)),
+        "c(i)" => Some(registers("c", 0..=15)),
+        _ if kind.starts_with("s(") && kind.ends_with(')') => {
+            let inner = &kind[2..kind.len() - 1];
+            match range(inner) {
+                Some(r) => Some(registers("s", *r.start() as isize..=*r.end() as isize)),
+                None => inner.parse::<isize>().ok().map(|i| registers("s", i..=i))
+            }
+        }
+        _ if kind.starts_with('u') => kind[1..].parse::<u32>().ok()
+            .filter(|bits| *bits <= 14)
+            .map(|bits| integers(0..=(1 << bits) - 1)),
+        _ if kind.starts_with('i') => kind[1..].parse::<u32>().ok()
+            .filter(|bits| *bits <= 8)
+            .map(|bits| integers(-(1 << (bits - 1))..=(1 << (bits - 1)) - 1)),
+        _ => range(kind).map(integers)
+    }
+}
+
+fn cartesian(domains: &[Vec<(String, Operand)>]) -> Vec<Vec<(String, Operand)>> {
+    domains.iter().fold(vec![vec![]], |acc, domain| {
+        acc.iter().flat_map(|prefix| domain.iter().map(move |value| {
+            let mut next = prefix.clone();
+            next.push(value.clone());
+            next
+        })).collect()
+    })
+}
+
+fn rank(instruction: &Instruction) -> (usize, bool, &str) {
+    let alias = ALIASES.iter().any(|(name, _)| *name == instruction.mnemonic);
+    (instruction.operands.len(), alias, instruction.mnemonic.as_str())
+}
+
+/// Cell holding the data of decoded slice and offset of the slice data there,
+/// inline continuations are located in the cell of the enclosing code
+#[derive(Clone)]
+struct Origin {
+    cell: UInt256,
+    offset: usize,
+}
+
+impl Origin {
+    fn of(slice: &SliceData) -> Self {
+        Self { cell: slice.cell().repr_hash(), offset: 0 }
+    }
+    fn at(&self, slice: &SliceData) -> (UInt256, usize) {
+        (self.cell, self.offset + slice.pos())
+    }
+}
+
+/// Locations of decoded instructions in preorder of the instruction tree
+type Trace = Vec<(UInt256, usize)>;
+
+// opcodes taking a reference to a continuation or a cell
+const REF_COMMANDS: &[(&[u8], &str)] = &[
+    (&[0x88], "PUSHREF"),
+    (&[0x89], "PUSHREFSLICE"),
+    (&[0x8A], "PUSHREFCONT"),
+    (&[0xCF, 0x20], "STREFCONST"),
+    (&[0xDB, 0x3C], "CALLREF"),
+    (&[0xDB, 0x3D], "JMPREF"),
+    (&[0xE3, 0x00], "IFREF"),
+    (&[0xE3, 0x01], "IFNOTREF"),
+    (&[0xE3, 0x02], "IFJMPREF"),
+    (&[0xE3, 0x03], "IFNOTJMPREF"),
+    (&[0xE3, 0x0D], "IFREFELSE"),
+    (&[0xE3, 0x0E], "IFELSEREF"),
+];
+
+/// Table of fixed length encodings built by encoding every operand combination
+/// of fixed length instructions with the compilation engine itself
+fn fixed_encodings() -> HashMap<Vec<u8>, Instruction> {
+    let mut engine = Engine::<Capture>::new(vec![]);
+    let mut fixed: HashMap<Vec<u8>, Instruction> = HashMap::new();
+    for info in instructions_info() {
+        if info.refs != 0 || info.bits.start() != info.bits.end() || info.bits.end() / 8 > MAX_FIXED_LEN {
+            continue
+        }
+        let handler = match engine.COMPILE_ROOT.get(info.mnemonic) {
+            Some(handler) => *handler,
+            None => continue
+        };
+        let domains = match info.operands.iter().map(|kind| operand_domain(kind)).collect::<Option<Vec<_>>>() {
+            Some(domains) => domains,
+            None => continue
+        };
+        for operands in cartesian(&domains) {
+            let par = operands.iter().map(|(text, _)| text.as_str()).collect::<Vec<_>>();
+            let mut capture = Capture::new();
+            if handler(&mut engine, &par, &mut capture, DbgPos::default()).is_err()
+                || !capture.plain || capture.code.is_empty() {
+                continue
+            }
+            let instruction = Instruction::with_operands(
+                info.mnemonic, operands.into_iter().map(|(_, operand)| operand).collect()
+            );
+            match fixed.get(&capture.code) {
+                Some(existing) if rank(existing) <= rank(&instruction) => (),
+                _ => { fixed.insert(capture.code, instruction); }
+            }
+        }
+    }
+    fixed
+}
+
+/// Decodes TVM bytecode into assembler instructions.
+/// The table of fixed length encodings is built once and shared by all instances.
+pub struct Disassembler {
+    fixed: &'static HashMap<Vec<u8>, Instruction>,
+}
+
+impl Default for Disassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Disassembler {
+    pub fn new() -> Self {
+        static FIXED: OnceLock<HashMap<Vec<u8>, Instruction>> = OnceLock::new();
+        Self { fixed: FIXED.get_or_init(fixed_encodings) }
+    }
+
+    /// Decodes code of the slice, following the implicit jump to the last
+    /// reference when the slice data is over; fails on instructions
+    /// the assembler has no syntax for
+    pub fn disassemble(&self, slice: &SliceData) -> Result<Vec<Instruction>, OperationError> {
+        self.code(slice, Origin::of(slice), &mut Trace::new())
+    }
+
+    /// Decodes code of the slice into assembler source text with every instruction
+    /// annotated by its cell and bit offset there, and by its source position
+    /// if the debug map has one
+    pub fn disassemble_annotated(&self, slice: &SliceData, dbg: Option<&DbgInfo>) -> Result<String, OperationError> {
+        let mut trace = Trace::new();
+        let code = self.code(slice, Origin::of(slice), &mut trace)?;
+        let mut result = String::new();
+        write_annotated(&mut result, &code, &mut trace.iter(), dbg, 0);
+        Ok(result)
+    }
+
+    fn code(&self, slice: &SliceData, mut origin: Origin, trace: &mut Trace) -> Result<Vec<Instruction>, OperationError> {
+        let mut code = vec![];
+        let mut slice = slice.clone();
+        loop {
+            while slice.remaining_bits() > 0 {
+                let index = trace.len();
+                trace.push(origin.at(&slice));
+                match self.decode(&mut slice, &origin, trace) {
+                    Some(instruction) => code.push(instruction?),
+                    None => {
+                        trace.truncate(index);
+                        break
+                    }
+                }
+            }
+            if slice.remaining_bits() == 0 && slice.remaining_references() == 1 {
+                match slice.reference(0) {
+                    Ok(next) => {
+                        slice = SliceData::from(next);
+                        origin = Origin::of(&slice);
+                    }
+                    Err(_) => break
+                }
+            } else {
+                break
+            }
+        }
+        if slice.remaining_bits() > 0 || slice.remaining_references() > 0 {
+            code.append(&mut self.raw(slice, &origin, trace)?);
+        }
+        Ok(code)
+    }
+
+    /// Represents undecodable rest of the cell with `.blob` and `.cell` directives
+    fn raw(&self, mut slice: SliceData, origin: &Origin, trace: &mut Trace) -> Result<Vec<Instruction>, OperationError> {
+        let mut code = vec![];
+        if slice.remaining_bits() > 0 {
+            trace.push(origin.at(&slice));
+            let bits = slice.remaining_bits();
+            let data = SliceData::from_raw(slice.get_bytestring(0), bits);
+            let _ = slice.move_by(bits);
+            code.push(Instruction::with_operands(".BLOB", vec![Operand::Slice(data)]));
+        }
+        while let Ok(cell) = slice.checked_drain_reference() {
+            trace.push((cell.repr_hash(), 0));
+            code.push(Instruction::with_operands(".CELL", vec![Operand::Block(self.disassemble_cell(&cell, trace)?)]));
+        }
+        Ok(code)
+    }
+
+    fn disassemble_cell(&self, cell: &Cell, trace: &mut Trace) -> Result<Vec<Instruction>, OperationError> {
+        let slice = SliceData::from(cell);
+        self.code(&slice, Origin::of(&slice), trace)
+    }
+
+    /// None if the instruction is not recognized, error if it has no assembler syntax
+    fn decode(&self, slice: &mut SliceData, origin: &Origin, trace: &mut Trace) -> Option<Result<Instruction, OperationError>> {
+        // variable decoding may read past the instruction before failing, so it takes a copy
+        let mut variable = slice.clone();
+        let depth = trace.len();
+        if let Some(result) = self.decode_variable(&mut variable, origin, trace) {
+            *slice = variable;
+            return Some(result)
+        }
+        trace.truncate(depth);
+        self.decode_fixed(slice).map(Ok)
+    }
+
+    fn decode_fixed(&self, slice: &mut SliceData) -> Option<Instruction> {
+        for len in 1..=MAX_FIXED_LEN {
+            if slice.remaining_bits() < len * 8 {
+                break
+            }
+            let key = (0..len).map(|i| slice.get_bits(i * 8, 8)).collect::<ton_types::Result<Vec<_>>>().ok()?;
+            if let Some(instruction) = self.fixed.get(&key) {
+                slice.move_by(len * 8).ok()?;
+                return Some(instruction.clone())
+            }
+        }
+        None
+    }
+
+    fn decode_variable(&self, slice: &mut SliceData, origin: &Origin, trace: &mut Trace) -> Option<Result<Instruction, OperationError>> {
+        let byte = slice.get_bits(0, 8).ok()?;
+        let word = if slice.remaining_bits() >= 16 {
+            Some(((byte as u16) << 8) | slice.get_bits(8, 8).ok()? as u16)
+        } else {
+            None
+        };
+        for (prefix, mnemonic) in REF_COMMANDS {
+            let matched = match prefix.len() {
+                1 => byte == prefix[0],
+                _ => word == Some(((prefix[0] as u16) << 8) | prefix[1] as u16),
+            };
+            if matched {
+                slice.move_by(prefix.len() * 8).ok()?;
+                let cell = slice.checked_drain_reference().ok()?;
+                return Some(self.disassemble_cell(&cell, trace).map(|code| {
+                    Instruction::with_operands(mnemonic, vec![Operand::Block(code)])
+                }))
+            }
+        }
+        // decoded block, failure to decode it fails the instruction
+        macro_rules! block {
+            ($code:expr) => {
+                match $code {
+                    Ok(code) => Operand::Block(code),
+                    Err(e) => return Some(Err(e))
+                }
+            };
+        }
+        let instruction = match (byte, word) {
+            // IFREFELSEREF takes both continuations from references
+            (0xE3, Some(0xE30F)) => return Some(Err(OperationError::Unsupported("IFREFELSEREF"))),
+            // IFBITJMPREF / IFNBITJMPREF
+            (0xE3, Some(word)) if word & 0xC0 == 0xC0 => {
+                slice.move_by(16).ok()?;
+                let cell = slice.checked_drain_reference().ok()?;
+                let mnemonic = if word & 0x20 == 0 { "IFBITJMPREF" } else { "IFNBITJMPREF" };
+                Instruction::with_operands(mnemonic, vec![block!(self.disassemble_cell(&cell, trace)), Operand::Integer((word & 0x1F).into())])
+            }
+            // PUSHINT with 16-bit argument
+            (0x81, Some(_)) if slice.remaining_bits() >= 24 => {
+                slice.move_by(8).ok()?;
+                let value = slice.get_next_u16().ok()? as i16;
+                Instruction::with_operands("PUSHINT", vec![Operand::Integer(value.into())])
+            }
+            // PUSHINT with long argument: 5-bit l, then 8l + 19 bits of signed integer
+            (0x82, _) => {
+                slice.move_by(8).ok()?;
+                let len = slice.get_next_int(5).ok()? as usize * 8 + 19;
+                let data = slice.get_next_bits(len).ok()?;
+                let mut value = BigInt::from_signed_bytes_be(&data);
+                // the integer is stored in the high bits of the bytes read
+                value >>= data.len() * 8 - len;
+                Instruction::with_operands("PUSHINT", vec![Operand::Integer(value)])
+            }
+            // PUSHSLICE with short argument
+            (0x8B, _) => {
+                slice.move_by(8).ok()?;
+                let len = slice.get_next_int(4).ok()? as usize;
+                Instruction::with_operands("PUSHSLICE", vec![Operand::Slice(next_tagged(slice, 8 * len + 4)?)])
+            }
+            // PUSHSLICE with long argument, references of the slice have no assembler syntax
+            (0x8D, _) => {
+                slice.move_by(8).ok()?;
+                if slice.get_next_int(3).ok()? != 0 {
+                    return Some(Err(OperationError::Unsupported("PUSHSLICE")))
+                }
+                let len = slice.get_next_int(7).ok()? as usize;
+                Instruction::with_operands("PUSHSLICE", vec![Operand::Slice(next_tagged(slice, 8 * len + 6)?)])
+            }
+            // PUSHCONT with short argument
+            (0x90..=0x9F, _) => {
+                slice.move_by(8).ok()?;
+                let len = (byte & 0x0F) as usize;
+                let inline = Origin { cell: origin.cell, offset: origin.offset + slice.pos() };
+                let data = slice.get_next_bytes(len).ok()?;
+                let cont = SliceData::from_raw(data, len * 8);
+                Instruction::with_operands("PUSHCONT", vec![block!(self.code(&cont, inline, trace))])
+            }
+            // PUSHCONT with long argument: r references and xx bytes of data
+            (0x8E, _) | (0x8F, _) => {
+                slice.move_by(7).ok()?;
+                let refs = slice.get_next_int(2).ok()? as usize;
+                let len = slice.get_next_int(7).ok()? as usize;
+                let inline = Origin { cell: origin.cell, offset: origin.offset + slice.pos() };
+                let mut cont = BuilderData::new();
+                cont.append_raw(&slice.get_next_bytes(len).ok()?, len * 8).ok()?;
+                for _ in 0..refs {
+                    cont.checked_append_reference(slice.checked_drain_reference().ok()?).ok()?;
+                }
+                let cont = SliceData::from(cont.into_cell().ok()?);
+                Instruction::with_operands("PUSHCONT", vec![block!(self.code(&cont, inline, trace))])
+            }
+            // STSLICECONST: 9-bit prefix, no references
+            (0xCF, Some(word)) if word & 0xFF80 == 0xCF80 => {
+                slice.move_by(9).ok()?;
+                if slice.get_next_int(2).ok()? != 0 {
+                    return None
+                }
+                let len = slice.get_next_int(3).ok()? as usize;
+                Instruction::with_operands("STSLICECONST", vec![Operand::Slice(next_tagged(slice, 8 * len + 2)?)])
+            }
+            // SDBEGINS / SDBEGINSQ: 14-bit prefix
+            (0xD7, Some(word)) if word & 0xFFF8 == 0xD728 => {
+                let mnemonic = if word & 0x04 == 0 { "SDBEGINS" } else { "SDBEGINSQ" };
+                slice.move_by(14).ok()?;
+                let len = slice.get_next_int(7).ok()? as usize;
+                Instruction::with_operands(mnemonic, vec![Operand::Slice(next_tagged(slice, 8 * len + 3)?)])
+            }
+            // DEBUGSTR and its LOGSTR/PRINTSTR forms: FEFn followed by n + 1 bytes
+            (0xFE, Some(word)) if word & 0xF0 == 0xF0 => {
+                slice.move_by(16).ok()?;
+                let mut data = slice.get_next_bytes((word & 0x0F) as usize + 1).ok()?;
+                let mnemonic = match data[0] {
+                    0x00 if data.len() > 1 => "LOGSTR",
+                    0x01 if data.len() > 1 => "PRINTSTR",
+                    _ => "DEBUGSTR",
+                };
+                if mnemonic != "DEBUGSTR" {
+                    data.remove(0);
+                }
+                Instruction::with_operands(mnemonic, vec![Operand::Text(string_operand(&data))])
+            }
+            _ => return None
+        };
+        Some(Ok(instruction))
+    }
+}
+
+/// Writes instructions one per line as `Instruction` does, with the location
+/// of every instruction in a comment at the end of its first line
+fn write_annotated<'a>(
+    result: &mut String,
+    code: &[Instruction],
+    trace: &mut impl Iterator<Item = &'a (UInt256, usize)>,
+    dbg: Option<&DbgInfo>,
+    indent: usize,
+) {
+    for instruction in code {
+        let mut comment = trace.next().map(|(cell, offset)| {
+            let hash = cell.to_hex_string();
+            let mut comment = format!("{}:{}", &hash[..8], offset);
+            if let Some(pos) = dbg.and_then(|dbg| dbg.get(cell)).and_then(|offsets| offsets.get(offset)) {
+                let _ = write!(comment, " {}", pos);
+            }
+            comment
+        });
+        let _ = write!(result, "{:indent$}{}", "", instruction.mnemonic, indent = indent);
+        for (i, operand) in instruction.operands.iter().enumerate() {
+            result.push_str(if i == 0 { " " } else { ", " });
+            match operand {
+                Operand::Block(code) => {
+                    result.push('{');
+                    if let Some(comment) = comment.take() {
+                        let _ = write!(result, " ; {}", comment);
+                    }
+                    result.push('\n');
+                    write_annotated(result, code, trace, dbg, indent + 2);
+                    let _ = write!(result, "{:indent$}}}", "", indent = indent);
+                }
+                operand => {
+                    let _ = write!(result, "{}", operand);
+                }
+            }
+        }
+        if let Some(comment) = comment {
+            let _ = write!(result, " ; {}", comment);
+        }
+        result.push('\n');
+    }
+}
+
+/// Reads bits terminated with completion tag
+fn next_tagged(slice: &mut SliceData, bits: usize) -> Option<SliceData> {
+    let data = slice.get_next_bits(bits).ok()?;
+    let mut len = bits;
+    while len > 0 && data[(len - 1) / 8] & (0x80 >> ((len - 1) % 8)) == 0 {
+        len -= 1;
+    }
+    // strip the tag bit itself
+    Some(SliceData::from_raw(data, len.checked_sub(1)?))
+}
+
+/// Writes string argument as plain token when it survives lexing, in hex otherwise
+fn string_operand(data: &[u8]) -> String {
+    let plain = !data.is_empty()
+        && data.iter().all(|c| c.is_ascii_alphanumeric() || *c == b'_' || *c == b'.')
+        && !data[0].eq_ignore_ascii_case(&b'x');
+    if plain {
+        String::from_utf8_lossy(data).to_string()
+    } else {
+        format!("x{}", hex::encode_upper(data))
+    }
+}
+
+/// Decodes code of the slice into instructions
+pub fn disassemble(slice: &SliceData) -> Result<Vec<Instruction>, OperationError> {
+    Disassembler::new().disassemble(slice)
+}
+
+/// Decodes code of the slice into assembler source text
+pub fn disassemble_to_string(slice: &SliceData) -> Result<String, OperationError> {
+    disassemble(slice).map(|code| instructions_to_string(&code))
+}
+
+/// Decodes code of the slice into assembler source text annotated with locations of instructions
+pub fn disassemble_annotated(slice: &SliceData, dbg: Option<&DbgInfo>) -> Result<String, OperationError> {
+    Disassembler::new().disassemble_annotated(slice, dbg)
+}
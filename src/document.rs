@@ -0,0 +1,152 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::ops::Range;
+
+use crate::{
+    CompileError, CompileOptions, Diagnostic, Line, Lines, Token, compile_code_with_diagnostics,
+    tokens::{Piece, classify_lines, scan_line},
+};
+
+/// Line of the document with its pieces and nesting level of block comment at its start
+struct Scanned {
+    text: String,
+    comment: usize,
+    pieces: Vec<(Piece, Range<usize>)>,
+}
+
+impl Scanned {
+    fn new(text: String, comment: &mut usize) -> Self {
+        let start = *comment;
+        let pieces = scan_line(&text, comment);
+        Self { text, comment: start, pieces }
+    }
+    /// Pieces the compilation depends on: all but comments, line comments may declare stack signatures
+    fn code(&self) -> impl Iterator<Item = (Piece, &str, &Range<usize>)> {
+        let text = &self.text;
+        self.pieces.iter()
+            .map(move |(piece, range)| (*piece, &text[range.clone()], range))
+            .filter(|(piece, text, _)| *piece != Piece::Comment || text.starts_with(';'))
+    }
+}
+
+/// Source file open in an editor: text edits rescan the lines touched and the following ones
+/// whose block comments change, the code is compiled again only when its tokens change
+pub struct Document {
+    filename: String,
+    options: CompileOptions,
+    lines: Vec<Scanned>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    pub fn new(filename: &str, text: &str, options: CompileOptions) -> Self {
+        let mut comment = 0;
+        let lines = text.split_inclusive('\n').map(|line| Scanned::new(line.to_string(), &mut comment)).collect();
+        let mut document = Self { filename: filename.to_string(), options, lines, diagnostics: Vec::new() };
+        document.compile();
+        document
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    /// Errors and warnings of the code as of the last edit
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn tokens(&self) -> Vec<Token> {
+        classify_lines(self.lines.iter().map(|line| (line.text.as_str(), line.pieces.as_slice())))
+    }
+
+    /// Byte offset of the position given by line and column in characters, both starting with 1
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let start = self.lines.iter().take(line.checked_sub(1)?).map(|line| line.text.len()).sum::<usize>();
+        let text = match self.lines.get(line - 1) {
+            Some(scanned) => scanned.text.strip_suffix('\n').unwrap_or(&scanned.text),
+            None if line == self.lines.len() + 1 && column == 1 => return Some(start),
+            None => return None
+        };
+        match text.char_indices().nth(column.checked_sub(1)?) {
+            Some((i, _)) => Some(start + i),
+            None if column == text.chars().count() + 1 => Some(start + text.len()),
+            None => None
+        }
+    }
+
+    /// Line index containing the byte offset and the offset of its start
+    fn find(&self, offset: usize) -> Result<(usize, usize), CompileError> {
+        let mut start = 0;
+        for (i, line) in self.lines.iter().enumerate() {
+            if offset < start + line.text.len() || i + 1 == self.lines.len() {
+                if !line.text.is_char_boundary(offset - start) {
+                    break
+                }
+                return Ok((i, start))
+            }
+            start += line.text.len();
+        }
+        match offset {
+            0 => Ok((0, 0)),
+            _ => Err(CompileError::syntax(0, 0, format!("Offset {} is out of the document", offset)))
+        }
+    }
+
+    /// Replaces bytes of the range by the text, returns diagnostics of the code edited
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Result<&[Diagnostic], CompileError> {
+        if range.start > range.end {
+            return Err(CompileError::syntax(0, 0, format!("Edit range {:?} is reversed", range)))
+        }
+        let (first, first_start) = self.find(range.start)?;
+        let (last, last_start) = self.find(range.end)?;
+        let mut edited = String::new();
+        if let Some(line) = self.lines.get(first) {
+            edited.push_str(&line.text[..range.start - first_start]);
+        }
+        edited.push_str(text);
+        if let Some(line) = self.lines.get(last) {
+            edited.push_str(&line.text[range.end - last_start..]);
+        }
+        let mut comment = self.lines.get(first).map_or(0, |line| line.comment);
+        let scanned = edited.split_inclusive('\n').map(|line| Scanned::new(line.to_string(), &mut comment)).collect::<Vec<_>>();
+        let end = (last + 1).min(self.lines.len());
+        let count = scanned.len();
+        let mut old = self.lines.splice(first..end, scanned).collect::<Vec<_>>();
+        // following lines are rescanned until block comments at their starts are the same
+        let mut i = first + count;
+        while i < self.lines.len() && self.lines[i].comment != comment {
+            let text = self.lines[i].text.clone();
+            old.push(std::mem::replace(&mut self.lines[i], Scanned::new(text, &mut comment)));
+            i += 1
+        }
+        let new = &self.lines[first..i];
+        let same = old.len() == new.len() && old.iter().zip(new).all(|(old, new)| old.code().eq(new.code()));
+        if !same {
+            self.compile()
+        }
+        Ok(&self.diagnostics)
+    }
+
+    fn compile(&mut self) {
+        let lines = self.lines.iter().enumerate()
+            .map(|(i, line)| Line::new(&line.text, &self.filename, i + 1))
+            .collect::<Lines>();
+        self.diagnostics = match compile_code_with_diagnostics(lines, &self.options) {
+            Ok((_, _, diagnostics)) => diagnostics,
+            Err(error) if error.position().filename.is_empty() => vec![error.with_filename(self.filename.as_str()).into()],
+            Err(error) => vec![error.into()]
+        };
+    }
+}
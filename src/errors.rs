@@ -11,18 +11,23 @@
 * limitations under the License.
 */
 
-use std::fmt;
+use std::{fmt, ops::Range, sync::Arc};
+
+use crate::{DiagnosticKind, TargetVersion, debug::no_filename};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Position {
-    pub filename: String,
+    pub filename: Arc<str>,
     pub line: usize,
     pub column: usize,
+    /// byte offsets of the token within its line, empty if unknown
+    pub span: Range<usize>,
 }
 
 pub type OperationName = String;
 pub type ParameterName = String;
 pub type Explanation = String;
+pub type Suggestions = Vec<String>;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ParameterError {
@@ -40,52 +45,93 @@ pub enum OperationError {
     MissingBlock,
     Nested(Box<CompileError>),
     NotFitInSlice,
+    DebugOffsetClash(usize),
+    InstructionLimit(usize),
+    CellLimit(usize),
+    DepthLimit(usize),
+    ExternalCell(String),
+    VersionRequired(TargetVersion),
+    /// command rejected by the visitor of options
+    Rejected(String),
+    /// instruction decoded has no assembler syntax
+    Unsupported(&'static str),
+    Internal(&'static str),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum CompileError {
     Syntax(Position, Explanation),
-    UnknownOperation(Position, OperationName),
+    UnknownOperation(Position, OperationName, Suggestions),
     Operation(Position, OperationName, OperationError),
+    /// warning of the kind options deny, see `CompileOptions::deny`
+    Denied(Position, DiagnosticKind, Explanation),
 }
 
 impl CompileError {
     pub fn syntax<S: ToString>(line: usize, column: usize, explanation: S) -> Self {
-        CompileError::Syntax(Position { filename: String::new(), line, column }, explanation.to_string())
+        CompileError::Syntax(Position { filename: no_filename(), line, column, span: 0..0 }, explanation.to_string())
     }
     pub fn unknown<S: ToString>(line: usize, column: usize, name: S) -> Self {
-        CompileError::UnknownOperation(Position { filename: String::new(), line, column }, name.to_string())
+        CompileError::UnknownOperation(Position { filename: no_filename(), line, column, span: 0..0 }, name.to_string(), Suggestions::new())
     }
     pub fn operation<S: ToString>(line: usize, column: usize, name: S, error: OperationError) -> Self {
-        CompileError::Operation(Position { filename: String::new(), line, column }, name.to_string(), error)
+        CompileError::Operation(Position { filename: no_filename(), line, column, span: 0..0 }, name.to_string(), error)
     }
     pub fn missing_params<S: ToString>(line: usize, column: usize, name: S) -> Self {
-        CompileError::Operation(Position { filename: String::new(), line, column }, name.to_string(), OperationError::MissingRequiredParameters)
+        CompileError::Operation(Position { filename: no_filename(), line, column, span: 0..0 }, name.to_string(), OperationError::MissingRequiredParameters)
     }
     pub fn missing_block<S: ToString>(line: usize, column: usize, name: S) -> Self {
-        CompileError::Operation(Position { filename: String::new(), line, column }, name.to_string(), OperationError::MissingBlock)
+        CompileError::Operation(Position { filename: no_filename(), line, column, span: 0..0 }, name.to_string(), OperationError::MissingBlock)
     }
     pub fn too_many_params<S: ToString>(line: usize, column: usize, name: S) -> Self {
-        CompileError::Operation(Position { filename: String::new(), line, column }, name.to_string(), OperationError::TooManyParameters)
+        CompileError::Operation(Position { filename: no_filename(), line, column, span: 0..0 }, name.to_string(), OperationError::TooManyParameters)
     }
     pub fn out_of_range<S1: ToString, S2: ToString>(line: usize, column: usize, name: S1, param: S2) -> Self {
         let operation = OperationError::Parameter(param.to_string(), ParameterError::OutOfRange);
-        CompileError::Operation(Position { filename: String::new(), line, column }, name.to_string(), operation)
+        CompileError::Operation(Position { filename: no_filename(), line, column, span: 0..0 }, name.to_string(), operation)
     }
-    pub fn with_filename(mut self, filename: String) -> Self {
+    pub fn with_filename<S: Into<Arc<str>>>(mut self, filename: S) -> Self {
+        let filename = filename.into();
         match self {
             Self::Syntax(ref mut pos, _) => {
                 pos.filename = filename;
             },
-            Self::UnknownOperation(ref mut pos, _) => {
+            Self::UnknownOperation(ref mut pos, _, _) => {
                 pos.filename = filename;
             },
             Self::Operation(ref mut pos, _, _) => {
                 pos.filename = filename;
             }
+            Self::Denied(ref mut pos, _, _) => {
+                pos.filename = filename;
+            }
+        };
+        self
+    }
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        match self {
+            Self::Syntax(ref mut pos, _) => {
+                pos.span = span;
+            },
+            Self::UnknownOperation(ref mut pos, _, _) => {
+                pos.span = span;
+            },
+            Self::Operation(ref mut pos, _, _) => {
+                pos.span = span;
+            }
+            Self::Denied(ref mut pos, _, _) => {
+                pos.span = span;
+            }
         };
         self
     }
+    /// Attaches names of known operations similar to the unknown one
+    pub fn with_suggestions(mut self, names: Suggestions) -> Self {
+        if let Self::UnknownOperation(_, _, ref mut suggestions) = self {
+            *suggestions = names;
+        }
+        self
+    }
     pub fn unexpected_type<S1: ToString, S2: ToString>(line: usize, column: usize, name: S1, param: S2) -> Self {
         let operation = OperationError::Parameter(param.to_string(), ParameterError::UnexpectedType);
         CompileError::operation(line, column, name.to_string(), operation)
@@ -174,6 +220,15 @@ impl fmt::Display for OperationError {
             }
             OperationError::Nested(error) => write!(f, "\n{}", indent(error.to_string())),
             OperationError::NotFitInSlice => write!(f, "Command bytecode is too long for single slice"),
+            OperationError::DebugOffsetClash(offset) => write!(f, "Debug position of offset {} is already set", offset),
+            OperationError::InstructionLimit(limit) => write!(f, "Code exceeds limit of {} instructions", limit),
+            OperationError::CellLimit(limit) => write!(f, "Code exceeds limit of {} cells", limit),
+            OperationError::DepthLimit(limit) => write!(f, "Code exceeds limit of cell depth {}", limit),
+            OperationError::ExternalCell(error) => write!(f, "Cannot embed bag of cells {}", error),
+            OperationError::VersionRequired(version) => write!(f, "Instruction requires TVM version {} or later", version),
+            OperationError::Rejected(message) => write!(f, "Rejected: {}", message),
+            OperationError::Unsupported(mnemonic) => write!(f, "Instruction {} has no assembler syntax", mnemonic),
+            OperationError::Internal(error) => write!(f, "Internal error: {}", error),
         }
     }
 }
@@ -184,10 +239,19 @@ impl fmt::Display for CompileError {
             CompileError::Syntax(position, explanation) => {
                 write!(f, "{} Syntax error: {}", position, explanation)
             }
-            CompileError::UnknownOperation(position, name) => write!(f, "{} Unknown operation {}", position, name),
+            CompileError::UnknownOperation(position, name, suggestions) => {
+                write!(f, "{} Unknown operation {}", position, name)?;
+                if !suggestions.is_empty() {
+                    write!(f, ", did you mean {}?", suggestions.join(" or "))?;
+                }
+                Ok(())
+            }
             CompileError::Operation(position, name, error) => {
                 write!(f, "Instruction {} at {}: {}", name, position, error)
             }
+            CompileError::Denied(position, kind, explanation) => {
+                write!(f, "{} {} [denied {}]", position, explanation, kind)
+            }
         }
     }
 }
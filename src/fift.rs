@@ -0,0 +1,93 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::HashMap;
+
+const INDENT: &str = "  ";
+
+// commands taking a slice literal operand
+const SLICE_COMMANDS: &[&str] = &["PUSHSLICE", "SDBEGINS", "SDBEGINSQ", "STSLICECONST"];
+
+/// Line and column the code of a block starts at along with its length
+pub(crate) type BlockSpan = (usize, usize, usize);
+
+/// Collects Fift assembler text of compiled commands.
+/// Blocks are identified by their span in the source: the engine compiles
+/// a block while handling the command owning it, so the text of the block is
+/// complete by the time the command itself is recorded.
+#[derive(Default)]
+pub(crate) struct FiftSource {
+    stack: Vec<Vec<String>>,
+    blocks: HashMap<BlockSpan, Vec<String>>,
+}
+
+impl FiftSource {
+    /// Starts a new (possibly nested) block
+    pub(crate) fn begin(&mut self) {
+        self.stack.push(Vec::new())
+    }
+    /// Completes the current block
+    pub(crate) fn end(&mut self, span: BlockSpan) {
+        if let Some(lines) = self.stack.pop() {
+            self.blocks.insert(span, lines);
+        }
+    }
+    /// Returns text of the compiled block
+    pub(crate) fn take(&mut self, span: BlockSpan) -> Option<String> {
+        self.blocks.remove(&span).map(|lines| lines.join("\n"))
+    }
+    /// Records command in Fift postfix form: operands first, then mnemonic,
+    /// spans are given for operands being blocks
+    pub(crate) fn write_command(&mut self, operation: &str, par: &[&str], spans: &[Option<BlockSpan>]) {
+        let mut lines = vec![];
+        let mut operands = vec![];
+        for (p, span) in par.iter().zip(spans) {
+            match span.and_then(|span| self.blocks.remove(&span)) {
+                Some(block) => {
+                    operands.push("<{".to_string());
+                    lines.push(operands.join(" "));
+                    operands.clear();
+                    lines.extend(block.into_iter().map(|line| format!("{}{}", INDENT, line)));
+                    operands.push("}>".to_string());
+                }
+                None => operands.push(operand(operation, p))
+            }
+        }
+        operands.push(mnemonic(operation, par.len()).to_string());
+        lines.push(operands.join(" "));
+        if let Some(current) = self.stack.last_mut() {
+            current.extend(lines);
+        }
+    }
+}
+
+fn operand(operation: &str, par: &str) -> String {
+    if SLICE_COMMANDS.contains(&operation) {
+        match par {
+            "0" | "1" => format!("b{{{}}}", par),
+            _ if par.get(1..).map_or(false, |rest| rest.starts_with('{')) => par.to_string(),
+            _ if par.len() > 1 && par.starts_with(['x', 'X']) => format!("x{{{}}}", &par[1..]),
+            _ if par.len() > 1 && par.starts_with(['b', 'B']) => format!("b{{{}}}", &par[1..]),
+            _ => par.to_string()
+        }
+    } else {
+        par.to_string()
+    }
+}
+
+fn mnemonic(operation: &str, params: usize) -> &str {
+    match (operation, params) {
+        ("XCHG", 1) => "XCHG0",
+        _ => operation
+    }
+}
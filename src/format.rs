@@ -0,0 +1,191 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::HashSet;
+
+use crate::{CodePage0, CompileError, Engine, tokens::{CLOSINGS, DECLARATIONS, Piece, scan_line}};
+
+const INDENT: &str = "  ";
+
+/// Line of the formatted code: the first token is padded to align operands of adjacent lines
+struct Formatted {
+    indent: usize,
+    head: String,
+    rest: String,
+}
+
+struct Formatter {
+    commands: HashSet<&'static str>,
+    depth: usize,
+    // lines of blocks not closed yet
+    blocks: Vec<(usize, usize)>,
+    // nesting level of /* ... */ comment and its start
+    comment: usize,
+    comment_start: (usize, usize),
+    lines: Vec<Option<Formatted>>,
+}
+
+impl Formatter {
+    /// Pieces of the line with their text, braces are checked to match
+    fn tokenize<'a>(&mut self, line: &'a str, number: usize) -> Result<Vec<(Piece, &'a str)>, CompileError> {
+        let opened = self.comment == 0;
+        let pieces = scan_line(line, &mut self.comment);
+        let mut items = Vec::new();
+        for (piece, range) in pieces {
+            let column = line[..range.start].chars().count() + 1;
+            match piece {
+                Piece::Open => self.blocks.push((number, column)),
+                Piece::Close if self.blocks.pop().is_none() => return Err(CompileError::syntax(number, column, "Unexpected }")),
+                // the comment not closed on the line is the last piece
+                Piece::Comment if opened && self.comment > 0 => self.comment_start = (number, column),
+                _ => ()
+            }
+            items.push((piece, &line[range]));
+        }
+        Ok(items)
+    }
+
+    /// Mnemonics in upper case, directives in lower case, other tokens as written
+    fn normalize(&self, token: &str) -> String {
+        let upper = token.to_ascii_uppercase();
+        if token.starts_with('.') && (self.commands.contains(upper.as_str()) || DECLARATIONS.contains(&token.to_ascii_lowercase().as_str())
+            || CLOSINGS.contains(&token.to_ascii_lowercase().as_str())) {
+            token.to_ascii_lowercase()
+        } else if self.commands.contains(upper.as_str()) {
+            upper
+        } else {
+            token.to_string()
+        }
+    }
+
+    fn line(&mut self, line: &str, number: usize) -> Result<(), CompileError> {
+        // lines of multiline comments are kept as they are
+        if self.comment > 0 {
+            let items = self.tokenize(line, number)?;
+            self.count_blocks(&items);
+            self.lines.push(Some(Formatted { indent: 0, head: String::new(), rest: line.trim_end().to_string() }));
+            return Ok(())
+        }
+        let items = self.tokenize(line, number)?;
+        if items.is_empty() {
+            self.lines.push(None);
+            return Ok(())
+        }
+        let first = match items.first() {
+            Some((Piece::Word, token)) => token.to_ascii_lowercase(),
+            _ => String::new()
+        };
+        let declaration = DECLARATIONS.contains(&first.as_str());
+        let mut indent = self.depth;
+        if matches!(items.first(), Some((Piece::Close, _))) || CLOSINGS.contains(&first.as_str()) {
+            indent = indent.saturating_sub(1)
+        }
+        // the first token is aligned if operands follow it
+        let aligned = matches!(items.first(), Some((Piece::Word, token)) if !token.ends_with(':'))
+            && matches!(items.get(1), Some((Piece::Word, _)));
+        let mut head = String::new();
+        let mut rest = String::new();
+        for (i, (piece, token)) in items.iter().enumerate() {
+            let token = match piece {
+                Piece::Word if i == 0 || !declaration => self.normalize(token),
+                _ => token.to_string()
+            };
+            if i == 0 && aligned {
+                head = token;
+                continue
+            }
+            if !rest.is_empty() && *piece != Piece::Comma {
+                rest.push(' ')
+            }
+            rest.push_str(&token);
+        }
+        self.count_blocks(&items);
+        match first.as_str() {
+            ".macro" | ".rep" => self.depth += 1,
+            ".endmacro" | ".endr" => self.depth = self.depth.saturating_sub(1),
+            _ => ()
+        }
+        self.lines.push(Some(Formatted { indent, head, rest }));
+        Ok(())
+    }
+
+    fn count_blocks(&mut self, items: &[(Piece, &str)]) {
+        for (piece, _) in items {
+            match piece {
+                Piece::Open => self.depth += 1,
+                Piece::Close => self.depth = self.depth.saturating_sub(1),
+                _ => ()
+            }
+        }
+    }
+
+    fn finish(self) -> Result<String, CompileError> {
+        if self.comment > 0 {
+            let (line, column) = self.comment_start;
+            return Err(CompileError::syntax(line, column, "Missing end of block comment"))
+        }
+        if let Some((line, column)) = self.blocks.last() {
+            return Err(CompileError::syntax(*line, *column, "Missing } of block"))
+        }
+        let mut result = String::new();
+        let mut start = 0;
+        while start < self.lines.len() {
+            // adjacent lines of the same indent are aligned
+            let indent = self.lines[start].as_ref().map(|line| line.indent);
+            let end = self.lines[start..].iter()
+                .position(|line| line.as_ref().map(|line| line.indent) != indent)
+                .map_or(self.lines.len(), |n| start + n);
+            let width = self.lines[start..end].iter().flatten().map(|line| line.head.len()).max().unwrap_or_default();
+            for line in &self.lines[start..end] {
+                match line {
+                    // blank lines are collapsed and trimmed at the ends
+                    None if result.is_empty() || result.ends_with("\n\n") => (),
+                    None => result.push('\n'),
+                    Some(line) => {
+                        result.push_str(&INDENT.repeat(line.indent));
+                        match (line.head.is_empty(), line.rest.is_empty()) {
+                            (true, _) => result.push_str(&line.rest),
+                            (false, true) => result.push_str(&line.head),
+                            (false, false) => result.push_str(&format!("{:width$} {}", line.head, line.rest, width = width)),
+                        }
+                        result.push('\n')
+                    }
+                }
+            }
+            start = end;
+        }
+        while result.ends_with("\n\n") {
+            result.pop();
+        }
+        Ok(result)
+    }
+}
+
+/// Formats assembler code: blocks in braces and macro bodies are indented, mnemonics are
+/// upper case and directives are lower case, operands of adjacent lines are aligned and
+/// separated by commas with a single space. Comments are kept, lines of block comments as they are.
+pub fn format_code(code: &str) -> Result<String, CompileError> {
+    let engine = Engine::<CodePage0>::new(vec![]);
+    let mut formatter = Formatter {
+        commands: engine.COMPILE_ROOT.keys().copied().collect(),
+        depth: 0,
+        blocks: Vec::new(),
+        comment: 0,
+        comment_start: (0, 0),
+        lines: Vec::new(),
+    };
+    for (i, line) in code.lines().enumerate() {
+        formatter.line(line, i + 1)?;
+    }
+    formatter.finish()
+}
@@ -0,0 +1,129 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use num::BigInt;
+use std::sync::OnceLock;
+use ton_types::SliceData;
+
+use crate::{Instruction, InstructionInfo, Operand, instructions_info};
+
+/// Nesting limit of blocks, deeper ones are empty
+const MAX_DEPTH: usize = 2;
+/// Limit of instructions in a block
+const MAX_BLOCK: usize = 4;
+
+/// Values taken by operands of the kind named in `InstructionInfo`
+enum Kind {
+    Integer(i64, i64),
+    /// PLDUZ takes multiples of 32
+    Bits,
+    Stack(isize, isize),
+    Control,
+    Block,
+    /// slice of the given bytes at most
+    Slice(usize),
+    Text,
+}
+
+fn kind(name: &str) -> Option<Kind> {
+    let range = |text: &str| match text.split_once("..") {
+        Some((min, max)) => Some((min.parse().ok()?, max.parse().ok()?)),
+        None => text.parse().ok().map(|value| (value, value))
+    };
+    let kind = match name {
+        "32..256" => Kind::Bits,
+        "i257" => Kind::Integer(i64::MIN, i64::MAX),
+        "s(i)" => Kind::Stack(0, 15),
+        "c(i)" => Kind::Control,
+        "{block}" | "{short}" | "{long}" | "{ref}" => Kind::Block,
+        "x{short}" | "slice" => Kind::Slice(3),
+        "x{long}" => Kind::Slice(64),
+        "string" => Kind::Text,
+        _ if name.starts_with("s(") => {
+            let (min, max) = range(name.strip_prefix("s(")?.strip_suffix(')')?)?;
+            Kind::Stack(min as isize, max as isize)
+        }
+        _ if name.starts_with('u') => Kind::Integer(0, (1 << name[1..].parse::<u32>().ok()?) - 1),
+        _ if name.starts_with('i') => {
+            let bits = name[1..].parse::<u32>().ok()?;
+            Kind::Integer(-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+        }
+        _ => {
+            let (min, max) = range(name)?;
+            Kind::Integer(min, max)
+        }
+    };
+    Some(kind)
+}
+
+/// Shapes of instructions whose operands can be generated
+fn shapes() -> &'static [InstructionInfo] {
+    static SHAPES: OnceLock<Vec<InstructionInfo>> = OnceLock::new();
+    SHAPES.get_or_init(|| instructions_info().into_iter()
+        .filter(|info| info.operands.iter().all(|name| kind(name).is_some()))
+        .collect())
+}
+
+fn operand(u: &mut Unstructured, kind: Kind, depth: usize) -> Result<Operand> {
+    let operand = match kind {
+        Kind::Integer(min, max) => Operand::Integer(BigInt::from(u.int_in_range(min..=max)?)),
+        Kind::Bits => Operand::Integer(BigInt::from(32 * u.int_in_range(1..=8)?)),
+        Kind::Stack(min, max) => Operand::StackRegister(u.int_in_range(min..=max)?),
+        Kind::Control => Operand::ControlRegister(u.int_in_range(0..=15)?),
+        Kind::Block if depth >= MAX_DEPTH => Operand::Block(Vec::new()),
+        Kind::Block => {
+            let count = u.int_in_range(0..=MAX_BLOCK)?;
+            Operand::Block((0..count).map(|_| instruction(u, depth + 1)).collect::<Result<_>>()?)
+        }
+        Kind::Slice(max) => {
+            let bytes = u.int_in_range(1..=max)?;
+            let bits = u.int_in_range(bytes * 8 - 7..=bytes * 8)?;
+            Operand::Slice(SliceData::from_raw(u.bytes(bytes)?.to_vec(), bits))
+        }
+        Kind::Text => {
+            let length = u.int_in_range(1..=15)?;
+            let text = (0..length)
+                .map(|_| u.int_in_range(0..=35u32).map(|digit| std::char::from_digit(digit, 36).unwrap_or('0')))
+                .collect::<Result<String>>()?;
+            Operand::Text(format!("\"{}\"", text))
+        }
+    };
+    Ok(operand)
+}
+
+fn instruction(u: &mut Unstructured, depth: usize) -> Result<Instruction> {
+    let shape = u.choose(shapes())?;
+    let mut operands = shape.operands.iter()
+        .filter_map(|name| kind(name))
+        .map(|kind| operand(u, kind, depth))
+        .collect::<Result<Vec<_>>>()?;
+    // XCHG takes registers in ascending order
+    if let [Operand::StackRegister(i), Operand::StackRegister(j)] = operands.as_mut_slice() {
+        if shape.mnemonic == "XCHG" && i >= j {
+            std::mem::swap(i, j);
+            if i == j {
+                *j += 1
+            }
+        }
+    }
+    Ok(Instruction::with_operands(shape.mnemonic, operands))
+}
+
+/// Instruction of a known shape with operands in its ranges, blocks are nested up to a few levels,
+/// so generated code compiles and may be checked to survive disassembly
+impl<'a> Arbitrary<'a> for Instruction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        instruction(u, 0)
+    }
+}
@@ -0,0 +1,198 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::{collections::{HashMap, HashSet}, fmt};
+use ton_types::{Cell, SliceData, UInt256};
+
+use crate::{ControlFlowGraph, DbgInfo, DbgPos, Instruction, compile_code};
+
+const BASIC_PRICE: u64 = 10;
+const REFERENCE_PRICE: u64 = 5;
+const CELL_CREATE_PRICE: u64 = 500;
+const CELL_LOAD_PRICE: u64 = 100;
+
+// commands creating or loading cells by encoding prefix
+const CELL_CREATING: &[&[u8]] = &[
+    &[0xC9],        // ENDC
+    &[0xCD],        // ENDCST
+    &[0xCF, 0x23],  // ENDXC
+];
+const CELL_LOADING: &[&[u8]] = &[
+    &[0xD0],        // CTOS
+    &[0xD5],        // LDREFRTOS
+    &[0xD7, 0x39],  // XCTOS
+    &[0xDB, 0x3C],  // CALLREF
+    &[0xDB, 0x3D],  // JMPREF
+    &[0xDB, 0x3E],  // JMPREFDATA
+    &[0xE3, 0x00],  // IFREF
+    &[0xE3, 0x01],  // IFNOTREF
+    &[0xE3, 0x02],  // IFJMPREF
+    &[0xE3, 0x03],  // IFNOTJMPREF
+    &[0xE3, 0x0D],  // IFREFELSE
+    &[0xE3, 0x0E],  // IFELSEREF
+    &[0xE3, 0x0F],  // IFREFELSEREF
+];
+
+/// Basic gas price of an instruction as defined by TVM: 10 + b + 5r, where b is
+/// its length in bits and r is the number of references, see `InstructionInfo::gas`
+pub(crate) fn basic_gas(bits: usize, refs: usize) -> u64 {
+    BASIC_PRICE + bits as u64 + REFERENCE_PRICE * refs as u64
+}
+
+/// Static gas estimate of the command: its basic price, see `basic_gas`, plus prices
+/// of cells created or loaded. Costs depending on run time state such as exceptions,
+/// implicit jumps, dictionary and stack operations are not counted.
+pub fn instruction_gas(command: &SliceData) -> u64 {
+    let bytes = command.get_bytestring(0);
+    let matches = |prefixes: &[&[u8]]| prefixes.iter().any(|prefix| bytes.starts_with(prefix));
+    let mut gas = basic_gas(command.remaining_bits(), command.remaining_references());
+    if matches(CELL_CREATING) {
+        gas += CELL_CREATE_PRICE
+    }
+    if matches(CELL_LOADING) {
+        gas += CELL_LOAD_PRICE
+    }
+    gas
+}
+
+/// Estimate of a command found at the offset of the cell
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstructionGas {
+    pub hash: UInt256,
+    pub offset: usize,
+    pub pos: Option<DbgPos>,
+    pub gas: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GasEstimate {
+    pub instructions: Vec<InstructionGas>,
+    pub total: u64,
+}
+
+/// Estimates gas of every command of the code once, commands are delimited
+/// by offsets of the debug info; bits of a cell without them make up one command
+pub fn estimate_gas(code: &Cell, dbg: &DbgInfo) -> GasEstimate {
+    let mut estimate = GasEstimate::default();
+    let mut visited = HashSet::new();
+    let mut stack = vec![code.clone()];
+    while let Some(cell) = stack.pop() {
+        let hash = cell.repr_hash();
+        if !visited.insert(hash) {
+            continue
+        }
+        let mut data = SliceData::from(cell.clone());
+        let total = data.remaining_bits();
+        let mut starts = dbg.get(&hash)
+            .map(|offsets| offsets.keys().copied().filter(|offset| *offset < total).collect::<Vec<_>>())
+            .unwrap_or_default();
+        if starts.first() != Some(&0) && total > 0 {
+            starts.insert(0, 0);
+        }
+        for (i, start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(total);
+            let gas = data.get_next_slice(end - start).map(|command| instruction_gas(&command)).unwrap_or_default();
+            estimate.total += gas;
+            estimate.instructions.push(InstructionGas { hash, offset: *start, pos: dbg.find(&hash, *start).cloned(), gas });
+        }
+        for i in (0..cell.references_count()).rev() {
+            if let Ok(child) = cell.reference(i) {
+                stack.push(child)
+            }
+        }
+    }
+    estimate
+}
+
+/// Upper bound of gas spent from an entry of the control flow graph
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GasBound {
+    /// the most expensive path through blocks of the graph and its gas
+    Bounded { gas: u64, path: Vec<usize> },
+    /// a loop is reachable, closed at the block given
+    Unbounded { block: usize },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryGas {
+    pub entry: usize,
+    pub bound: GasBound,
+}
+
+impl fmt::Display for EntryGas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.bound {
+            GasBound::Bounded { gas, path } => {
+                let path = path.iter().map(|block| format!("b{}", block)).collect::<Vec<_>>();
+                write!(f, "entry b{}: at most {} gas along {}", self.entry, gas, path.join(" -> "))
+            }
+            GasBound::Unbounded { block } => write!(f, "entry b{}: unbounded, loop at b{}", self.entry, block),
+        }
+    }
+}
+
+/// Gas of the instruction compiled alone, basic price if it does not compile so, e.g. with constants
+fn command_gas(instruction: &Instruction) -> u64 {
+    compile_code(&instruction.to_string()).map_or(BASIC_PRICE, |code| instruction_gas(&code))
+}
+
+/// Worst gas from the entry to the end of the code, the graph is walked depth first
+fn bound(entry: usize, gas: &[u64], successors: &[Vec<usize>]) -> GasBound {
+    // gas from the block to the end and the successor taking the most of it
+    let mut worst: HashMap<usize, (u64, Option<usize>)> = HashMap::new();
+    let mut active = HashSet::new();
+    active.insert(entry);
+    let mut stack = vec![(entry, 0)];
+    while let Some((block, i)) = stack.last_mut() {
+        let block = *block;
+        match successors[block].get(*i) {
+            Some(next) => {
+                *i += 1;
+                if active.contains(next) {
+                    return GasBound::Unbounded { block: *next }
+                }
+                if !worst.contains_key(next) {
+                    active.insert(*next);
+                    stack.push((*next, 0));
+                }
+            }
+            None => {
+                stack.pop();
+                active.remove(&block);
+                let next = successors[block].iter().map(|next| (worst[next].0, *next)).max_by_key(|(gas, _)| *gas);
+                worst.insert(block, (gas[block].saturating_add(next.map_or(0, |(gas, _)| gas)), next.map(|(_, next)| next)));
+            }
+        }
+    }
+    let mut path = vec![entry];
+    while let Some(next) = path.last().and_then(|block| worst[block].1) {
+        path.push(next)
+    }
+    GasBound::Bounded { gas: worst[&entry].0, path }
+}
+
+/// Upper bound of gas of every entry of the graph, see `ControlFlowGraph::entries`:
+/// static estimates of commands, see `instruction_gas`, summed along the most expensive path.
+/// Paths through loops are unbounded, continuations of unknown origin are taken to return
+/// right away as in `control_flow_graph`.
+pub fn worst_case_gas(graph: &ControlFlowGraph) -> Vec<EntryGas> {
+    let mut prices = HashMap::new();
+    let gas = graph.blocks.iter().map(|block| {
+        block.instructions.iter().map(|instruction| *prices.entry(instruction.to_string()).or_insert_with(|| command_gas(instruction))).sum()
+    }).collect::<Vec<u64>>();
+    let mut successors = vec![Vec::new(); graph.blocks.len()];
+    for edge in &graph.edges {
+        successors[edge.from].push(edge.to)
+    }
+    graph.entries().into_iter().map(|entry| EntryGas { entry, bound: bound(entry, &gas, &successors) }).collect()
+}
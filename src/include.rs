@@ -0,0 +1,147 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::{io, path::{Path, PathBuf}, sync::Arc};
+
+use super::{CompileError, Line, Lines, macros::enter_span};
+
+const INCLUDE: &str = ".include";
+
+/// Path of `.include "path"` directive taking the whole line
+fn directive(text: &str) -> Option<Result<&str, ()>> {
+    let text = text.trim();
+    match text.get(..INCLUDE.len()) {
+        Some(head) if head.eq_ignore_ascii_case(INCLUDE) => (),
+        _ => return None
+    }
+    let rest = text[INCLUDE.len()..].trim_start();
+    if rest.len() == text.len() - INCLUDE.len() && !rest.starts_with('"') {
+        // some longer token like `.includes`
+        return None
+    }
+    let path = match rest.strip_prefix('"').and_then(|rest| rest.split_once('"')) {
+        Some((path, tail)) if !path.is_empty() => {
+            let tail = tail.trim_start();
+            if tail.is_empty() || tail.starts_with(';') {
+                path
+            } else {
+                return Some(Err(()))
+            }
+        }
+        _ => return Some(Err(()))
+    };
+    Some(Ok(path))
+}
+
+/// Splits code into lines of the source file, empty name for unnamed one
+pub(crate) fn code_to_lines(code: &str, filename: &str) -> Lines {
+    let filename = Arc::from(filename);
+    code.split_inclusive('\n').enumerate().map(|(i, text)| Line::new_shared(text, &filename, i + 1)).collect()
+}
+
+/// Checks if there is any include directive to expand
+pub(crate) fn has_includes(lines: &Lines) -> bool {
+    lines.iter().any(|line| directive(&line.text).is_some())
+}
+
+/// Checks the source text for include directives without splitting it into lines
+pub(crate) fn source_has_includes(code: &str) -> bool {
+    code.split_inclusive('\n').any(|line| directive(line).is_some())
+}
+
+/// Replaces include directives with lines of included files recursively.
+/// Relative paths are looked up next to the including file, then in the directories
+/// given, then in the current one.
+pub(crate) fn expand_includes(lines: Lines, dirs: &[PathBuf]) -> Result<Lines, CompileError> {
+    let _span = enter_span!(DEBUG, "include");
+    let mut result = Lines::new();
+    expand(lines, dirs, &mut vec![], &mut result)?;
+    Ok(result)
+}
+
+fn expand(lines: Lines, dirs: &[PathBuf], stack: &mut Vec<PathBuf>, result: &mut Lines) -> Result<(), CompileError> {
+    for line in lines {
+        let path = match directive(&line.text) {
+            Some(Ok(path)) => path,
+            Some(Err(_)) => {
+                let pos = &line.pos;
+                return Err(CompileError::syntax(pos.line_code, 1, "Bad .include directive").with_filename(pos.filename.clone()))
+            }
+            None => {
+                result.push(line);
+                continue
+            }
+        };
+        let error = |explanation: String| {
+            CompileError::syntax(line.pos.line_code, 1, explanation).with_filename(line.pos.filename.clone())
+        };
+        let file = resolve(path, &line.pos.filename, dirs).ok_or_else(|| error(format!("Cannot find included file {}", path)))?;
+        let canonical = file.canonicalize().unwrap_or_else(|_| file.clone());
+        if stack.contains(&canonical) {
+            return Err(error(format!("Recursive include of {}", path)))
+        }
+        let text = read_file(&file)
+            .and_then(|data| String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+            .map_err(|e| error(format!("Cannot include {}: {}", path, e)))?;
+        let filename = Arc::from(file.to_string_lossy());
+        let mut included = text.split_inclusive('\n')
+            .enumerate()
+            .map(|(i, text)| Line::new_shared(text, &filename, i + 1))
+            .collect::<Lines>();
+        // tokens of the last line must not stick to the following code
+        if let Some(last) = included.last_mut() {
+            if !last.text.ends_with('\n') {
+                last.text.push('\n')
+            }
+        }
+        stack.push(canonical);
+        expand(included, dirs, stack, result)?;
+        stack.pop();
+    }
+    Ok(())
+}
+
+/// Reads the file if the filesystem is available, it is not e.g. in WebAssembly of browsers
+#[cfg(feature = "fs")]
+pub(crate) fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    std::fs::read(path)
+}
+
+#[cfg(not(feature = "fs"))]
+pub(crate) fn read_file(_path: &Path) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "filesystem access is disabled"))
+}
+
+#[cfg(feature = "fs")]
+fn is_file(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(not(feature = "fs"))]
+fn is_file(_path: &Path) -> bool {
+    // the failure to read the file tells the reason
+    true
+}
+
+pub(crate) fn resolve(path: &str, including: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return Some(path.to_path_buf()).filter(|path| is_file(path))
+    }
+    let parent = Path::new(including).parent().filter(|_| !including.is_empty());
+    parent.into_iter()
+        .chain(dirs.iter().map(|dir| dir.as_path()))
+        .map(|dir| dir.join(path))
+        .chain(std::iter::once(path.to_path_buf()))
+        .find(|candidate| is_file(candidate))
+}
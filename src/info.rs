@@ -0,0 +1,244 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::ops::RangeInclusive;
+use super::{Engine, gas::basic_gas, writer::CodePage0};
+
+/// Static encoding properties of an instruction for a given operand shape
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstructionInfo {
+    pub mnemonic: &'static str,
+    /// operand kinds in order, e.g. `["s(i)", "s(j)"]`
+    pub operands: Vec<&'static str>,
+    /// encoded length in bits (a range for variable length forms)
+    pub bits: RangeInclusive<usize>,
+    /// number of cell references consumed by the encoding
+    pub refs: usize,
+    /// bytes of the encoding with operand fields set to zero,
+    /// empty for instructions encoded depending on their operands
+    pub opcode: Vec<u8>,
+}
+
+impl InstructionInfo {
+    fn new(mnemonic: &'static str, operands: &[&'static str], bits: RangeInclusive<usize>, refs: usize) -> Self {
+        Self { mnemonic, operands: operands.to_vec(), bits, refs, opcode: Vec::new() }
+    }
+    fn fixed(mnemonic: &'static str, operands: &[&'static str], bits: usize) -> Self {
+        Self::new(mnemonic, operands, bits..=bits, 0)
+    }
+    /// Basic gas price as defined by TVM: 10 + b + 5r, where b is the instruction
+    /// length in bits and r is the number of references. Dynamic costs
+    /// (cell creation, cell loading, exceptions) are not included, see `instruction_gas`.
+    pub fn gas(&self) -> RangeInclusive<u64> {
+        basic_gas(*self.bits.start(), self.refs)..=basic_gas(*self.bits.end(), self.refs)
+    }
+}
+
+impl std::fmt::Display for InstructionInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.mnemonic)?;
+        if !self.operands.is_empty() {
+            write!(f, " {}", self.operands.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn operand_kind(parser: &str) -> &'static str {
+    match parser {
+        "parse_const_u2" => "u2",
+        "parse_const_i4" => "-1..14",
+        "parse_const_u4" => "u4",
+        "parse_const_u4_plus_one" => "1..16",
+        "parse_const_u4_plus_two" => "2..17",
+        "parse_const_u4_14" => "0..14",
+        "parse_const_u4_1_14" => "1..14",
+        "parse_const_u4_nonzero" => "1..16",
+        "parse_const_u5" => "u5",
+        "parse_const_u10" => "u10",
+        "parse_const_u11" => "u11",
+        "parse_const_u12" => "u12",
+        "parse_const_u14" => "u14",
+        "parse_const_u8_setcp" => "-15..239",
+        "parse_const_i8" => "i8",
+        "parse_const_u8_plus_one" => "1..256",
+        "parse_const_u8_240" => "0..239",
+        "parse_control_register" => "c(i)",
+        "parse_stack_register_u4" => "s(i)",
+        "parse_stack_register_u4_minus_one" => "s(-1..14)",
+        "parse_stack_register_u4_minus_two" => "s(-2..13)",
+        "parse_plduz_parameter" => "32..256",
+        _ => "?",
+    }
+}
+
+/// Aliases of mnemonics along with their canonical spelling, compiled by the same handler.
+/// The disassembler prints canonical mnemonics.
+pub const ALIASES: &[(&str, &str)] = &[
+    ("-ROLL",     "ROLLREV"),
+    ("-ROLLX",    "ROLLREVX"),
+    ("-ROT",      "ROTREV"),
+    ("2DROP",     "DROP2"),
+    ("2DUP",      "DUP2"),
+    ("2OVER",     "OVER2"),
+    ("2ROT",      "ROT2"),
+    ("2SWAP",     "SWAP2"),
+    ("DICTEMPTY", "ISNULL"),
+    ("JMPDICT",   "JMP"),
+    ("NEWDICT",   "PUSHNULL"),
+    ("NULL",      "PUSHNULL"),
+    ("ZERO",      "FALSE"),
+];
+
+/// Returns the canonical spelling of the mnemonic, itself if it is not an alias
+pub fn canonical_mnemonic(mnemonic: &str) -> &str {
+    ALIASES.iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(mnemonic))
+        .map_or(mnemonic, |(_, canonical)| canonical)
+}
+
+const DIV_VARIANTS: &[&str] = &[
+    "LSHIFTDIV", "LSHIFTDIVC", "LSHIFTDIVR", "LSHIFTDIVMOD", "LSHIFTDIVMODC", "LSHIFTDIVMODR",
+    "LSHIFTMOD", "LSHIFTMODC", "LSHIFTMODR", "MODPOW2", "MODPOW2C", "MODPOW2R",
+    "MULMODPOW2", "MULMODPOW2C", "MULMODPOW2R", "MULRSHIFT", "MULRSHIFTC", "MULRSHIFTR",
+    "MULRSHIFTMOD", "MULRSHIFTMODC", "MULRSHIFTMODR", "RSHIFTC", "RSHIFTR",
+    "RSHIFTMOD", "RSHIFTMODR", "RSHIFTMODC",
+];
+
+const QUIET_DIV_VARIANTS: &[&str] = &[
+    "QLSHIFTDIV", "QLSHIFTDIVC", "QLSHIFTDIVR", "QLSHIFTDIVMOD", "QLSHIFTDIVMODC", "QLSHIFTDIVMODR",
+    "QLSHIFTMOD", "QLSHIFTMODC", "QLSHIFTMODR", "QMODPOW2", "QMODPOW2C", "QMODPOW2R",
+    "QMULMODPOW2", "QMULMODPOW2C", "QMULMODPOW2R", "QMULRSHIFT", "QMULRSHIFTC", "QMULRSHIFTR",
+    "QMULRSHIFTMOD", "QMULRSHIFTMODC", "QMULRSHIFTMODR", "QRSHIFTC", "QRSHIFTR",
+    "QRSHIFTMOD", "QRSHIFTMODR", "QRSHIFTMODC",
+];
+
+const REF_COMMANDS: &[(&str, usize)] = &[
+    ("CALLREF", 16),
+    ("IFELSEREF", 16),
+    ("IFJMPREF", 16),
+    ("IFNOTJMPREF", 16),
+    ("IFNOTREF", 16),
+    ("IFREF", 16),
+    ("IFREFELSE", 16),
+    ("JMPREF", 16),
+    ("PUSHREF", 8),
+    ("PUSHREFCONT", 8),
+    ("PUSHREFSLICE", 8),
+    ("STREFCONST", 16),
+];
+
+fn complex_commands_info() -> Vec<InstructionInfo> {
+    let mut info = vec![
+        InstructionInfo::fixed("BCHKBITS", &[], 16),
+        InstructionInfo::fixed("BCHKBITS", &["1..256"], 24),
+        InstructionInfo::fixed("BCHKBITSQ", &[], 16),
+        InstructionInfo::fixed("BCHKBITSQ", &["1..256"], 24),
+        InstructionInfo::fixed("CALL", &["u8"], 16),
+        InstructionInfo::fixed("CALL", &["u14"], 24),
+        InstructionInfo::fixed("CALLDICT", &["u8"], 16),
+        InstructionInfo::fixed("CALLDICT", &["u14"], 24),
+        InstructionInfo::fixed("CALLXARGS", &["u4", "-1..14"], 16),
+        InstructionInfo::new("DEBUGSTR", &["string"], 24..=144, 0),
+        InstructionInfo::fixed("DICTPUSHCONST", &["u10"], 24),
+        InstructionInfo::new("DICTPUSHCONST", &["{dict}", "u10"], 24..=24, 1),
+        InstructionInfo::new("DUMPTOSFMT", &["string"], 24..=144, 0),
+        InstructionInfo::new("LOGSTR", &["string"], 32..=144, 0),
+        InstructionInfo::new("IFBITJMPREF", &["{block}", "u5"], 16..=16, 1),
+        InstructionInfo::new("IFNBITJMPREF", &["{block}", "u5"], 16..=16, 1),
+        InstructionInfo::fixed("LSHIFT", &[], 8),
+        InstructionInfo::fixed("LSHIFT", &["1..256"], 16),
+        InstructionInfo::fixed("POP", &["s(0..15)"], 8),
+        InstructionInfo::fixed("POP", &["s(16..255)"], 16),
+        InstructionInfo::fixed("POP", &["c(i)"], 16),
+        InstructionInfo::new("PRINTSTR", &["string"], 32..=144, 0),
+        InstructionInfo::fixed("PUSH", &["s(0..15)"], 8),
+        InstructionInfo::fixed("PUSH", &["s(16..255)"], 16),
+        InstructionInfo::fixed("PUSH", &["c(i)"], 16),
+        InstructionInfo::new("PUSHCONT", &["{short}"], 8..=128, 0),
+        InstructionInfo::new("PUSHCONT", &["{long}"], 16..=1016, 0),
+        InstructionInfo::new("PUSHCONT", &["{ref}"], 16..=16, 1),
+        InstructionInfo::fixed("PUSHINT", &["-5..10"], 8),
+        InstructionInfo::fixed("PUSHINT", &["i8"], 16),
+        InstructionInfo::fixed("PUSHINT", &["i16"], 24),
+        InstructionInfo::new("PUSHINT", &["i257"], 32..=272, 0),
+        InstructionInfo::new("PUSHSLICE", &["x{short}"], 16..=136, 0),
+        InstructionInfo::new("PUSHSLICE", &["x{long}"], 24..=1023, 0),
+        InstructionInfo::fixed("QLSHIFT", &[], 16),
+        InstructionInfo::fixed("QLSHIFT", &["1..256"], 24),
+        InstructionInfo::fixed("QRSHIFT", &[], 16),
+        InstructionInfo::fixed("QRSHIFT", &["1..256"], 24),
+        InstructionInfo::fixed("RSHIFT", &[], 8),
+        InstructionInfo::fixed("RSHIFT", &["1..256"], 16),
+        InstructionInfo::new("SDBEGINS", &["slice"], 24..=1023, 0),
+        InstructionInfo::new("SDBEGINSQ", &["slice"], 24..=1023, 0),
+        InstructionInfo::fixed("SETCONTARGS", &["u4"], 16),
+        InstructionInfo::fixed("SETCONTARGS", &["u4", "-1..14"], 16),
+        InstructionInfo::new("STSLICECONST", &["slice"], 16..=72, 0),
+        InstructionInfo::fixed("SWAP", &[], 8),
+    ];
+    for name in &["THROW", "THROWIF", "THROWIFNOT"] {
+        info.push(InstructionInfo::fixed(name, &["0..63"], 16));
+        info.push(InstructionInfo::fixed(name, &["64..2047"], 24));
+    }
+    info.push(InstructionInfo::fixed("XCHG", &[], 8));
+    info.push(InstructionInfo::fixed("XCHG", &["s(1..15)"], 8));
+    info.push(InstructionInfo::fixed("XCHG", &["s(0)", "s(1..15)"], 8));
+    info.push(InstructionInfo::fixed("XCHG", &["s(1)", "s(2..15)"], 8));
+    info.push(InstructionInfo::fixed("XCHG", &["s(0)", "s(16..255)"], 16));
+    info.push(InstructionInfo::fixed("XCHG", &["s(2..14)", "s(3..15)"], 16));
+    for (name, bits) in REF_COMMANDS {
+        info.push(InstructionInfo::new(name, &["{block}"], *bits..=*bits, 1));
+    }
+    for name in DIV_VARIANTS {
+        info.push(InstructionInfo::fixed(name, &[], 16));
+        info.push(InstructionInfo::fixed(name, &["1..256"], 24));
+    }
+    for name in QUIET_DIV_VARIANTS {
+        info.push(InstructionInfo::fixed(name, &[], 24));
+        info.push(InstructionInfo::fixed(name, &["1..256"], 32));
+    }
+    info
+}
+
+fn simple_commands_info() -> Vec<InstructionInfo> {
+    Engine::<CodePage0>::enumerate_simple_commands_info().into_iter()
+        .map(|(mnemonic, parsers, opcode)| {
+            let operands = parsers.iter().map(|p| operand_kind(p)).collect::<Vec<_>>();
+            let bits = opcode.len() * 8;
+            InstructionInfo { opcode, ..InstructionInfo::fixed(mnemonic, &operands, bits) }
+        })
+        .collect()
+}
+
+/// Returns the encoding table for every known instruction and operand shape, along with
+/// opcodes of simple instructions, e.g. for editors and documentation to list instructions.
+/// Directives such as `.blob` are not included, see `Engine::mnemonics` for all the commands.
+pub fn instructions_info() -> Vec<InstructionInfo> {
+    let simple = simple_commands_info();
+    let mut info = complex_commands_info();
+    for (alias, canonical) in ALIASES {
+        for entry in simple.iter().filter(|i| i.mnemonic == *canonical) {
+            info.push(InstructionInfo { mnemonic: alias, ..entry.clone() });
+        }
+    }
+    info.extend(simple);
+    info.sort_by(|a, b| a.mnemonic.cmp(b.mnemonic));
+    info
+}
+
+/// Returns the encodings of a single mnemonic, one entry per operand shape
+pub fn instruction_info(mnemonic: &str) -> Vec<InstructionInfo> {
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    instructions_info().into_iter().filter(|i| i.mnemonic == mnemonic).collect()
+}
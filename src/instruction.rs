@@ -0,0 +1,171 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use num::BigInt;
+use std::{collections::HashMap, fmt};
+use ton_types::SliceData;
+
+use super::fift::BlockSpan;
+use super::parse::{parse_integer, parse_slice, unbrace};
+
+/// Operand of an instruction
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Integer(BigInt),
+    /// s(i), negative indices are used by a few stack primitives
+    StackRegister(isize),
+    /// c(i)
+    ControlRegister(u8),
+    Slice(SliceData),
+    /// any other token, e.g. a string argument of DEBUGSTR
+    Text(String),
+    /// nested code in `{}` braces
+    Block(Vec<Instruction>),
+}
+
+/// Single assembler instruction with its operands
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub mnemonic: String,
+    pub operands: Vec<Operand>,
+}
+
+impl Operand {
+    /// Types operand token by its syntax only
+    pub fn parse(token: &str) -> Self {
+        let unbraced = unbrace(token);
+        let mut chars = unbraced.chars();
+        let (prefix, rest) = match chars.next() {
+            Some(prefix) => (prefix.to_ascii_uppercase(), chars.as_str()),
+            None => return Operand::Text(String::new())
+        };
+        match prefix {
+            'S' => if let Ok(index) = rest.parse::<isize>() {
+                return Operand::StackRegister(index)
+            }
+            'C' => if let Ok(index) = rest.parse::<u8>() {
+                return Operand::ControlRegister(index)
+            }
+            'X' if !rest.is_empty() => if let Ok(slice) = SliceData::from_string(rest) {
+                return Operand::Slice(slice)
+            }
+            'B' if !rest.is_empty() => match parse_slice(token, 0) {
+                Ok(data) if data.len() * 8 <= 1024 => return Operand::Slice(SliceData::new(data)),
+                _ => ()
+            }
+            _ => ()
+        }
+        match parse_integer(token) {
+            Ok(value) => Operand::Integer(value),
+            Err(_) => Operand::Text(token.to_string())
+        }
+    }
+}
+
+impl Instruction {
+    pub fn new(mnemonic: &str) -> Self {
+        Self { mnemonic: mnemonic.to_string(), operands: vec![] }
+    }
+    pub fn with_operands(mnemonic: &str, operands: Vec<Operand>) -> Self {
+        Self { mnemonic: mnemonic.to_string(), operands }
+    }
+    fn fmt_indented(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        write!(f, "{:indent$}{}", "", self.mnemonic, indent = indent)?;
+        for (i, operand) in self.operands.iter().enumerate() {
+            write!(f, "{}", if i == 0 { " " } else { ", " })?;
+            match operand {
+                Operand::Block(code) => {
+                    writeln!(f, "{{")?;
+                    for instruction in code {
+                        instruction.fmt_indented(f, indent + 2)?;
+                        writeln!(f)?;
+                    }
+                    write!(f, "{:indent$}}}", "", indent = indent)?;
+                }
+                operand => write!(f, "{}", operand)?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Integer(value) => write!(f, "{}", value),
+            Operand::StackRegister(index) => write!(f, "s{}", index),
+            Operand::ControlRegister(index) => write!(f, "c{}", index),
+            Operand::Slice(slice) => {
+                let hex = slice.to_hex_string();
+                // an empty slice is written as a lone completion tag
+                write!(f, "x{}", if hex.is_empty() { "8_" } else { hex.as_str() })
+            }
+            Operand::Text(text) => write!(f, "{}", text),
+            Operand::Block(code) => {
+                write!(f, "{{ ")?;
+                for instruction in code {
+                    write!(f, "{} ", instruction)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Collects compiled commands as instructions.
+/// Blocks are identified by their span in the source the same way
+/// as in `FiftSource`.
+#[derive(Default)]
+pub(crate) struct InstructionTree {
+    stack: Vec<Vec<Instruction>>,
+    blocks: HashMap<BlockSpan, Vec<Instruction>>,
+}
+
+impl InstructionTree {
+    /// Starts a new (possibly nested) block
+    pub(crate) fn begin(&mut self) {
+        self.stack.push(Vec::new())
+    }
+    /// Completes the current block
+    pub(crate) fn end(&mut self, span: BlockSpan) {
+        if let Some(code) = self.stack.pop() {
+            self.blocks.insert(span, code);
+        }
+    }
+    /// Returns instructions of the compiled block
+    pub(crate) fn take(&mut self, span: BlockSpan) -> Option<Vec<Instruction>> {
+        self.blocks.remove(&span)
+    }
+    /// Records command with operands typed by their syntax,
+    /// spans are given for operands being blocks
+    pub(crate) fn write_command(&mut self, operation: &str, par: &[&str], spans: &[Option<BlockSpan>]) {
+        let operands = par.iter().zip(spans).map(|(p, span)| match span.and_then(|span| self.blocks.remove(&span)) {
+            Some(code) => Operand::Block(code),
+            None => Operand::parse(p)
+        }).collect();
+        if let Some(current) = self.stack.last_mut() {
+            current.push(Instruction::with_operands(operation, operands))
+        }
+    }
+}
+
+/// Renders instructions as assembler source, one per line
+pub fn instructions_to_string(code: &[Instruction]) -> String {
+    code.iter().map(|instruction| format!("{}\n", instruction)).collect()
+}
@@ -11,32 +11,145 @@
 * limitations under the License.
 */
 
-use std::{collections::HashMap, ops::RangeInclusive};
-use ton_types::{Cell, SliceData, BuilderData};
+use std::{borrow::Cow, collections::{BTreeMap, BTreeSet, HashMap}, io::BufRead, ops::RangeInclusive, path::PathBuf, rc::Rc, sync::Arc};
+use num::BigInt;
+use ton_types::{Cell, SliceData, BuilderData, UInt256};
 
-pub use debug::{Line, Lines, DbgInfo, lines_to_string};
+pub use debug::{Line, Lines, DbgInfo, DbgNode, SourceIndex, lines_to_string};
+use debug::no_filename;
 
 mod errors;
 pub use errors::{
-    CompileError, OperationError, ParameterError, Position, 
+    CompileError, OperationError, ParameterError, Position, Suggestions,
     ToOperationParameterError,
 };
 
+mod diagnostics;
+pub use diagnostics::{Diagnostic, DiagnosticKind, Severity, SourceMap};
+
 mod debug;
+mod dbg_format;
 mod macros;
+use macros::{enter_span, event, with_writer};
 mod parse;
 mod complex;
 mod simple;
 mod convert;
 
 mod writer;
-use writer::{CodePage0, Writer};
+pub use writer::{CodePage0, Layout, NullDebug, Writer, Written};
+
+mod peephole;
 pub use debug::DbgPos;
 
+mod info;
+pub use info::{ALIASES, InstructionInfo, canonical_mnemonic, instruction_info, instructions_info};
+
+mod abi;
+pub use abi::{compile_dispatch, compile_dispatch_cached, compile_dispatch_with_options};
+#[cfg(feature = "serde")]
+pub use abi::{compile_abi_dispatch, compile_abi_dispatch_cached, compile_abi_dispatch_with_options};
+
+mod cache;
+pub use cache::{CachedCode, CodeCache, MemoryCache};
+
+mod object;
+use object::{Linker, extern_cell};
+pub use object::{Object, compile_object, link, link_with_resolver};
+
+mod fift;
+use fift::FiftSource;
+
+mod suggest;
+use suggest::suggestions;
+
+mod include;
+use include::{code_to_lines, expand_includes, has_includes, source_has_includes};
+
+mod scope;
+use scope::{Label, Macro, Repetition, Scope, is_token_char};
+
+mod instruction;
+use instruction::InstructionTree;
+pub use instruction::{Instruction, Operand, instructions_to_string};
+mod cfg;
+pub use cfg::{BasicBlock, ControlFlowGraph, Edge, EdgeKind, control_flow_graph};
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+
+mod builder;
+pub use builder::CodeBuilder;
+
+mod state_init;
+pub use state_init::{compile_code_to_tvc, state_init};
+#[cfg(feature = "fs")]
+pub use state_init::write_tvc;
+
+mod gas;
+pub use gas::{EntryGas, GasBound, GasEstimate, InstructionGas, estimate_gas, instruction_gas, worst_case_gas};
+
+mod stats;
+pub use stats::CodeStats;
+
+mod listing;
+use listing::IntoListing;
+pub use listing::Listing;
+
+mod snapshot;
+pub use snapshot::{compile_snapshot, snapshot};
+
+#[cfg(feature = "profile")]
+mod profile;
+#[cfg(feature = "profile")]
+use profile::Profiler;
+#[cfg(feature = "profile")]
+pub use profile::{CommandProfile, CompileProfile};
+
+mod dedup;
+use dedup::SharedBlocks;
+
+mod codepage;
+pub use codepage::{CodePage, StandardCodePage};
+
+mod stack;
+use stack::{Signature, StackCheck};
+pub use stack::{StackWarning, StackWarningKind};
+
+mod reach;
+use reach::Reachability;
+pub use reach::DeadCode;
+
+mod version;
+pub use version::{TargetVersion, required_version};
+
+mod disasm;
+pub use disasm::{Disassembler, disassemble, disassemble_annotated, disassemble_to_string};
+
+mod format;
+pub use format::format_code;
+
+mod tokens;
+pub use tokens::{Token, TokenKind, tokenize};
+
+mod document;
+pub use document::Document;
+
 // Basic types *****************************************************************
 /// Operation Compilation result
-type CompileResult = Result<(), OperationError>;
-type CompileHandler<T> = fn(&mut Engine<T>, &Vec<&str>, destination:&mut T, pos: DbgPos) -> CompileResult;
+pub type CompileResult = Result<(), OperationError>;
+/// Handler encoding command with given parameters into destination
+pub type CompileHandler<T> = fn(&mut Engine<T>, &Vec<&str>, destination:&mut T, pos: DbgPos) -> CompileResult;
+/// Code of global labels by their names
+type Symbols<T> = Vec<(String, T)>;
+
+/// Rule compiling command: built-in handler or macro defined in source,
+/// unknown commands are skipped along with their parameters when recovering from errors
+enum Rule<T: Writer> {
+    Handler(CompileHandler<T>),
+    Macro(Rc<Macro>),
+    Repeat(Rc<Repetition>),
+    Skip,
+}
 
 // CompileError::Operation handlers ***********************************************************
 trait EnsureParametersCountInRange {
@@ -67,33 +180,54 @@ impl<T> EnsureParametersCountInRange for Vec<T>{
 
 // Command compilation context ************************************************
 
-struct CommandContext<T> 
+/// Byte offset of the column counted in chars, past the end of the text chars count as bytes
+fn byte_offset(text: &str, column: usize) -> usize {
+    let index = column.saturating_sub(1);
+    match text.char_indices().nth(index) {
+        Some((offset, _)) => offset,
+        None => text.len() + index - text.chars().count()
+    }
+}
+
+/// Text of the line before a comment if any
+fn strip_comment(text: &str) -> &str {
+    let end = [text.find(';'), text.find("/*")].iter().flatten().min().copied();
+    &text[..end.unwrap_or(text.len())]
+}
+
+/// Parameter of a command: its position, text, whether it follows comma
+/// and the position the code of the block starts at if the parameter is a block
+type Parameter<'a> = (usize, usize, Cow<'a, str>, bool, Option<(usize, usize)>);
+
+struct CommandContext<'a, T>
 where
     T: Writer
 {
-    operation: String,
+    operation: Cow<'a, str>,
     line_no_cmd: usize,
     char_no_cmd: usize,
     line_no_par: usize,
     char_no_par: usize,
-    rule_option: Option<CompileHandler<T>>,
+    rule_option: Option<Rule<T>>,
+    labels: Vec<(usize, usize)>,
 }
 
-impl<T: Writer> Default for CommandContext<T> {
+impl<T: Writer> Default for CommandContext<'_, T> {
     fn default() -> Self {
         Self {
-            operation: String::new(),
+            operation: Cow::Borrowed(""),
             line_no_cmd: 0,
             char_no_cmd: 0,
             line_no_par: 0,
             char_no_par: 0,
             rule_option: None,
+            labels: Vec::new(),
         }
     }
     
 }
-impl<T: Writer> CommandContext<T> {
-    fn new(operation: String, char_no_cmd: usize, line_no_cmd: usize, rule_option: Option<CompileHandler<T>>) -> Self {
+impl<'a, T: Writer> CommandContext<'a, T> {
+    fn new(operation: Cow<'a, str>, char_no_cmd: usize, line_no_cmd: usize, rule_option: Option<Rule<T>>) -> Self {
         Self {
             operation,
             line_no_cmd,
@@ -101,6 +235,7 @@ impl<T: Writer> CommandContext<T> {
             line_no_par: 0,
             char_no_par: 0,
             rule_option,
+            labels: Vec::new(),
         }
     }
     fn abort<X>(&self, error: OperationError, engine: &Engine<T>) -> Result<X, CompileError> {
@@ -108,84 +243,204 @@ impl<T: Writer> CommandContext<T> {
             let pos = &line.pos;
             let filename = pos.filename.clone();
             let line = pos.line_code;
-            Err(CompileError::operation(line, self.char_no_cmd, self.operation.clone(), error).with_filename(filename))
+            Err(CompileError::operation(line, self.char_no_cmd, &self.operation, error).with_filename(filename).with_span(self.span(engine)))
         } else {
-            Err(CompileError::operation(self.line_no_cmd, self.char_no_cmd, self.operation.clone(), error))
+            Err(CompileError::operation(self.line_no_cmd, self.char_no_cmd, &self.operation, error).with_span(self.span(engine)))
         }
     }
+    /// Bytes of the command token within its line
+    fn span(&self, engine: &Engine<T>) -> std::ops::Range<usize> {
+        engine.span(self.line_no_cmd, self.char_no_cmd, self.operation.len())
+    }
     fn has_command(&self) -> bool {
         self.rule_option.is_some()
     }
     fn compile(
         &mut self,
         destination: &mut T,
-        par: &mut Vec<(usize, usize, &str, bool)>,
+        par: &mut Vec<Parameter>,
+        engine: &mut Engine<T>,
+    ) -> Result<(), CompileError> {
+        #[cfg(feature = "profile")]
+        if engine.profile.is_some() && !matches!(self.rule_option, None | Some(Rule::Skip)) {
+            let operation = self.operation.to_string();
+            if let Some(profile) = engine.profile.as_mut() {
+                profile.begin()
+            }
+            let result = self.compile_rule(destination, par, engine);
+            if let Some(profile) = engine.profile.as_mut() {
+                profile.end_command(&operation)
+            }
+            return result
+        }
+        self.compile_rule(destination, par, engine)
+    }
+    fn compile_rule(
+        &mut self,
+        destination: &mut T,
+        par: &mut Vec<Parameter>,
         engine: &mut Engine<T>,
     ) -> Result<(), CompileError> {
         let rule = match self.rule_option.as_ref() {
+            Some(Rule::Skip) => {
+                par.clear();
+                self.rule_option = None;
+                return Ok(())
+            }
             Some(rule) => rule,
             None => return Ok(())
         };
+        let _span = enter_span!(TRACE, "instruction", mnemonic = %self.operation, line = self.line_no_cmd, column = self.char_no_cmd);
         let (line_no, char_no) = engine.set_pos(self.line_no_par, self.char_no_par);
+        // labels referenced by the command are being expanded while it compiles
+        let depth = engine.expanding.len();
+        engine.expanding.append(&mut self.labels);
+        let instructions = engine.instructions;
+        let regions = engine.reach.as_ref().map_or(0, |reach| reach.len());
+        // unreachable command is compiled into a scratch writer when eliminated, directives are kept
+        let dead = matches!(rule, Rule::Handler(_)) && !self.operation.starts_with('.')
+            && engine.reach.as_ref().is_some_and(|reach| reach.is_dead());
+        let eliminated = dead && engine.reach.as_ref().is_some_and(|reach| reach.eliminate);
         let mut n = par.len();
+        // parameters borrow the source, the last ones are dropped on retry
+        let mut args = par.iter().map(|(_, _, e, _, _)| e.as_ref()).collect::<Vec<_>>();
+        // blocks are recorded by the span their code is compiled from
+        let spans = par.iter()
+            .map(|(_, _, e, _, block)| block.map(|(line, column)| (line, column, e.len())))
+            .collect::<Vec<_>>();
         loop {
-            let par = &par[0..n].iter().map(|(_, _, e, _)| *e).collect::<Vec<_>>();
-            let pos = if let Some(line) = engine.lines.get(self.line_no_cmd - 1) {
-                line.pos.clone()
+            args.truncate(n);
+            let blocks = par[0..n].iter().map(|(_, _, _, _, block)| block.is_some());
+            let par = &args;
+            // analyses report positions even if debug info is skipped
+            let skip = engine.options.skip_debug_info && engine.stack.is_none() && engine.reach.is_none()
+                && engine.options.visitor.is_none();
+            let pos = if let Some(line) = engine.lines.get(self.line_no_cmd - 1).filter(|_| !skip) {
+                let mut pos = line.pos.clone();
+                pos.column = self.char_no_cmd;
+                pos.span = self.span(engine);
+                pos
             } else {
                 DbgPos::default()
             };
-            match rule(engine, par, destination, pos) {
-                Ok(_) => break,
+            let stack_pos = engine.stack.as_ref().map(|_| pos.clone());
+            let reach_pos = engine.reach.as_ref().map(|_| pos.clone());
+            let visit_pos = engine.options.visitor.as_ref().filter(|_| !eliminated).map(|_| pos.clone());
+            // commands generated from another source carry its position set by `.loc`
+            let pos = match engine.scopes.last().filter(|_| !skip).and_then(|scope| scope.location(self.line_no_cmd)) {
+                Some(location) => DbgPos { line_code: pos.line_code, ..location.clone() },
+                None => pos
+            };
+            let result = match rule {
+                Rule::Handler(handler) if eliminated => engine.check_version(&self.operation).and_then(|_| handler(engine, par, &mut T::new(), pos)),
+                Rule::Handler(handler) => {
+                    if visit_pos.is_some() {
+                        destination.record()
+                    }
+                    engine.check_version(&self.operation).and_then(|_| handler(engine, par, destination, pos))
+                }
+                Rule::Macro(definition) => {
+                    // blocks are substituted into macro body along with their braces
+                    let args = par.iter().zip(blocks).map(|(arg, block)| match block {
+                        true => format!("{{{}}}", arg),
+                        false => arg.to_string()
+                    }).collect::<Vec<_>>();
+                    engine.expand_macro(definition, &args, destination)
+                }
+                Rule::Repeat(repetition) => engine.repeat(repetition, par, destination),
+                Rule::Skip => Ok(())
+            };
+            let result = match (result, rule) {
+                (Ok(_), Rule::Handler(_)) if !eliminated => engine.check_limits(destination),
+                (result, _) => result
+            };
+            let result = match (result, rule, visit_pos) {
+                (Ok(_), Rule::Handler(_), Some(pos)) => engine.visit(&self.operation, par, &pos, destination),
+                (result, _, _) => result
+            };
+            match result {
+                Ok(_) => {
+                    // expanded macro body records its own commands
+                    if let (Rule::Handler(_), Some(reach), Some(pos)) = (rule, engine.reach.as_mut(), reach_pos) {
+                        if !self.operation.starts_with('.') {
+                            reach.command(&self.operation, &pos)
+                        }
+                    }
+                    if let (Rule::Handler(_), false) = (rule, eliminated) {
+                        if let Some(fift) = engine.fift.as_mut() {
+                            fift.write_command(&self.operation, par, &spans)
+                        }
+                        if let Some(tree) = engine.tree.as_mut() {
+                            tree.write_command(&self.operation, par, &spans)
+                        }
+                        if let Some((stack, pos)) = engine.stack.as_mut().zip(stack_pos) {
+                            stack.command(&self.operation, par, &pos)
+                        }
+                    }
+                    break
+                }
                 Err(OperationError::TooManyParameters) if n != 0 => {
+                    // commands of blocks compiled by the failed attempt are counted again
+                    engine.instructions = instructions;
+                    if let Some(reach) = engine.reach.as_mut() {
+                        reach.truncate(regions)
+                    }
                     n -= 1;
                 }
-                Err(e) => return self.abort(e, engine)
+                Err(e) => {
+                    event!(debug, error = %e, "instruction failed");
+                    engine.expanding.truncate(depth);
+                    engine.set_pos(line_no, char_no);
+                    return self.abort(e, engine)
+                }
             }
         }
+        engine.expanding.truncate(depth);
         engine.set_pos(line_no, char_no);
         self.rule_option = None;
         // detecting some errors here if was
         if n > 1 {
-            for (line, column, _, was_comma) in &par[1..n] {
+            for (line, column, token, was_comma, block) in &par[1..n] {
                 if !*was_comma {
+                    let span = engine.span(*line, *column, if block.is_some() { 0 } else { token.len() });
                     if let Some(line) = engine.lines.get(*line - 1) {
                         let pos = &line.pos;
-                        return Err(CompileError::syntax(pos.line_code, *column, "Missing comma").with_filename(pos.filename.clone()))
+                        return Err(CompileError::syntax(pos.line_code, *column, "Missing comma").with_filename(pos.filename.clone()).with_span(span))
                     } else {
-                        return Err(CompileError::syntax(*line, *column, "Missing comma"))
+                        return Err(CompileError::syntax(*line, *column, "Missing comma").with_span(span))
                     }
                 }
             }
         }
         par.drain(..n);
         if !par.is_empty() {
-            let (line, column, token, was_comma) = par.remove(0);
+            let (line, column, token, was_comma, block) = par.remove(0);
+            let span = engine.span(line, column, if block.is_some() { 0 } else { token.len() });
             let position = if let Some(line) = engine.lines.get(line - 1) {
                 let pos = &line.pos;
                 let filename = pos.filename.clone();
                 let line = pos.line_code;
-                Position { filename, line, column }
+                Position { filename, line, column, span }
             } else {
-                Position { filename: String::new(), line, column }
+                Position { filename: no_filename(), line, column, span }
             };
             if was_comma {
                 return Err(CompileError::Operation(
                     position,
-                    self.operation.clone(),
+                    self.operation.to_string(),
                     OperationError::TooManyParameters,
                 ))
             } else if n == 0 {
                 // or CompileError::Operation
                 return Err(CompileError::Operation(
                     position,
-                    self.operation.clone(),
+                    self.operation.to_string(),
                     OperationError::TooManyParameters,
                 ))
             } else {
                 // or CompileError::Syntax "missing comma"
                 return Err(CompileError::UnknownOperation(
-                    position, token.into()
+                    position, token.to_string(), engine.suggestions(&token)
                 ))
             }
         }
@@ -193,6 +448,137 @@ impl<T: Writer> CommandContext<T> {
     }
 }
 
+// Compilation options ********************************************************
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompileOptions {
+    /// Emit PUSHREFCONT instead of PUSHCONT with the block in a reference when it does not fit in-cell encodings
+    pub auto_pushrefcont: bool,
+    /// Rewrite adjacent commands into shorter or cheaper equivalents, encode powers of two
+    /// pushed by PUSHINT as PUSHPOW2, PUSHPOW2DEC or PUSHNEGPOW2
+    pub optimize: bool,
+    /// Directories to look up files of `.include "path"` directives in
+    pub include_dirs: Vec<PathBuf>,
+    /// Argument of SETCP selecting codepage to compile with
+    pub codepage: i16,
+    /// Limits of code size checked while commands are written
+    pub limits: Limits,
+    /// Version of TVM to reject instructions introduced later than, None for the latest one
+    pub target_version: Option<TargetVersion>,
+    /// Provides symbols declared `.extern` and not defined by the program,
+    /// see `compile_program`, `compile_object` and `link_with_resolver`
+    pub resolver: Option<Resolver>,
+    /// Match mnemonics only written in upper case as they are registered,
+    /// directives and macros are matched ignoring case either way
+    pub case_sensitive: bool,
+    /// Constants visible to the code as if defined by `.equ`, the code may redefine them.
+    /// Operands may also take their values in parts with `${NAME}` placeholders, e.g. `x${ADDRESS}`
+    pub defines: BTreeMap<String, String>,
+    /// Do not track source positions of commands, debug info returned is empty
+    pub skip_debug_info: bool,
+    /// Drop commands following unconditional RET, JMPX or THROW up to the next label
+    /// or the end of the block, see `find_dead_code`
+    pub eliminate_dead_code: bool,
+    /// Kinds of warnings failing compilation of the code, such as unreachable code,
+    /// see `compile_code_with_diagnostics`. Separately compiled objects are not checked.
+    pub deny: BTreeSet<DiagnosticKind>,
+    /// Called for every command compiled, e.g. by coverage tools or custom validators
+    pub visitor: Option<Visitor>,
+    /// Put blocks of PUSHCONT with identical code into a single cell taken by PUSHREFCONT
+    /// where it makes the code smaller. The code is compiled twice, loading the cell costs gas.
+    pub dedup_blocks: bool,
+    /// How cells of the code are joined on finalization, pin one other than `Layout::Latest`
+    /// for the code to stay the same when the assembler is updated
+    pub layout: Layout,
+    /// Cell attached as the last reference of the root cell of the code, e.g. with compiler version,
+    /// hash of the sources or salt. The code is ended with RET so that it never runs into the cell.
+    pub metadata: Option<Cell>,
+}
+
+/// Value of external symbol provided by the environment
+#[derive(Clone, Debug, PartialEq)]
+pub enum Symbol {
+    /// Cell taken by `@name` reference, such as code of precompiled continuation
+    Cell(Cell),
+    /// Number taken by `name` parameter, such as address or code hash
+    Integer(BigInt),
+}
+
+type Resolve = dyn Fn(&str) -> Option<Symbol> + Send + Sync;
+
+/// Callback giving value of external symbol by its name
+#[derive(Clone)]
+pub struct Resolver(Arc<Resolve>);
+
+impl Resolver {
+    pub fn new<F: Fn(&str) -> Option<Symbol> + Send + Sync + 'static>(resolve: F) -> Self {
+        Self(Arc::new(resolve))
+    }
+    pub fn resolve(&self, name: &str) -> Option<Symbol> {
+        (self.0)(name)
+    }
+}
+
+impl std::fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Resolver")
+    }
+}
+
+impl PartialEq for Resolver {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Command compiled into the code, see `Visitor`
+pub struct CompiledCommand<'a> {
+    /// mnemonic as written in the source
+    pub mnemonic: &'a str,
+    pub operands: &'a [&'a str],
+    pub position: &'a DbgPos,
+    /// encodings written by the command and their places in the chain of cells,
+    /// blocks kept in references are not included. Empty if commands are rewritten by optimizations.
+    pub written: &'a [(SliceData, Written)],
+}
+
+type Visit = dyn Fn(&CompiledCommand) -> Result<(), String> + Send + Sync;
+
+/// Callback taking every command compiled, its error fails compilation with the message.
+/// Commands of nested blocks are visited before the one containing them.
+/// Procedure bodies of dispatchers are compiled on several threads, so it may be called from any of them.
+#[derive(Clone)]
+pub struct Visitor(Arc<Visit>);
+
+impl Visitor {
+    pub fn new<F: Fn(&CompiledCommand) -> Result<(), String> + Send + Sync + 'static>(visit: F) -> Self {
+        Self(Arc::new(visit))
+    }
+    pub fn visit(&self, command: &CompiledCommand) -> Result<(), String> {
+        (self.0)(command)
+    }
+}
+
+impl std::fmt::Debug for Visitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Visitor")
+    }
+}
+
+impl PartialEq for Visitor {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Structural limits of compiled code, None for no limit
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_depth: Option<usize>,
+    pub max_cells: Option<usize>,
+    pub max_instructions: Option<usize>,
+}
+
 // Compilation engine *********************************************************
 
 #[allow(non_snake_case)]
@@ -200,6 +586,23 @@ pub struct Engine<T: Writer> {
     line_no: usize,
     char_no: usize,
     lines: Lines,
+    options: CompileOptions,
+    fift: Option<FiftSource>,
+    tree: Option<InstructionTree>,
+    stack: Option<StackCheck>,
+    reach: Option<Reachability>,
+    #[cfg(feature = "profile")]
+    profile: Option<Profiler>,
+    blocks: Option<SharedBlocks>,
+    /// blocks of the command encoded from typed operands, compiled already
+    compiled: Vec<T>,
+    scopes: Vec<Scope>,
+    expanding: Vec<(usize, usize)>,
+    macro_calls: Vec<Rc<Macro>>,
+    errors: Option<Vec<CompileError>>,
+    instructions: usize,
+    codepage: i16,
+    codepages: HashMap<i16, Rc<dyn CodePage<T>>>,
     COMPILE_ROOT: HashMap<&'static str, CompileHandler<T>>,
 }
 
@@ -212,13 +615,104 @@ impl<T: Writer> Engine<T> {
             line_no: 1,
             char_no: 1,
             lines,
+            options: CompileOptions::default(),
+            fift: None,
+            tree: None,
+            stack: None,
+            reach: None,
+            #[cfg(feature = "profile")]
+            profile: None,
+            blocks: None,
+            compiled: Vec::new(),
+            scopes: Vec::new(),
+            expanding: Vec::new(),
+            macro_calls: Vec::new(),
+            errors: None,
+            instructions: 0,
+            codepage: 0,
+            codepages: HashMap::new(),
             COMPILE_ROOT: HashMap::new(),
         };
+        ret.codepages.insert(0, Rc::new(StandardCodePage));
         ret.add_complex_commands();
         ret.add_simple_commands();
+        // an alias clashing with a command is skipped, the table is checked by tests
+        for (alias, canonical) in info::ALIASES {
+            if let Err(_e) = ret.register_alias(alias, canonical) {
+                event!(debug, error = %_e, "alias skipped");
+            }
+        }
         ret
     }
 
+    pub fn with_options(lines: Lines, options: &CompileOptions) -> Engine<T> {
+        let mut ret = Engine::<T>::new(lines);
+        ret.options = options.clone();
+        // denied warnings are looked for anyway
+        if options.eliminate_dead_code || !options.deny.is_empty() {
+            ret.reach = Some(Reachability::new(options.eliminate_dead_code));
+        }
+        ret
+    }
+
+    /// Engine putting blocks compiled several times into shared cells if options ask to,
+    /// the code is compiled by the first engine to find them
+    fn sharing_blocks<F>(lines: Lines, options: &CompileOptions, compile: F) -> Result<Engine<T>, CompileError>
+    where F: FnOnce(&mut Engine<T>) -> Result<(), CompileError> {
+        if !options.dedup_blocks {
+            return Ok(Engine::with_options(lines, options))
+        }
+        let mut first = Engine::<T>::with_options(lines.clone(), options);
+        first.options.visitor = None;
+        first.blocks = Some(SharedBlocks::default());
+        compile(&mut first)?;
+        let mut engine = Engine::with_options(lines, options);
+        engine.blocks = first.blocks.take().map(SharedBlocks::share);
+        Ok(engine)
+    }
+
+    /// Names of the commands registered, built-in ones and added by `register`, in order
+    pub fn mnemonics(&self) -> Vec<&'static str> {
+        let mut names = self.COMPILE_ROOT.keys().copied().collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    /// Adds command compiled by the handler, replacing a built-in one of the same name.
+    /// Returns the handler replaced if any.
+    pub fn register(&mut self, name: &'static str, handler: CompileHandler<T>) -> Option<CompileHandler<T>> {
+        self.COMPILE_ROOT.insert(name, handler)
+    }
+
+    /// Makes the alias compile by the handler of the canonical command, the command
+    /// of the same name if any is kept
+    pub fn register_alias(&mut self, alias: &'static str, canonical: &str) -> Result<(), CompileError> {
+        let handler = *self.COMPILE_ROOT.get(canonical)
+            .ok_or_else(|| CompileError::syntax(0, 0, format!("Alias {} of unknown command {}", alias, canonical)))?;
+        if self.COMPILE_ROOT.contains_key(alias) {
+            return Err(CompileError::syntax(0, 0, format!("Alias {} redefines command", alias)))
+        }
+        self.COMPILE_ROOT.insert(alias, handler);
+        Ok(())
+    }
+
+    /// Makes codepage available for selection by its number, replacing one of the same number
+    pub fn register_codepage(&mut self, codepage: Rc<dyn CodePage<T>>) {
+        self.codepages.insert(codepage.number(), codepage);
+    }
+
+    /// Switches to commands of the codepage, dropping commands registered for the previous one
+    pub fn set_codepage(&mut self, number: i16) -> Result<(), CompileError> {
+        if number == self.codepage {
+            return Ok(())
+        }
+        let codepage = self.codepages.get(&number)
+            .ok_or_else(|| CompileError::syntax(0, 0, format!("Unknown codepage {}", number)))?;
+        self.COMPILE_ROOT = codepage.commands();
+        self.codepage = number;
+        Ok(())
+    }
+
     fn is_whitespace(x: char) -> bool {
         match x {
             ' ' => true,
@@ -235,9 +729,680 @@ impl<T: Writer> Engine<T> {
         (l, c)
     }
 
-    fn compile(&mut self, source: &str) -> Result<T, CompileError> {
-        let mut ret = T::new();
-        let mut par: Vec<(usize, usize, &str, bool)> = Vec::new();
+    /// Known commands and macros with names similar to the unknown one
+    fn suggestions(&self, name: &str) -> Suggestions {
+        let macros = self.scopes.last().into_iter().flat_map(|scope| scope.macros.keys().map(|name| name.as_str()));
+        suggestions(name, self.COMPILE_ROOT.keys().copied().chain(macros))
+    }
+
+    /// Records error when recovering from errors, returns it otherwise
+    fn report(&mut self, error: CompileError) -> Result<(), CompileError> {
+        match self.errors.as_mut() {
+            Some(errors) => {
+                errors.push(error);
+                Ok(())
+            }
+            None => Err(error)
+        }
+    }
+
+    fn syntax_error<S: ToString>(&self, line: usize, column: usize, explanation: S) -> CompileError {
+        if let Some(line) = self.lines.get(line - 1) {
+            let pos = &line.pos;
+            CompileError::syntax(pos.line_code, column, explanation).with_filename(pos.filename.clone())
+        } else {
+            CompileError::syntax(line, column, explanation)
+        }
+    }
+
+    /// Replaces `${NAME}` placeholders of the operand with values of defines of options
+    fn substitute<'a>(&self, operand: &'a str, line: usize, column: usize) -> Result<Cow<'a, str>, CompileError> {
+        if !operand.contains("${") {
+            return Ok(Cow::Borrowed(operand))
+        }
+        let error = |explanation: String| self.syntax_error(line, column, explanation).with_span(self.span(line, column, operand.len()));
+        let mut result = String::new();
+        let mut rest = operand;
+        while let Some(start) = rest.find("${") {
+            result.push_str(&rest[..start]);
+            let (name, tail) = rest[start + 2..].split_once('}')
+                .ok_or_else(|| error(format!("Unterminated placeholder in {}", operand)))?;
+            let value = self.options.defines.get(name)
+                .ok_or_else(|| error(format!("Placeholder of unknown define {}", name)))?;
+            result.push_str(value);
+            rest = tail;
+        }
+        result.push_str(rest);
+        Ok(Cow::Owned(result))
+    }
+
+    /// Scope of the constants defined by options
+    fn defined(&self) -> Scope {
+        let mut scope = Scope::default();
+        scope.constants.extend(self.options.defines.clone());
+        scope
+    }
+
+    /// Collects labels, macros and constants defined in the block itself, not in its nested blocks.
+    /// Macro and constant definitions are blanked out of the returned text except line breaks,
+    /// so positions of the rest of the code are kept.
+    fn scan<'a>(&mut self, source: &'a str) -> Result<(Cow<'a, str>, Scope), CompileError> {
+        #[cfg(feature = "profile")]
+        if let Some(profile) = self.profile.as_mut() {
+            profile.begin()
+        }
+        let result = self.scan_block(source);
+        #[cfg(feature = "profile")]
+        if let Some(profile) = self.profile.as_mut() {
+            profile.end_parse()
+        }
+        result
+    }
+
+    fn scan_block<'a>(&self, source: &'a str) -> Result<(Cow<'a, str>, Scope), CompileError> {
+        let _span = enter_span!(TRACE, "parse", line = self.line_no, column = self.char_no);
+        let mut scope = Scope::default();
+        let mut labels = vec![];
+        let mut definitions = vec![];
+        let mut commented = vec![];
+        let (mut line, mut column) = (self.line_no, self.char_no);
+        let mut in_block = 0;
+        let mut in_comment = false;
+        let mut token_start = None;
+        let mut skip_until = 0;
+        // nesting level of /* ... */ comments
+        let mut block_comment = 0;
+        // closing quote of character or string literal being skipped
+        let mut literal = None;
+        let mut escaped = false;
+        for (i, ch) in source.char_indices().chain(std::iter::once((source.len(), ' '))) {
+            if ch == '\n' {
+                line += 1;
+                column = 1
+            } else {
+                column += 1
+            }
+            if i < skip_until {
+                continue;
+            }
+            if in_comment {
+                in_comment = (ch != '\r') && (ch != '\n');
+                continue;
+            }
+            if block_comment > 0 {
+                if source[i..].starts_with("*/") {
+                    block_comment -= 1;
+                    skip_until = i + 2
+                } else if source[i..].starts_with("/*") {
+                    block_comment += 1;
+                    skip_until = i + 2
+                }
+                continue;
+            }
+            if let Some(closing) = literal {
+                if (ch == '\n') || (!escaped && (ch == closing)) {
+                    literal = None
+                }
+                escaped = !escaped && (ch == '\\') && (closing == '"');
+                continue;
+            }
+            if is_token_char(ch) {
+                token_start.get_or_insert((i, line));
+                continue;
+            }
+            if let Some((start, start_line)) = token_start.take() {
+                let token = &source[start..i];
+                if in_block == 0 && ch == ':' {
+                    labels.push((token, i + 1, line, column));
+                    continue;
+                }
+                if in_block == 0 && token.eq_ignore_ascii_case(".macro") {
+                    let (definition, end) = self.scan_macro(source, start, start_line)?;
+                    if self.COMPILE_ROOT.contains_key(definition.name.as_str()) {
+                        return Err(self.syntax_error(start_line, 1, format!("Macro {} redefines command", definition.name)))
+                    }
+                    if let Some(definition) = scope.macros.insert(definition.name.clone(), Rc::new(definition)) {
+                        return Err(self.syntax_error(start_line, 1, format!("Duplicate macro {}", definition.name)))
+                    }
+                    definitions.push(start..end);
+                    skip_until = end;
+                    continue;
+                }
+                if in_block == 0 && token.eq_ignore_ascii_case(".rep") {
+                    let (repetition, end) = self.scan_repetition(source, start, start_line)?;
+                    scope.repetitions.insert(start_line, Rc::new(repetition));
+                    // the directive is kept to expand the body in its place
+                    definitions.push(i..end);
+                    skip_until = end;
+                    continue;
+                }
+                if in_block == 0 && token.eq_ignore_ascii_case(".sig") {
+                    let end = source[i..].find('\n').map_or(source.len(), |n| i + n);
+                    let signature = Signature::parse(strip_comment(&source[i..end]))
+                        .ok_or_else(|| self.syntax_error(start_line, 1, "Bad .sig directive"))?;
+                    // signatures following a label are of the code of the label
+                    if labels.is_empty() {
+                        self.declare_signature(&mut scope, signature, start_line)?;
+                    }
+                    // the directive is turned into a comment for code of labels to see it
+                    commented.push(start);
+                    skip_until = end;
+                    continue;
+                }
+                if in_block == 0 && token.eq_ignore_ascii_case(".loc") {
+                    let (pos, end) = self.scan_location(source, start, start_line)?;
+                    scope.locations.insert(start_line, pos);
+                    definitions.push(start..end);
+                    skip_until = end;
+                    continue;
+                }
+                if in_block == 0 && (token.eq_ignore_ascii_case(".globl") || token.eq_ignore_ascii_case(".extern")) {
+                    let directive = token.to_ascii_lowercase();
+                    if !self.scopes.is_empty() {
+                        return Err(self.syntax_error(start_line, 1, format!("{} directive is allowed at top level only", directive)))
+                    }
+                    let (names, end) = self.scan_names(source, start, start_line, &directive)?;
+                    match directive.as_str() {
+                        ".globl" => scope.globals.insert(start_line, names),
+                        _ => scope.externs.insert(start_line, names)
+                    };
+                    definitions.push(start..end);
+                    skip_until = end;
+                    continue;
+                }
+                if in_block == 0 && token.eq_ignore_ascii_case(".equ") {
+                    let (name, value, end) = self.scan_constant(source, start, start_line)?;
+                    if self.COMPILE_ROOT.contains_key(name.to_ascii_uppercase().as_str()) {
+                        return Err(self.syntax_error(start_line, 1, format!("Constant {} redefines command", name)))
+                    }
+                    if scope.constants.insert(name.clone(), value).is_some() {
+                        return Err(self.syntax_error(start_line, 1, format!("Duplicate constant {}", name)))
+                    }
+                    definitions.push(start..end);
+                    skip_until = end;
+                    continue;
+                }
+            }
+            match ch {
+                '{' => in_block += 1,
+                '}' => in_block -= 1,
+                ';' if in_block == 0 => {
+                    in_comment = true;
+                    let end = source[i..].find('\n').map_or(source.len(), |n| i + n);
+                    match Signature::parse_comment(&source[i + 1..end]) {
+                        Some(signature) if labels.is_empty() => self.declare_signature(&mut scope, signature, line)?,
+                        _ => ()
+                    }
+                }
+                '/' if source[i..].starts_with("/*") => {
+                    block_comment = 1;
+                    skip_until = i + 2
+                }
+                '\'' | '"' => literal = Some(ch),
+                _ => ()
+            }
+        }
+        let text = if definitions.is_empty() && commented.is_empty() {
+            Cow::Borrowed(source)
+        } else {
+            // every char is blanked by a single space to keep columns
+            let text = source.char_indices().map(|(i, ch)| match ch {
+                '\r' | '\n' => ch,
+                _ if commented.contains(&i) => ';',
+                _ if definitions.iter().any(|range| range.contains(&i)) => ' ',
+                _ => ch
+            });
+            Cow::Owned(text.collect())
+        };
+        for (name, offset, line, column) in labels {
+            let body = Rc::from(&text[offset..]);
+            if scope.labels.insert(name.to_string(), Label { line, column, body }).is_some() {
+                return Err(self.syntax_error(line, column - 1, format!("Duplicate label {}", name)))
+            }
+        }
+        Ok((text, scope))
+    }
+
+    /// Parses macro definition starting at the offset,
+    /// returns it along with the offset of the definition end
+    fn scan_macro(&self, source: &str, start: usize, line: usize) -> Result<(Macro, usize), CompileError> {
+        let header_start = start + ".macro".len();
+        let header_end = source[header_start..].find('\n').map_or(source.len(), |n| header_start + n);
+        let header = strip_comment(&source[header_start..header_end]).trim();
+        let (name, params) = match header.find(char::is_whitespace) {
+            Some(n) => (&header[..n], header[n..].trim()),
+            None => (header, "")
+        };
+        if name.is_empty() || !name.chars().all(is_token_char) {
+            return Err(self.syntax_error(line, 1, "Bad macro name"))
+        }
+        let params = match params {
+            "" => vec![],
+            params => params.split(',').map(|param| param.trim().to_string()).collect::<Vec<_>>()
+        };
+        if params.iter().any(|param| param.is_empty() || !param.chars().all(is_token_char)) {
+            return Err(self.syntax_error(line, 1, format!("Bad parameters of macro {}", name)))
+        }
+        let body_start = std::cmp::min(header_end + 1, source.len());
+        let mut offset = body_start;
+        let mut body_line = line + 1;
+        while offset < source.len() {
+            let line_end = source[offset..].find('\n').map_or(source.len(), |n| offset + n);
+            let text = source[offset..line_end].trim_start();
+            let first = text.split(|ch| !is_token_char(ch)).next().unwrap_or_default();
+            if first.eq_ignore_ascii_case(".endmacro") {
+                let definition = Macro {
+                    name: name.to_ascii_uppercase(),
+                    params,
+                    body: source[body_start..offset].to_string(),
+                    line: line + 1,
+                    column: 1,
+                };
+                return Ok((definition, line_end - text.len() + first.len()))
+            }
+            if first.eq_ignore_ascii_case(".macro") {
+                return Err(self.syntax_error(body_line, 1, "Nested macro definition"))
+            }
+            offset = line_end + 1;
+            body_line += 1;
+        }
+        Err(self.syntax_error(line, 1, format!("Missing .endmacro of macro {}", name)))
+    }
+
+    /// Parses `.rep count[, counter]` directive starting at the offset up to its `.endr` line,
+    /// returns it along with the offset of the end of `.endr`
+    fn scan_repetition(&self, source: &str, start: usize, line: usize) -> Result<(Repetition, usize), CompileError> {
+        let header_start = start + ".rep".len();
+        let header_end = source[header_start..].find('\n').map_or(source.len(), |n| header_start + n);
+        let header = strip_comment(&source[header_start..header_end]);
+        let tokens = header.split(',').map(str::trim).collect::<Vec<_>>();
+        let (count, counter) = match tokens[..] {
+            [count] => (count, None),
+            [count, counter] => (count, Some(counter)),
+            _ => return Err(self.syntax_error(line, 1, "Bad .rep directive"))
+        };
+        if count.is_empty() || !count.chars().all(is_token_char)
+            || counter.is_some_and(|counter| counter.is_empty() || !counter.chars().all(is_token_char)) {
+            return Err(self.syntax_error(line, 1, "Bad .rep directive"))
+        }
+        let body_start = std::cmp::min(header_end + 1, source.len());
+        let mut offset = body_start;
+        let mut depth = 0;
+        while offset < source.len() {
+            let line_end = source[offset..].find('\n').map_or(source.len(), |n| offset + n);
+            let text = source[offset..line_end].trim_start();
+            let first = text.split(|ch| !is_token_char(ch)).next().unwrap_or_default();
+            if first.eq_ignore_ascii_case(".rep") {
+                depth += 1
+            } else if first.eq_ignore_ascii_case(".endr") && depth > 0 {
+                depth -= 1
+            } else if first.eq_ignore_ascii_case(".endr") {
+                let body = Macro {
+                    name: ".REP".to_string(),
+                    params: counter.into_iter().map(String::from).collect(),
+                    body: source[body_start..offset].to_string(),
+                    line: line + 1,
+                    column: 1,
+                };
+                let repetition = Repetition { count: count.to_string(), body: Rc::new(body) };
+                return Ok((repetition, line_end - text.len() + first.len()))
+            }
+            offset = line_end + 1;
+        }
+        Err(self.syntax_error(line, 1, "Missing .endr of .rep directive"))
+    }
+
+    /// Parses `.equ NAME value` definition starting at the offset,
+    /// returns the name and the value along with the offset of the definition end
+    fn scan_constant(&self, source: &str, start: usize, line: usize) -> Result<(String, String, usize), CompileError> {
+        let definition_start = start + ".equ".len();
+        let end = source[definition_start..].find('\n').map_or(source.len(), |n| definition_start + n);
+        let definition = strip_comment(&source[definition_start..end]);
+        let tokens = definition.split(|ch: char| ch == ',' || Engine::<T>::is_whitespace(ch))
+            .filter(|token| !token.is_empty())
+            .collect::<Vec<_>>();
+        match tokens[..] {
+            [name, value] if name.chars().all(is_token_char) && value.chars().all(is_token_char) =>
+                Ok((name.to_string(), value.to_string(), end)),
+            _ => Err(self.syntax_error(line, 1, "Bad .equ definition"))
+        }
+    }
+
+    /// Parses `.globl name, ...` or `.extern name, ...` directive starting at the offset,
+    /// returns the names declared along with the offset of the directive end
+    fn scan_names(&self, source: &str, start: usize, line: usize, directive: &str) -> Result<(Vec<String>, usize), CompileError> {
+        let directive_start = start + directive.len();
+        let end = source[directive_start..].find('\n').map_or(source.len(), |n| directive_start + n);
+        let names = strip_comment(&source[directive_start..end])
+            .split(|ch: char| ch == ',' || Engine::<T>::is_whitespace(ch))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.to_string())
+            .collect::<Vec<_>>();
+        if names.is_empty() || names.iter().any(|name| !name.chars().all(is_token_char)) {
+            return Err(self.syntax_error(line, 1, format!("Bad {} directive", directive)))
+        }
+        Ok((names, end))
+    }
+
+    /// Parses `.loc file line [column]` directive starting at the offset,
+    /// returns the position it sets along with the offset of the directive end
+    fn scan_location(&self, source: &str, start: usize, line: usize) -> Result<(DbgPos, usize), CompileError> {
+        let directive_start = start + ".loc".len();
+        let end = source[directive_start..].find('\n').map_or(source.len(), |n| directive_start + n);
+        let tokens = strip_comment(&source[directive_start..end])
+            .split(|ch: char| ch == ',' || Engine::<T>::is_whitespace(ch))
+            .filter(|token| !token.is_empty())
+            .collect::<Vec<_>>();
+        let location = match tokens[..] {
+            [filename, source_line] => Some((filename, source_line, "0")),
+            [filename, source_line, column] => Some((filename, source_line, column)),
+            _ => None
+        };
+        let pos = location.and_then(|(filename, source_line, column)| Some(DbgPos {
+            filename: filename.trim_matches('"').into(),
+            line: source_line.parse().ok()?,
+            line_code: line,
+            column: column.parse().ok()?,
+            span: 0..0,
+        }));
+        pos.map(|pos| (pos, end)).ok_or_else(|| self.syntax_error(line, 1, "Bad .loc directive"))
+    }
+
+    /// Findings of the analyses enabled, taken out of the engine,
+    /// fails on the first warning of the kinds denied by options
+    fn take_diagnostics(&mut self) -> Result<Vec<Diagnostic>, CompileError> {
+        let mut diagnostics = Vec::new();
+        if let Some(stack) = self.stack.as_mut() {
+            diagnostics.extend(stack.take().into_iter().map(Diagnostic::from));
+        }
+        if let Some(reach) = self.reach.as_mut() {
+            let removed = reach.eliminate;
+            diagnostics.extend(reach.take().into_iter().map(|dead| Diagnostic::dead_code(dead, removed)));
+        }
+        match diagnostics.iter().find_map(|diagnostic| diagnostic.denied(&self.options)) {
+            Some(error) => Err(error),
+            None => Ok(diagnostics)
+        }
+    }
+
+    /// Sets stack signature of the block, one per block
+    fn declare_signature(&self, scope: &mut Scope, signature: Signature, line: usize) -> Result<(), CompileError> {
+        if scope.signature.is_some() {
+            return Err(self.syntax_error(line, 1, "Duplicate stack signature of the block"))
+        }
+        scope.signature = Some((line, signature));
+        Ok(())
+    }
+
+    /// Bytes of the token of the length starting at the column of the line
+    fn span(&self, line: usize, column: usize, len: usize) -> std::ops::Range<usize> {
+        let start = match self.lines.get(line.wrapping_sub(1)) {
+            Some(line) => byte_offset(&line.text, column),
+            None => column.saturating_sub(1)
+        };
+        start..start + len
+    }
+
+    /// Counts command written checking the code against limits of options
+    fn check_limits(&mut self, destination: &T) -> Result<(), OperationError> {
+        self.instructions += 1;
+        let limits = &self.options.limits;
+        if let Some(limit) = limits.max_instructions.filter(|limit| self.instructions > *limit) {
+            return Err(OperationError::InstructionLimit(limit))
+        }
+        if let Some(limit) = limits.max_cells.filter(|limit| destination.cells() > *limit) {
+            return Err(OperationError::CellLimit(limit))
+        }
+        if let Some(limit) = limits.max_depth.filter(|limit| destination.depth() > *limit) {
+            return Err(OperationError::DepthLimit(limit))
+        }
+        Ok(())
+    }
+
+    /// Passes the command written to the visitor of options
+    fn visit(&self, mnemonic: &str, operands: &[&str], position: &DbgPos, destination: &mut T) -> Result<(), OperationError> {
+        let written = destination.take_written();
+        let command = CompiledCommand { mnemonic, operands, position, written: &written };
+        match self.options.visitor.as_ref().map(|visitor| visitor.visit(&command)) {
+            Some(Err(message)) => Err(OperationError::Rejected(message)),
+            _ => Ok(())
+        }
+    }
+
+    /// Checks the command is available in TVM version targeted by options
+    fn check_version(&self, name: &str) -> CompileResult {
+        match (self.options.target_version, required_version(name)) {
+            (Some(target), Some(required)) if required > target => Err(OperationError::VersionRequired(required)),
+            _ => Ok(())
+        }
+    }
+
+    /// New writer joining cells as the options say
+    fn writer(&self) -> T {
+        let mut writer = T::new();
+        writer.set_layout(self.options.layout);
+        writer
+    }
+
+    /// Compiles code with known commands including registered ones
+    pub fn compile(&mut self, source: &str) -> Result<T, CompileError> {
+        // handler takes the block of typed operands instead of compiling its text
+        if let Some(code) = self.compiled.pop() {
+            return Ok(code)
+        }
+        self.compile_into(source, self.writer())
+    }
+
+    /// Encodes command given by typed operands with its handler: blocks are given
+    /// as code compiled already, other operands in their source syntax
+    pub(crate) fn encode(
+        &mut self,
+        handler: CompileHandler<T>,
+        par: &Vec<&str>,
+        mut blocks: Vec<T>,
+        destination: &mut T,
+        pos: DbgPos,
+    ) -> CompileResult {
+        blocks.reverse();
+        self.compiled = blocks;
+        let result = handler(self, par, destination, pos);
+        self.compiled.clear();
+        result
+    }
+
+    /// Compiles code appending it to the code written already
+    pub(crate) fn compile_into(&mut self, source: &str, mut ret: T) -> Result<T, CompileError> {
+        let _span = enter_span!(DEBUG, "compile", line = self.line_no, column = self.char_no);
+        let span = (self.line_no, self.char_no, source.len());
+        self.set_codepage(self.options.codepage)?;
+        if let Some(fift) = self.fift.as_mut() {
+            fift.begin()
+        }
+        if let Some(tree) = self.tree.as_mut() {
+            tree.begin()
+        }
+        if let Some(stack) = self.stack.as_mut() {
+            stack.begin()
+        }
+        if let Some(reach) = self.reach.as_mut() {
+            reach.begin()
+        }
+        let result = self.compile_scoped(source, &mut ret);
+        if let Some(fift) = self.fift.as_mut() {
+            fift.end(span)
+        }
+        if let Some(tree) = self.tree.as_mut() {
+            tree.end(span)
+        }
+        if let Some(stack) = self.stack.as_mut() {
+            stack.end()
+        }
+        if let Some(reach) = self.reach.as_mut() {
+            reach.end()
+        }
+        result.map(|_| ret)
+    }
+
+    /// Compiles code seeing labels and macros of enclosing blocks
+    fn compile_scoped(&mut self, source: &str, destination: &mut T) -> Result<(), CompileError> {
+        let (text, own) = match self.scan(source) {
+            Ok(scanned) => scanned,
+            Err(e) => return self.report(e)
+        };
+        let signature = own.signature.clone();
+        let mut scope = self.scopes.last().cloned().unwrap_or_else(|| self.defined());
+        scope.extend(own);
+        self.scopes.push(scope.clone());
+        let before = self.stack.as_mut().zip(signature.as_ref()).and_then(|(stack, (_, signature))| stack.enter(signature));
+        let result = self.compile_source(&text, &scope, destination);
+        if let Some((stack, (line, signature))) = self.stack.as_mut().zip(signature.as_ref()) {
+            let mut pos = self.lines.get(line - 1).map(|line| line.pos.clone()).unwrap_or_default();
+            pos.column = 1;
+            stack.leave(signature, before, &pos);
+        }
+        self.scopes.pop();
+        result
+    }
+
+    /// Compiles sources of several files as one program, the code of the files follows in order.
+    /// Every file sees its own labels, macros and constants along with the ones declared
+    /// `.globl` by any file, so they are resolved after all the files are scanned.
+    /// Sources are given with the line of engine lines they start at.
+    /// Returns the code along with names of external labels left to resolve.
+    fn compile_units(&mut self, sources: &[(usize, &str)]) -> Result<(T, Vec<String>), CompileError> {
+        let _span = enter_span!(DEBUG, "compile_units", units = sources.len());
+        self.set_codepage(self.options.codepage)?;
+        let mut units = Vec::new();
+        let mut globals = Scope::default();
+        for (line, source) in sources {
+            self.set_pos(*line, 1);
+            let (text, own) = self.scan(source)?;
+            for (line, names) in &own.globals {
+                for name in names {
+                    own.export(name, &mut globals)
+                        .map_err(|error| self.syntax_error(*line, 1, format!("Global symbol {} {}", name, error)))?;
+                }
+            }
+            units.push((*line, text, own));
+        }
+        let mut externs = Vec::new();
+        for (_, _, own) in units.iter_mut() {
+            externs.append(&mut self.define_externs(own, &globals, true)?);
+        }
+        let mut ret = self.writer();
+        for (line, text, own) in units {
+            let mut scope = self.defined();
+            scope.extend(globals.clone());
+            scope.extend(own);
+            self.scopes.push(scope.clone());
+            self.set_pos(line, 1);
+            let result = self.compile_source(&text, &scope, &mut ret);
+            self.scopes.pop();
+            result?;
+        }
+        Ok((ret, externs))
+    }
+
+    /// Defines symbols declared `.extern` in the scope and not defined in the other one:
+    /// integers given by the resolver of options become constants, the rest become labels
+    /// of placeholder cells. Returns names of the latter ones, which the resolver must provide if required.
+    fn define_externs(&self, scope: &mut Scope, defined: &Scope, required: bool) -> Result<Vec<String>, CompileError> {
+        let mut externs = Vec::new();
+        for (line, names) in scope.externs.clone() {
+            for (i, name) in names.into_iter().enumerate() {
+                if defined.defines(&name) {
+                    continue
+                }
+                if scope.defines(&name) {
+                    return Err(self.syntax_error(line, 1, format!("External symbol {} is defined in the file", name)))
+                }
+                match self.options.resolver.as_ref().and_then(|resolver| resolver.resolve(&name)) {
+                    Some(Symbol::Integer(value)) => {
+                        scope.constants.insert(name, value.to_string());
+                        continue
+                    }
+                    None if required => return Err(self.syntax_error(line, 1, format!("External symbol {} is not defined", name))),
+                    _ => ()
+                }
+                let placeholder = extern_cell(&name).map_err(|e| self.syntax_error(line, 1, e))?;
+                let body = Rc::from(format!(".blob x{{{}}}\n", SliceData::from(placeholder).as_hex_string()));
+                scope.labels.insert(name.clone(), Label { line, column: i + 1, body });
+                externs.push(name);
+            }
+        }
+        Ok(externs)
+    }
+
+    /// Compiles source of the file along with the code of its labels declared `.globl`,
+    /// references to labels declared `.extern` are compiled into placeholder cells
+    /// to be replaced by `link` unless the resolver of options gives integers for them. Returns the code, the code of global labels and external names.
+    fn compile_object(&mut self, source: &str) -> Result<(T, Symbols<T>, Vec<String>), CompileError> {
+        let _span = enter_span!(DEBUG, "compile_object");
+        self.set_codepage(self.options.codepage)?;
+        let (text, mut scope) = self.scan(source)?;
+        let externs = self.define_externs(&mut scope, &Scope::default(), false)?;
+        for (name, value) in self.defined().constants {
+            scope.constants.entry(name).or_insert(value);
+        }
+        self.scopes.push(scope.clone());
+        let mut code = self.writer();
+        let result = self.compile_source(&text, &scope, &mut code)
+            .and_then(|_| self.compile_globals(&scope));
+        self.scopes.pop();
+        result.map(|symbols| (code, symbols, externs))
+    }
+
+    /// Compiles code following every label declared `.globl` in the scope
+    fn compile_globals(&mut self, scope: &Scope) -> Result<Symbols<T>, CompileError> {
+        let mut symbols = Vec::new();
+        for (line, names) in &scope.globals {
+            for name in names {
+                let label = scope.labels.get(name)
+                    .ok_or_else(|| self.syntax_error(*line, 1, format!("Global symbol {} is not a label", name)))?;
+                let mut code = self.writer();
+                self.set_pos(label.line, label.column);
+                self.expanding.push((label.line, label.column));
+                let result = self.compile_source(&label.body, scope, &mut code);
+                self.expanding.pop();
+                result?;
+                symbols.push((name.clone(), code));
+            }
+        }
+        Ok(symbols)
+    }
+
+    /// Compiles macro body with parameters substituted right into the destination
+    fn expand_macro(&mut self, definition: &Rc<Macro>, args: &Vec<String>, destination: &mut T) -> CompileResult {
+        args.assert_len(definition.params.len())?;
+        if self.macro_calls.iter().any(|call| Rc::ptr_eq(call, definition)) {
+            let error = self.syntax_error(definition.line, definition.column, format!("Recursive call of macro {}", definition.name));
+            return Err(OperationError::Nested(Box::new(error)))
+        }
+        self.set_pos(definition.line, definition.column);
+        let body = definition.expand(args);
+        self.macro_calls.push(definition.clone());
+        let result = self.compile_scoped(&body, destination);
+        self.macro_calls.pop();
+        result.map_err(|e| OperationError::Nested(Box::new(e)))
+    }
+
+    /// Compiles the body of `.rep` directive the number of times given
+    fn repeat(&mut self, repetition: &Repetition, par: &Vec<&str>, destination: &mut T) -> CompileResult {
+        par.assert_empty()?;
+        let count = self.scopes.last().and_then(|scope| scope.constant(&repetition.count)).unwrap_or(&repetition.count);
+        let count = count.parse::<usize>().map_err(|_| {
+            let error = self.syntax_error(repetition.body.line - 1, 1, format!("Bad .rep count {}", count));
+            OperationError::Nested(Box::new(error))
+        })?;
+        for i in 0..count {
+            let args = repetition.body.params.iter().map(|_| i.to_string()).collect();
+            self.expand_macro(&repetition.body, &args, destination)?;
+        }
+        Ok(())
+    }
+
+    fn compile_source<'a>(&mut self, source: &'a str, scope: &'a Scope, ret: &mut T) -> Result<(), CompileError> {
+        let mut par: Vec<Parameter> = Vec::new();
         let mut acc = (0, 0);
         let mut expect_comma = false;
         let mut comma_found = false;
@@ -245,6 +1410,15 @@ impl<T: Writer> Engine<T> {
         let mut was_newline = false; // was line break before token
         let mut in_block = 0;
         let mut in_comment = false;
+        // nesting level of /* ... */ comments and position of the outer one
+        let mut block_comment = 0;
+        let mut comment_start = (0, 0);
+        // second char of comment delimiter is to be skipped
+        let mut delimiter = false;
+        // closing char of character, string, Fift-style slice literal or operand like boc("path") being read,
+        // space for the literal lasting to the end of the token
+        let mut literal: Option<char> = None;
+        let mut escaped = false;
         let mut command_ctx = CommandContext::default();
         for ch in source.chars().chain(" ".chars()) {
             let mut newline_found = false;
@@ -259,15 +1433,57 @@ impl<T: Writer> Engine<T> {
             }
             let (s0, s1) = acc;
             let new_s1 = s1 + ch.len_utf8();
-            // Process internal block if any
+            // Process literal if any, it ends with the line at most
+            match literal {
+                Some(' ') if Engine::<T>::is_whitespace(ch) || (ch == ',') || (ch == ';') => literal = None,
+                Some(_) if ch == '\n' => literal = None,
+                Some(closing) => {
+                    if escaped {
+                        escaped = false
+                    } else if (ch == '\\') && (closing == '"') {
+                        escaped = true
+                    } else if ch == closing {
+                        literal = None
+                    }
+                    acc = (s0, new_s1);
+                    continue;
+                }
+                None => ()
+            }
+            escaped = false;
+            // Process block comment if any, it is kept in the text of internal block
+            if delimiter || (block_comment > 0) {
+                if delimiter {
+                    delimiter = false
+                } else if source[s1..].starts_with("*/") {
+                    block_comment -= 1;
+                    delimiter = true
+                } else if source[s1..].starts_with("/*") {
+                    block_comment += 1;
+                    delimiter = true
+                } else if (ch == '\n') && (in_block == 0) {
+                    was_newline = true
+                }
+                acc = if in_block > 0 { (s0, new_s1) } else { (new_s1, new_s1) };
+                continue;
+            }
+            // Process internal block if any, braces of its literals and comments are not counted
             if in_block > 0 {
-                if ch == '{' {
+                if source[s1..].starts_with("/*") {
+                    block_comment = 1;
+                    comment_start = (y, x);
+                    delimiter = true
+                } else if ch == '{' {
                     in_block += 1
                 } else if ch == '}' {
                     in_block -= 1
+                } else if (ch == '\'') || (ch == '"') {
+                    literal = Some(ch)
                 }
                 if in_block == 0 {
-                    par.push((y, x, &source[s0..s1], comma_found));
+                    par.push((y, x, Cow::Borrowed(&source[s0..s1]), comma_found, Some((command_ctx.line_no_par, command_ctx.char_no_par))));
+                    // parameters following the block are separated by comma
+                    expect_comma = true;
                     acc = (new_s1, new_s1)
                 } else {
                     acc = (s0, new_s1)
@@ -297,14 +1513,27 @@ impl<T: Writer> Engine<T> {
                 acc = (new_s1, new_s1);
                 in_comment = true;
                 continue;
+            } else if source[s1..].starts_with("/*") {
+                // block comment separates tokens like whitespace
+                block_comment = 1;
+                comment_start = (y, x);
+                delimiter = true;
+                acc = (new_s1, new_s1);
+                if s0 == s1 {
+                    continue;
+                }
             } else if ch == ',' {
                 if !expect_comma {
-                    if let Some(line) = self.lines.get(y - 1) {
+                    let error = if let Some(line) = self.lines.get(y - 1) {
                         let pos = &line.pos;
-                        return Err(CompileError::syntax(pos.line_code, x, ",").with_filename(pos.filename.clone()))
+                        CompileError::syntax(pos.line_code, x, ",").with_filename(pos.filename.clone())
                     } else {
-                        return Err(CompileError::syntax(y, x, ","))
-                    }
+                        CompileError::syntax(y, x, ",")
+                    };
+                    let error = error.with_span(self.span(y, x, 1));
+                    self.report(error)?;
+                    acc = (new_s1, new_s1);
+                    continue;
                 }
                 acc = (new_s1, new_s1);
                 expect_comma = false;
@@ -312,14 +1541,26 @@ impl<T: Writer> Engine<T> {
                 if s0 == s1 {
                     continue;
                 }
+            } else if ch == '{' && (matches!(&source[s0..s1], "x" | "X" | "b" | "B") || source[s0..s1].ends_with('$')) {
+                // x{...} or b{...} slice literal, or ${NAME} placeholder
+                literal = Some('}');
+                acc = (s0, new_s1);
+                continue;
+            } else if ch == '(' && matches!(source[s0..s1].to_ascii_lowercase().as_str(), "boc" | "library") {
+                // boc("path") or library(hash) operand
+                literal = Some(')');
+                acc = (s0, new_s1);
+                continue;
             } else if ch == '{' {
                 if expect_comma || !command_ctx.has_command() || !par.is_empty() {
-                    if let Some(line) = self.lines.get(y - 1) {
+                    let error = if let Some(line) = self.lines.get(y - 1) {
                         let pos = &line.pos;
-                        return Err(CompileError::syntax(pos.line_code, x, ch).with_filename(pos.filename.clone()))
+                        CompileError::syntax(pos.line_code, x, ch).with_filename(pos.filename.clone())
                     } else {
-                        return Err(CompileError::syntax(y, x, ch))
-                    }
+                        CompileError::syntax(y, x, ch)
+                    };
+                    self.report(error.with_span(self.span(y, x, 1)))?;
+                    command_ctx = CommandContext::new(Cow::Borrowed(""), x, y, Some(Rule::Skip));
                 }
                 acc = (new_s1, new_s1);
                 in_block = 1;
@@ -327,13 +1568,44 @@ impl<T: Writer> Engine<T> {
                 command_ctx.char_no_par = self.char_no;
                 continue;
             } else if ch == '}' {
-                if let Some(line) = self.lines.get(y - 1) {
+                let error = if let Some(line) = self.lines.get(y - 1) {
                     let pos = &line.pos;
-                    return Err(CompileError::syntax(pos.line_code, x, ch).with_filename(pos.filename.clone()))
+                    CompileError::syntax(pos.line_code, x, ch).with_filename(pos.filename.clone())
                 } else {
-                    return Err(CompileError::syntax(y, x, ch))
+                    CompileError::syntax(y, x, ch)
+                };
+                self.report(error.with_span(self.span(y, x, 1)))?;
+                acc = (new_s1, new_s1);
+                continue;
+            } else if ch == ':' && (&source[s0..s1] == "base64") {
+                // base64:... string literal
+                literal = Some(' ');
+                acc = (s0, new_s1);
+                continue;
+            } else if ch == ':' {
+                if s0 == s1 {
+                    self.report(self.syntax_error(y, x, ch))?;
+                    acc = (new_s1, new_s1);
+                    continue;
+                }
+                // label definition completes the pending command
+                if let Err(e) = command_ctx.compile(ret, &mut par, self) {
+                    self.report(e)?;
+                    par.clear();
+                }
+                if let Some(reach) = self.reach.as_mut() {
+                    reach.label()
+                }
+                command_ctx = CommandContext::default();
+                acc = (new_s1, new_s1);
+                expect_comma = false;
+                comma_found = false;
+                was_comma = false;
+                continue;
+            } else if is_token_char(ch) || (ch == '\'') || (ch == '"') || (ch == '$' && source[new_s1..].starts_with('{')) {
+                if (ch == '\'') || (ch == '"') {
+                    literal = Some(ch)
                 }
-            } else if ch.is_ascii_alphanumeric() || (ch == '-') || (ch == '_') || (ch == '.') {
                 acc = (s0, new_s1);
                 if s0 == s1 { //start of new token
                     was_comma = comma_found;
@@ -342,34 +1614,94 @@ impl<T: Writer> Engine<T> {
                 }
                 continue;
             } else { // TODO: (message for the owner: please write descriptive explanation)
-                if let Some(line) = self.lines.get(y - 1) {
+                let error = if let Some(line) = self.lines.get(y - 1) {
                     let pos = &line.pos;
-                    return Err(CompileError::syntax(pos.line_code, x, "Bad char").with_filename(pos.filename.clone()))
+                    CompileError::syntax(pos.line_code, x, "Bad char").with_filename(pos.filename.clone())
                 } else {
-                    return Err(CompileError::syntax(y, x, "Bad char"))
+                    CompileError::syntax(y, x, "Bad char")
+                };
+                self.report(error.with_span(self.span(y, x, ch.len_utf8())))?;
+                // when recovering the char separates tokens
+                acc = (new_s1, new_s1);
+                if s0 == s1 {
+                    continue;
                 }
             }
             // Token extracted
-            let token = source[s0..s1].to_ascii_uppercase();
-            log::trace!(target: "tvm", "--> {}\n", token);
+            // commands are looked up in upper case, the token is copied only if it has lower case letters
+            let token = match &source[s0..s1] {
+                text if text.bytes().any(|b| b.is_ascii_lowercase()) => Cow::Owned(text.to_ascii_uppercase()),
+                text => Cow::Borrowed(text)
+            };
+            event!(trace, target: "tvm", "--> {}", token);
             x -= token.chars().count();
-            match self.COMPILE_ROOT.get(&token[..]) {
+            // `.cell { ... }` operand is the block describing the cell taken by the command
+            if (token == ".CELL") && command_ctx.has_command() && par.is_empty() && !was_newline {
+                continue
+            }
+            let command = if self.options.case_sensitive && !token.starts_with('.') && token != source[s0..s1] {
+                None
+            } else {
+                self.COMPILE_ROOT.get(&token[..])
+            };
+            let rule = match command {
+                Some(&handler) => Some(Rule::Handler(handler)),
+                None if token == ".REP" => scope.repetitions.get(&y).cloned().map(Rule::Repeat),
+                None => scope.macros.get(token.as_ref()).cloned().map(Rule::Macro)
+            };
+            match rule {
                 None => {
                     if command_ctx.has_command() {
-                        par.push((y, x, &source[s0..s1], was_comma));
+                        if let Some(name) = source[s0..s1].strip_prefix('@') {
+                            // reference to label is a block with the code following it
+                            let label = match scope.labels.get(name) {
+                                Some(label) => label,
+                                None => {
+                                    self.report(self.syntax_error(y, x, format!("Unknown label {}", name)))?;
+                                    continue
+                                }
+                            };
+                            if self.expanding.contains(&(label.line, label.column)) {
+                                self.report(self.syntax_error(y, x, format!("Recursive reference to label {}", name)))?;
+                                continue
+                            }
+                            command_ctx.labels.push((label.line, label.column));
+                            command_ctx.line_no_par = label.line;
+                            command_ctx.char_no_par = label.column;
+                            par.push((y, x, Cow::Borrowed(&label.body), was_comma, Some((label.line, label.column))));
+                            was_comma = false;
+                            continue
+                        }
+                        // named constant is replaced with its value, placeholders with values of defines
+                        let value = match scope.constant(&source[s0..s1]) {
+                            Some(value) => Cow::Borrowed(value),
+                            None => match self.substitute(&source[s0..s1], y, x) {
+                                Ok(value) => value,
+                                Err(e) => {
+                                    self.report(e)?;
+                                    Cow::Borrowed(&source[s0..s1])
+                                }
+                            }
+                        };
+                        par.push((y, x, value, was_comma, None));
                         was_comma = false;
                         continue
                     } else {
-                        if let Some(line) = self.lines.get(y - 1) {
+                        // mnemonic is reported as written when its case matters
+                        let token = if self.options.case_sensitive { Cow::Borrowed(&source[s0..s1]) } else { token };
+                        let error = if let Some(line) = self.lines.get(y - 1) {
                             let pos = &line.pos;
-                            return Err(CompileError::unknown(pos.line_code, x, &token).with_filename(pos.filename.clone()))
+                            CompileError::unknown(pos.line_code, x, &token).with_filename(pos.filename.clone())
                         } else {
-                            return Err(CompileError::unknown(y, x, &token))
-                        }
+                            CompileError::unknown(y, x, &token)
+                        };
+                        let error = error.with_suggestions(self.suggestions(&token)).with_span(self.span(y, x, token.len()));
+                        self.report(error)?;
+                        command_ctx = CommandContext::new(token, x, y, Some(Rule::Skip));
                     }
                 }
-                Some(&new_rule) => {
-                    match command_ctx.compile(&mut ret, &mut par, self) {
+                Some(new_rule) => {
+                    match command_ctx.compile(ret, &mut par, self) {
                         Ok(_) => {
                             command_ctx = CommandContext::new(token, x, y, Some(new_rule));
                             expect_comma = false;
@@ -378,45 +1710,422 @@ impl<T: Writer> Engine<T> {
                         }
                         Err(e @ CompileError::Operation(_, _, OperationError::MissingRequiredParameters)) => {
                             if was_newline { // it seems realy new command - rturn correct missing params error
-                                return Err(e)
+                                self.report(e)?;
+                                par.clear();
+                                command_ctx = CommandContext::new(token, x, y, Some(new_rule));
+                                expect_comma = false;
+                                was_comma = false;
+                                was_newline = newline_found;
                             } else {
-                                par.push((y, x, &source[s0..s1], was_comma));
+                                par.push((y, x, Cow::Borrowed(&source[s0..s1]), was_comma, None));
                                 was_comma = false;
                             }
                         }
-                        Err(e) => return Err(e)
+                        Err(e) => {
+                            self.report(e)?;
+                            par.clear();
+                            command_ctx = CommandContext::new(token, x, y, Some(new_rule));
+                            expect_comma = false;
+                            was_comma = false;
+                            was_newline = newline_found;
+                        }
                     }
                 }
             }
         }
+        if block_comment > 0 {
+            let (line, column) = comment_start;
+            self.report(self.syntax_error(line, column, "Missing end of block comment").with_span(self.span(line, column, 2)))?;
+        }
         // Compile last pending command if any
-        command_ctx.compile(&mut ret, &mut par, self)?;
-        Ok(ret)
+        if let Err(e) = command_ctx.compile(ret, &mut par, self) {
+            self.report(e)?;
+        }
+        Ok(())
     }
 
 }
 
+/// Completes the code, failure of the writer is reported as compilation error
+pub(crate) fn finalize_code<T: Writer>(code: T) -> Result<(BuilderData, DbgNode), CompileError> {
+    let _span = enter_span!(DEBUG, "finalize", cells = code.cells());
+    code.finalize().map_err(|e| {
+        event!(debug, error = %e, "finalization failed");
+        CompileError::operation(0, 0, "", e)
+    })
+}
+
+/// Completes the code of the program along with the metadata cell of options, see `CompileOptions::metadata`
+fn finalize_root<T: Writer>(code: T, options: &CompileOptions) -> Result<(BuilderData, DbgNode), CompileError> {
+    finalize_root_with(code, options, |_| ())
+}
+
+/// Completes the code of the program as `finalize_root` does, passing it to `complete` before finalization
+fn finalize_root_with<T: Writer>(mut code: T, options: &CompileOptions, complete: impl FnOnce(&T)) -> Result<(BuilderData, DbgNode), CompileError> {
+    if options.metadata.is_some() {
+        end_code(&mut code)?;
+    }
+    complete(&code);
+    let (mut builder, dbg) = finalize_code(code)?;
+    attach_metadata(&mut builder, options)?;
+    Ok((builder, dbg))
+}
+
+/// Ends the code with explicit RET, otherwise the root cell would jump to the metadata as its last reference
+fn end_code<T: Writer>(code: &mut T) -> Result<(), CompileError> {
+    code.write_command(&[0xDB, 0x30], DbgNode::new()).map(|_| ()).map_err(|e| CompileError::operation(0, 0, "RET", e))
+}
+
+fn attach_metadata(builder: &mut BuilderData, options: &CompileOptions) -> Result<(), CompileError> {
+    match &options.metadata {
+        Some(metadata) => builder.checked_append_reference(metadata.clone()).map(|_| ())
+            .map_err(|_| CompileError::syntax(0, 0, "No free reference in the root cell of the code for metadata")),
+        None => Ok(())
+    }
+}
+
 pub fn compile_code(code: &str) -> Result<SliceData, CompileError> {
     compile_code_to_cell(code).map(|code| code.into())
 }
 
+/// Compiles code with the options, see `CompileOptions`
+pub fn compile_code_with_options(code: &str, options: &CompileOptions) -> Result<SliceData, CompileError> {
+    compile_code_to_cell_with_options(code, options).map(|code| code.into())
+}
+
 pub fn compile_code_to_cell(code: &str) -> Result<Cell, CompileError> {
-    log::trace!(target: "tvm", "begin compile\n");
-    Engine::<CodePage0>::new(vec![]).compile(code).map(|code| code.finalize().0.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell")))?
+    compile_code_to_cell_with_options(code, &CompileOptions::default())
+}
+
+pub fn compile_code_to_cell_with_options(code: &str, options: &CompileOptions) -> Result<Cell, CompileError> {
+    // denied warnings are reported at positions of lines
+    if source_has_includes(code) || !options.deny.is_empty() {
+        return compile_code_debuggable_with_options(code_to_lines(code, ""), options).map(|(code, _)| code.cell().clone())
+    }
+    // debug info is not returned so it is not collected
+    let options = CompileOptions { skip_debug_info: true, ..options.clone() };
+    let (builder, _, _) = compile_with_writer(vec![], code, &options, false)?;
+    builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))
+}
+
+/// Compiles code reporting its size, see `CodeStats`
+pub fn compile_code_stats(code: &str) -> Result<(Cell, CodeStats), CompileError> {
+    let cell = compile_code_to_cell(code)?;
+    let stats = CodeStats::from_cell(&cell);
+    Ok((cell, stats))
+}
+
+/// Representation hash of the root cell of compiled code
+pub fn compile_code_hash(code: &str) -> Result<UInt256, CompileError> {
+    compile_code_to_cell(code).map(|cell| cell.repr_hash())
+}
+
+/// Compiles code into serialized bag of cells
+pub fn compile_code_to_boc(code: &str) -> Result<Vec<u8>, CompileError> {
+    let cell = compile_code_to_cell(code)?;
+    ton_types::serialize_toc(&cell).map_err(|_| CompileError::unknown(0, 0, "failure while serialize cell"))
+}
+
+/// Compiles code into serialized bag of cells encoded in base64
+pub fn compile_code_to_boc_base64(code: &str) -> Result<String, CompileError> {
+    compile_code_to_boc(code).map(base64::encode)
 }
 
 pub fn compile_code_to_builder(code: &str) -> Result<BuilderData, CompileError> {
-    log::trace!(target: "tvm", "begin compile\n");
-    Engine::<CodePage0>::new(vec![]).compile(code).map(|code| code.finalize().0)
+    Engine::<CodePage0>::new(vec![]).compile(code).and_then(finalize_code).map(|(code, _)| code)
+}
+
+/// Translates code into equivalent Fift assembler source: one mnemonic per line
+/// with operands in postfix order and blocks as `<{ }>`
+pub fn compile_code_to_fift(code: &str) -> Result<String, CompileError> {
+    let mut engine = Engine::<CodePage0>::new(vec![]);
+    engine.fift = Some(FiftSource::default());
+    let span = (engine.line_no, engine.char_no, code.len());
+    engine.compile(code)?;
+    Ok(engine.fift.as_mut().and_then(|fift| fift.take(span)).unwrap_or_default())
+}
+
+/// Parses code into instructions with nested blocks; the code is checked
+/// to compile, operands are typed by their syntax
+pub fn parse_code(code: &str) -> Result<Vec<Instruction>, CompileError> {
+    let mut engine = Engine::<CodePage0>::new(vec![]);
+    engine.tree = Some(InstructionTree::default());
+    let span = (engine.line_no, engine.char_no, code.len());
+    engine.compile(code)?;
+    Ok(engine.tree.as_mut().and_then(|tree| tree.take(span)).unwrap_or_default())
+}
+
+/// Checks stack depth through straight-line code of the blocks starting with
+/// `depth` values on the stack, returns commands taking more values than there are for sure
+pub fn check_stack(code: Lines, depth: usize, options: &CompileOptions) -> Result<Vec<StackWarning>, CompileError> {
+    let code = if has_includes(&code) {
+        expand_includes(code, &options.include_dirs)?
+    } else {
+        code
+    };
+    let source = lines_to_string(&code);
+    let mut engine = Engine::<CodePage0>::with_options(code, options);
+    engine.stack = Some(StackCheck::new(depth));
+    engine.compile(source.as_str())?;
+    Ok(engine.stack.as_mut().map(|stack| stack.take()).unwrap_or_default())
+}
+
+/// Finds commands following unconditional RET, JMPX or THROW in blocks, up to the next label
+/// or the end of the block. They are dropped from the code if options eliminate dead code.
+pub fn find_dead_code(code: Lines, options: &CompileOptions) -> Result<Vec<DeadCode>, CompileError> {
+    let code = if has_includes(&code) {
+        expand_includes(code, &options.include_dirs)?
+    } else {
+        code
+    };
+    let source = lines_to_string(&code);
+    let mut engine = Engine::<CodePage0>::with_options(code, options);
+    engine.reach = Some(Reachability::new(options.eliminate_dead_code));
+    engine.compile(source.as_str())?;
+    Ok(engine.reach.as_mut().map(|reach| reach.take()).unwrap_or_default())
+}
+
+/// Compiles code skipping erroneous commands to report all errors found
+pub fn compile_code_recovering(code: Lines, options: &CompileOptions) -> Result<(SliceData, DbgInfo), Vec<CompileError>> {
+    let code = if has_includes(&code) {
+        expand_includes(code, &options.include_dirs).map_err(|e| vec![e])?
+    } else {
+        code
+    };
+    let source = lines_to_string(&code);
+    fn compile<T: Writer>(code: Lines, source: &str, options: &CompileOptions) -> Result<(BuilderData, DbgNode), Vec<CompileError>> {
+        // errors are collected by the engine compiling the code finally
+        let mut engine = Engine::<T>::sharing_blocks(code, options, |engine| {
+            engine.errors = Some(Vec::new());
+            let _ = engine.compile(source);
+            Ok(())
+        }).map_err(|e| vec![e])?;
+        engine.errors = Some(Vec::new());
+        let result = engine.compile(source);
+        let mut errors = engine.errors.take().unwrap_or_default();
+        if let Err(e) = engine.take_diagnostics() {
+            errors.push(e)
+        }
+        match result {
+            Ok(code) if errors.is_empty() => finalize_root(code, options).map_err(|e| vec![e]),
+            Ok(_) => Err(errors),
+            Err(e) => {
+                errors.push(e);
+                Err(errors)
+            }
+        }
+    }
+    let (builder, dbg) = with_writer!(options, |T| compile::<T>(code, &source, options))?;
+    let cell = builder.into_cell().map_err(|_| vec![CompileError::unknown(0, 0, "failure while convert BuilderData to cell")])?;
+    let dbg_info = debug_info(&cell, &dbg, options).map_err(|e| vec![e])?;
+    Ok((cell.into(), dbg_info))
 }
 
 pub fn compile_code_debuggable(code: Lines) -> Result<(SliceData, DbgInfo), CompileError> {
-    log::trace!(target: "tvm", "begin compile\n");
+    compile_code_debuggable_with_options(code, &CompileOptions::default())
+}
+
+/// Compiles code of the file, its name is set in debug info and error positions
+pub fn compile_code_debuggable_str(code: &str, filename: &str) -> Result<(SliceData, DbgInfo), CompileError> {
+    compile_code_debuggable(code_to_lines(code, filename))
+}
+
+/// Compiles code read line by line, such as a large generated file. The code is kept in
+/// a single buffer, since labels may be referenced before they are defined: lines keep
+/// only their positions unless their text is needed to locate tokens of non-ASCII text.
+/// Code with include directives is split into lines to expand them as `compile_code_debuggable` does.
+pub fn compile_reader(mut reader: impl BufRead, filename: &str) -> Result<(SliceData, DbgInfo), CompileError> {
+    let filename: Arc<str> = Arc::from(filename);
+    let mut source = String::new();
+    let mut lines = Lines::new();
+    let mut includes = false;
+    loop {
+        let start = source.len();
+        let read = reader.read_line(&mut source).map_err(|e| {
+            CompileError::syntax(lines.len() + 1, 1, format!("Cannot read: {}", e)).with_filename(filename.clone())
+        })?;
+        if read == 0 {
+            break
+        }
+        let text = &source[start..];
+        includes |= source_has_includes(text);
+        // columns of ASCII text are its byte offsets
+        let text = if text.is_ascii() { "" } else { text };
+        lines.push(Line::new_shared(text, &filename, lines.len() + 1));
+    }
+    if includes {
+        let lines = code_to_lines(&source, &filename);
+        drop(source);
+        return compile_code_debuggable(lines)
+    }
+    let options = CompileOptions::default();
+    let (builder, dbg, _) = compile_with_writer(lines, &source, &options, false)?;
+    let cell = builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))?;
+    let dbg_info = debug_info(&cell, &dbg, &options)?;
+    Ok((cell.into(), dbg_info))
+}
+
+pub fn compile_code_debuggable_with_options(code: Lines, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    let code = if has_includes(&code) {
+        expand_includes(code, &options.include_dirs)?
+    } else {
+        code
+    };
     let source = lines_to_string(&code);
-    let (builder, dbg) = Engine::<CodePage0>::new(code).compile(source.as_str()).map(|code| code.finalize())?;
+    let (builder, dbg, _) = compile_with_writer(code, &source, options, false)?;
     let cell = builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))?;
-    let dbg_info = DbgInfo::from(&cell, &dbg);
+    let dbg_info = debug_info(&cell, &dbg, options)?;
     Ok((cell.into(), dbg_info))
 }
 
+/// Compiles code along with warnings of analyses run while compiling, such as unreachable code,
+/// which is also removed if options eliminate dead code
+pub fn compile_code_with_diagnostics(code: Lines, options: &CompileOptions) -> Result<(SliceData, DbgInfo, Vec<Diagnostic>), CompileError> {
+    let code = if has_includes(&code) {
+        expand_includes(code, &options.include_dirs)?
+    } else {
+        code
+    };
+    let source = lines_to_string(&code);
+    let (builder, dbg, diagnostics) = compile_with_writer(code, &source, options, true)?;
+    let cell = builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))?;
+    let dbg_info = debug_info(&cell, &dbg, options)?;
+    Ok((cell.into(), dbg_info, diagnostics))
+}
+
+/// Compiles code with the writer selected by options, running analyses if diagnosing
+fn compile_with_writer(code: Lines, source: &str, options: &CompileOptions, diagnose: bool) -> Result<(BuilderData, DbgNode, Vec<Diagnostic>), CompileError> {
+    fn compile<T: Writer>(code: Lines, source: &str, options: &CompileOptions, diagnose: bool) -> Result<(BuilderData, DbgNode, Vec<Diagnostic>), CompileError> {
+        let mut engine = Engine::<T>::sharing_blocks(code, options, |engine| engine.compile(source).map(|_| ()))?;
+        if diagnose {
+            engine.reach = Some(Reachability::new(options.eliminate_dead_code));
+        }
+        let (builder, dbg) = engine.compile(source).and_then(|code| finalize_root(code, options))?;
+        Ok((builder, dbg, engine.take_diagnostics()?))
+    }
+    with_writer!(options, |T| compile::<T>(code, source, options, diagnose))
+}
+
+fn debug_info(cell: &Cell, dbg: &DbgNode, options: &CompileOptions) -> Result<DbgInfo, CompileError> {
+    if options.skip_debug_info {
+        return Ok(DbgInfo::new())
+    }
+    DbgInfo::from(cell, dbg).map_err(|_| CompileError::unknown(0, 0, "failure while collect debug info"))
+}
+
+/// Compiles several files as one program, see `compile_code_debuggable_with_options`.
+/// Labels, macros and constants of a file are visible to other files if it declares them
+/// with `.globl name, ...` directive, the code of the files follows in the order given.
+/// Symbols declared with `.extern name, ...` and defined by no file are given by the resolver of options.
+pub fn compile_program(files: Vec<Lines>, options: &CompileOptions) -> Result<(SliceData, DbgInfo), CompileError> {
+    compile_files(files, options, false).map(|(code, dbg, _)| (code, dbg))
+}
+
+/// Compiles several files as one program along with warnings of analyses,
+/// see `compile_program` and `compile_code_with_diagnostics`
+pub fn compile_program_with_diagnostics(files: Vec<Lines>, options: &CompileOptions) -> Result<(SliceData, DbgInfo, Vec<Diagnostic>), CompileError> {
+    compile_files(files, options, true)
+}
+
+fn compile_files(files: Vec<Lines>, options: &CompileOptions, diagnose: bool) -> Result<(SliceData, DbgInfo, Vec<Diagnostic>), CompileError> {
+    let mut code = Lines::new();
+    let mut starts = Vec::new();
+    for file in files {
+        let mut file = if has_includes(&file) {
+            expand_includes(file, &options.include_dirs)?
+        } else {
+            file
+        };
+        // tokens of the last line must not stick to the next file
+        if let Some(last) = file.last_mut() {
+            if !last.text.ends_with('\n') {
+                last.text.push('\n')
+            }
+        }
+        starts.push((code.len() + 1, file.iter().map(|line| line.text.len()).sum::<usize>()));
+        code.extend(file);
+    }
+    let source = lines_to_string(&code);
+    let mut offset = 0;
+    let sources = starts.into_iter().map(|(line, len)| {
+        offset += len;
+        (line, &source[offset - len..offset])
+    }).collect::<Vec<_>>();
+    type Compiled = ((BuilderData, DbgNode), Vec<String>, Vec<Diagnostic>);
+    fn compile<T: Writer>(code: Lines, sources: &[(usize, &str)], options: &CompileOptions, diagnose: bool) -> Result<Compiled, CompileError> {
+        let mut engine = Engine::<T>::sharing_blocks(code, options, |engine| engine.compile_units(sources).map(|_| ()))?;
+        if diagnose {
+            engine.reach = Some(Reachability::new(options.eliminate_dead_code));
+        }
+        let (code, externs) = engine.compile_units(sources)?;
+        Ok((finalize_root(code, options)?, externs, engine.take_diagnostics()?))
+    }
+    let ((builder, dbg), externs, diagnostics) = with_writer!(options, |T| compile::<T>(code, &sources, options, diagnose))?;
+    let cell = builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))?;
+    let dbg_info = debug_info(&cell, &dbg, options)?;
+    if externs.is_empty() {
+        return Ok((cell.into(), dbg_info, diagnostics))
+    }
+    // external labels are resolved by the resolver of options
+    let mut linker = Linker::new(options.resolver.clone());
+    linker.add_externs(&externs)?;
+    linker.add_debug_info(&dbg_info);
+    linker.link(&cell).map(|(cell, dbg_info)| (cell.into(), dbg_info, diagnostics))
+}
+
+/// Compiles code measuring time of its phases, see `CompileProfile`. Time is taken
+/// by `std::time::Instant`, which is not available in WebAssembly of browsers.
+#[cfg(feature = "profile")]
+pub fn compile_code_profiled(code: Lines, options: &CompileOptions) -> Result<(SliceData, DbgInfo, CompileProfile), CompileError> {
+    use std::time::Instant;
+    fn compile<T: Writer>(code: Lines, source: &str, options: &CompileOptions) -> Result<(BuilderData, DbgNode, CompileProfile), CompileError> {
+        let mut engine = Engine::<T>::with_options(code, options);
+        engine.profile = Some(Profiler::default());
+        let code = engine.compile(source)?;
+        engine.take_diagnostics()?;
+        let mut profile = engine.profile.as_mut().map(Profiler::take).unwrap_or_default();
+        let started = Instant::now();
+        let (builder, dbg) = finalize_root(code, options)?;
+        profile.finalize = started.elapsed();
+        Ok((builder, dbg, profile))
+    }
+    let started = Instant::now();
+    let code = if has_includes(&code) {
+        expand_includes(code, &options.include_dirs)?
+    } else {
+        code
+    };
+    let includes = started.elapsed();
+    let source = lines_to_string(&code);
+    let (builder, dbg, mut profile) = with_writer!(options, |T| compile::<T>(code, &source, options))?;
+    let cell = builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))?;
+    let dbg_info = debug_info(&cell, &dbg, options)?;
+    profile.includes = includes;
+    profile.total = started.elapsed();
+    Ok((cell.into(), dbg_info, profile))
+}
+
+/// Compiles code along with the listing of commands written, see `Listing`
+pub fn compile_code_listing(code: Lines, options: &CompileOptions) -> Result<(SliceData, DbgInfo, String), CompileError> {
+    let code = if has_includes(&code) {
+        expand_includes(code, &options.include_dirs)?
+    } else {
+        code
+    };
+    let source = lines_to_string(&code);
+    fn compile<T: IntoListing>(code: Lines, source: &str, options: &CompileOptions) -> Result<(BuilderData, DbgNode, String), CompileError> {
+        let mut engine = Engine::<T>::sharing_blocks(code, options, |engine| engine.compile(source).map(|_| ()))?;
+        let code = engine.compile(source)
+            .and_then(|code| code.into_listing().map_err(|e| CompileError::operation(0, 0, "", e)))?;
+        engine.take_diagnostics()?;
+        let mut listing = String::new();
+        let (builder, dbg) = finalize_root_with(code, options, |code| listing = code.listing())?;
+        Ok((builder, dbg, listing))
+    }
+    let (builder, dbg, listing) = with_writer!(options, Listing, Listing<NullDebug>, |T| compile::<T>(code, &source, options))?;
+    let cell = builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))?;
+    let dbg_info = debug_info(&cell, &dbg, options)?;
+    Ok((cell.into(), dbg_info, listing))
+}
+
 
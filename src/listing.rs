@@ -0,0 +1,171 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::BTreeMap;
+use ton_types::{BuilderData, Cell, SliceData};
+
+use crate::{OperationError, instruction_gas};
+use crate::debug::{DbgNode, DbgPos};
+use crate::peephole::Peephole;
+use crate::writer::{CodePage0, Layout, Writer, Written};
+
+/// Writer producing along with the code a listing of written commands: index of the cell
+/// in the chain, bit offset, encoding in hex and source position. Commands of nested
+/// continuations are split by their debug positions, ones kept in references are listed
+/// indented after the command. Cell indexes are the ones before finalization.
+/// The inner writer has to report where commands are written, so Peephole goes outside.
+pub struct Listing<W: Writer = CodePage0> {
+    inner: W,
+    lines: Vec<Entry>,
+}
+
+struct Entry {
+    code: String,
+    gas: u64,
+    pos: String,
+}
+
+pub(crate) fn format_pos(pos: Option<&DbgPos>) -> String {
+    match pos {
+        Some(pos) if pos.line != 0 => {
+            let filename = if pos.filename.is_empty() { "<none>" } else { &*pos.filename };
+            match pos.column {
+                0 => format!("{}:{}", filename, pos.line),
+                column => format!("{}:{}:{}", filename, pos.line, column)
+            }
+        }
+        _ => String::new()
+    }
+}
+
+impl<W: Writer> Listing<W> {
+    /// Lists bits of the command split at offsets having source positions
+    fn list_bits(&mut self, cell: &str, base: usize, mut data: SliceData, offsets: &BTreeMap<usize, DbgPos>, depth: usize) {
+        let total = data.remaining_bits();
+        let mut starts = offsets.keys().copied().filter(|offset| *offset < total).collect::<Vec<_>>();
+        if starts.first() != Some(&0) {
+            starts.insert(0, 0);
+        }
+        for (i, start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(total);
+            let (hex, gas) = data.get_next_slice(end - start)
+                .map(|piece| (piece.to_hex_string(), instruction_gas(&piece)))
+                .unwrap_or_default();
+            self.lines.push(Entry {
+                code: format!("{:<8}{:>5}  {:<32}", cell, base + start, "  ".repeat(depth) + &hex),
+                gas,
+                pos: format_pos(offsets.get(start)),
+            });
+        }
+    }
+
+    fn list_cell(&mut self, label: String, cell: &Cell, dbg: &DbgNode, depth: usize) {
+        self.list_bits(&label, 0, SliceData::from(cell.clone()), &dbg.offsets, depth);
+        for i in 0..cell.references_count() {
+            if let Ok(child) = cell.reference(i) {
+                let child_dbg = dbg.children.get(i).cloned().unwrap_or_default();
+                self.list_cell(format!("{}.{}", label, i), &child, &child_dbg, depth + 1);
+            }
+        }
+    }
+
+    pub fn listing(&self) -> String {
+        self.lines.iter().fold(String::new(), |result, line| {
+            result + format!("{} {}", line.code, line.pos).trim_end() + "\n"
+        })
+    }
+
+    /// Listing with static gas estimate of every command, see `instruction_gas`
+    pub fn listing_with_gas(&self) -> String {
+        self.lines.iter().fold(String::new(), |result, line| {
+            result + format!("{} {:>5}  {}", line.code, line.gas, line.pos).trim_end() + "\n"
+        })
+    }
+
+    /// Completes the code returning the listing along with it
+    pub fn finalize_with_listing(self) -> Result<(BuilderData, DbgNode, String), OperationError> {
+        let listing = self.listing();
+        let (code, dbg) = self.inner.finalize()?;
+        Ok((code, dbg, listing))
+    }
+}
+
+/// Writer holding a listing inside, possibly behind Peephole
+pub(crate) trait IntoListing: Writer {
+    type Inner: Writer;
+    fn into_listing(self) -> Result<Listing<Self::Inner>, OperationError>;
+}
+
+impl<W: Writer> IntoListing for Listing<W> {
+    type Inner = W;
+    fn into_listing(self) -> Result<Listing<W>, OperationError> {
+        Ok(self)
+    }
+}
+
+impl<W: Writer> IntoListing for Peephole<Listing<W>> {
+    type Inner = W;
+    fn into_listing(self) -> Result<Listing<W>, OperationError> {
+        self.into_inner()
+    }
+}
+
+impl<W: Writer> Writer for Listing<W> {
+    fn new() -> Self {
+        Self { inner: W::new(), lines: vec![] }
+    }
+    fn write_command(&mut self, command: &[u8], dbg: DbgNode) -> Result<Written, OperationError> {
+        self.write_command_bitstring(command, command.len() * 8, dbg)
+    }
+    fn write_command_bitstring(&mut self, command: &[u8], bits: usize, dbg: DbgNode) -> Result<Written, OperationError> {
+        let offsets = dbg.offsets.clone();
+        let written = self.inner.write_command_bitstring(command, bits, dbg)?;
+        let data = SliceData::from_raw(command.to_vec(), bits);
+        self.list_bits(&written.cell.to_string(), written.offset, data, &offsets, 0);
+        Ok(written)
+    }
+    fn write_composite_command(&mut self, code: &[u8], reference: BuilderData, pos: DbgPos, dbg: DbgNode) -> Result<Written, OperationError> {
+        let cell = reference.clone().into_cell().map_err(|_| OperationError::NotFitInSlice)?;
+        let child = dbg.clone();
+        let mut offsets = BTreeMap::new();
+        offsets.insert(0, pos.clone());
+        let written = self.inner.write_composite_command(code, reference, pos, dbg)?;
+        let label = written.cell.to_string();
+        if !code.is_empty() {
+            self.list_bits(&label, written.offset, SliceData::from_raw(code.to_vec(), code.len() * 8), &offsets, 0);
+        }
+        self.list_cell(format!("{}.ref", label), &cell, &child, 1);
+        Ok(written)
+    }
+    fn finalize(self) -> Result<(BuilderData, DbgNode), OperationError> {
+        self.inner.finalize()
+    }
+    fn cells(&self) -> usize {
+        self.inner.cells()
+    }
+    fn depth(&self) -> usize {
+        self.inner.depth()
+    }
+    fn record(&mut self) {
+        self.inner.record()
+    }
+    fn take_written(&mut self) -> Vec<(SliceData, Written)> {
+        self.inner.take_written()
+    }
+    fn set_layout(&mut self, layout: Layout) {
+        self.inner.set_layout(layout)
+    }
+    fn skip_debug_info(&mut self) {
+        self.inner.skip_debug_info()
+    }
+}
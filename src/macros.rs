@@ -32,7 +32,8 @@ macro_rules! simple_commands {
             pos: DbgPos
         ) -> CompileResult {
             par.assert_empty()?;
-            destination.write_command(&[$($code),*], DbgNode::from(pos))
+            destination.write_command(&[$($code),*], DbgNode::from(pos))?;
+            Ok(())
         }  
     };
 
@@ -56,20 +57,91 @@ macro_rules! simple_commands {
             $({
                 result.push($code);
             })*
-            destination.write_command(result.as_slice(), DbgNode::from(pos))
+            destination.write_command(result.as_slice(), DbgNode::from(pos))?;
+            Ok(())
         }
     };
 
+    // quiet variant has the same parameters and the encoding prefixed with 0xB7
+    (@quiet [] $($pname:ident = $parser:ident);* => $($code:expr),+) => {};
+    (@quiet [$quiet:ident] $($pname:ident = $parser:ident);* => $($code:expr),+) => {
+        simple_commands!(@resolve $quiet $($pname = $parser);* => 0xB7, $($code),+);
+    };
+
+    // register command along with its quiet variant if any
+    (@enumerate $commands:ident [$($name:ident)+]) => {
+        $( $commands.push((stringify!($name), Engine::<T>::$name as CompileHandler<T>)); )+
+    };
+
+    // describe command along with its quiet variant if any, the encoding has parameters set to zero
+    (@info $info:ident [$command:ident] [$($pname:ident = $parser:ident)*] $($code:expr),+) => {{
+        $( let $pname = zero($parser); )*
+        $info.push((stringify!($command), vec![$(stringify!($parser)),*], vec![$($code),+]));
+    }};
+    (@info $info:ident [$command:ident $quiet:ident] [$($pname:ident = $parser:ident)*] $($code:expr),+) => {
+        simple_commands!(@info $info [$command] [$($pname = $parser)*] $($code),+);
+        simple_commands!(@info $info [$quiet] [$($pname = $parser)*] 0xB7, $($code),+);
+    };
+
     // parse whole block of simple commands
-    ($($command: ident $($pname:ident = $parser:ident);* => $($code:expr),+ )*) => {
+    ($($command: ident $(/ $quiet:ident)? $($pname:ident = $parser:ident);* => $($code:expr),+ )*) => {
         $(
             simple_commands!(@resolve $command $($pname = $parser);* => $($code),*);
+            simple_commands!(@quiet [$($quiet)?] $($pname = $parser);* => $($code),*);
         )*
-        pub fn enumerate_simple_commands() -> &'static [(&'static str, CompileHandler<T>)] {
-            &[
-                $( (stringify!($command), Engine::<T>::$command), )*
-            ]
+        #[allow(clippy::vec_init_then_push)]
+        pub fn enumerate_simple_commands() -> Vec<(&'static str, CompileHandler<T>)> {
+            let mut commands = Vec::new();
+            $( simple_commands!(@enumerate commands [$command $($quiet)?]); )*
+            commands
+        }
+        #[allow(clippy::vec_init_then_push)]
+        pub fn enumerate_simple_commands_info() -> Vec<(&'static str, Vec<&'static str>, Vec<u8>)> {
+            let mut info = Vec::new();
+            $( simple_commands!(@info info [$command $($quiet)?] [$($pname = $parser)*] $($code),+); )*
+            info
         }
     };
 
 }
+
+// tracing is optional, e.g. to keep WebAssembly builds small
+#[cfg(feature = "tracing")]
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => { tracing::$level!($($arg)+) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {};
+}
+
+/// Enters the span, it is exited when the guard returned is dropped
+#[cfg(feature = "tracing")]
+macro_rules! enter_span {
+    ($level:ident, $($arg:tt)+) => { tracing::span!(tracing::Level::$level, $($arg)+).entered() };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! enter_span {
+    ($level:ident, $($arg:tt)+) => { () };
+}
+
+/// Evaluates the body with the writer type selected by options: commands are rewritten
+/// by `Peephole` if optimized, debug info is dropped by the writer given for skipping it.
+/// E.g. `with_writer!(options, |T| compile::<T>(code, options))`
+macro_rules! with_writer {
+    ($options:expr, |$writer:ident| $body:expr) => {
+        with_writer!($options, $crate::writer::CodePage0, $crate::writer::NullDebug, |$writer| $body)
+    };
+    ($options:expr, $plain:ty, $skipping:ty, |$writer:ident| $body:expr) => {
+        match ($options.optimize, $options.skip_debug_info) {
+            (false, false) => { type $writer = $plain; $body }
+            (true, false) => { type $writer = $crate::peephole::Peephole<$plain>; $body }
+            (false, true) => { type $writer = $skipping; $body }
+            (true, true) => { type $writer = $crate::peephole::Peephole<$skipping>; $body }
+        }
+    };
+}
+
+pub(crate) use {enter_span, event, with_writer};
@@ -0,0 +1,262 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use ton_types::{BuilderData, Cell, SliceData, UInt256};
+
+use super::{
+    CompileError, CompileOptions, DbgInfo, Engine, Lines, Resolver, Symbol, expand_includes, finalize_code, has_includes, lines_to_string,
+    macros::with_writer,
+    writer::Writer,
+};
+
+/// Cell standing for the code of external label until it is linked,
+/// it takes the whole cell so commands keep it in a reference
+pub(crate) fn extern_cell(name: &str) -> ton_types::Result<Cell> {
+    let hash = UInt256::calc_file_hash(format!("extern {}", name).as_bytes());
+    BuilderData::with_raw(hash.as_slice().repeat(4), 1023)?.into_cell()
+}
+
+/// Separately compiled file: its code, the code of its global labels
+/// and names of external labels it references, see `compile_object` and `link`
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Object {
+    #[cfg_attr(feature = "serde", serde(with = "boc"))]
+    pub code: Cell,
+    #[cfg_attr(feature = "serde", serde(with = "boc_values"))]
+    pub symbols: BTreeMap<String, Cell>,
+    pub externs: Vec<String>,
+    pub dbg: DbgInfo,
+}
+
+/// Cells are kept as bags of cells encoded in base64
+#[cfg(feature = "serde")]
+pub(crate) mod boc {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+    use ton_types::Cell;
+
+    pub fn encode(cell: &Cell) -> Result<String, String> {
+        ton_types::serialize_toc(cell).map(base64::encode).map_err(|e| e.to_string())
+    }
+
+    pub fn decode(text: &str) -> Result<Cell, String> {
+        let bytes = base64::decode(text).map_err(|e| e.to_string())?;
+        ton_types::deserialize_tree_of_cells(&mut bytes.as_slice()).map_err(|e| e.to_string())
+    }
+
+    pub fn serialize<S: Serializer>(cell: &Cell, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(cell).map_err(serde::ser::Error::custom)?)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cell, D::Error> {
+        decode(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod boc_values {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+    use std::collections::BTreeMap;
+    use ton_types::Cell;
+
+    pub fn serialize<S: Serializer>(map: &BTreeMap<String, Cell>, serializer: S) -> Result<S::Ok, S::Error> {
+        let map = map.iter()
+            .map(|(key, cell)| super::boc::encode(cell).map(|value| (key, value)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(serde::ser::Error::custom)?;
+        serializer.collect_map(map)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, Cell>, D::Error> {
+        let map = BTreeMap::<String, String>::deserialize(deserializer)?;
+        map.into_iter()
+            .map(|(key, value)| super::boc::decode(&value).map(|cell| (key, cell)).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+fn into_cell(builder: BuilderData) -> Result<Cell, CompileError> {
+    builder.into_cell().map_err(|_| CompileError::unknown(0, 0, "failure while convert BuilderData to cell"))
+}
+
+fn debug_info(cell: &Cell, dbg: &crate::DbgNode) -> Result<DbgInfo, CompileError> {
+    DbgInfo::from(cell, dbg).map_err(|_| CompileError::unknown(0, 0, "failure while collect debug info"))
+}
+
+fn compile<T: Writer>(code: Lines, options: &CompileOptions) -> Result<Object, CompileError> {
+    let source = lines_to_string(&code);
+    let (code, symbols, externs) = Engine::<T>::with_options(code, options).compile_object(&source)?;
+    let (builder, dbg) = finalize_code(code)?;
+    let code = into_cell(builder)?;
+    let mut info = debug_info(&code, &dbg)?;
+    let symbols = symbols.into_iter().map(|(name, symbol)| {
+        let (builder, dbg) = finalize_code(symbol)?;
+        let cell = into_cell(builder)?;
+        info.append(&mut debug_info(&cell, &dbg)?);
+        Ok((name, cell))
+    }).collect::<Result<BTreeMap<_, _>, CompileError>>()?;
+    // placeholders are not part of the code linked
+    for name in &externs {
+        if let Ok(cell) = extern_cell(name) {
+            info.remove(&cell.repr_hash());
+        }
+    }
+    Ok(Object { code, symbols, externs, dbg: info })
+}
+
+/// Compiles file to be linked with other ones. Labels declared `.globl` are compiled
+/// into cells of the object, labels declared `.extern` may be referenced with `@name`
+/// by commands taking a block in a reference, such as CALLREF or PUSHREFCONT.
+/// Integers given by the resolver of options for external symbols are taken right away.
+pub fn compile_object(code: Lines, options: &CompileOptions) -> Result<Object, CompileError> {
+    let code = if has_includes(&code) {
+        expand_includes(code, &options.include_dirs)?
+    } else {
+        code
+    };
+    with_writer!(options, |T| compile::<T>(code, options))
+}
+
+/// Replaces placeholders of external labels in cell trees by cells of the symbols
+pub(crate) struct Linker {
+    symbols: HashMap<String, Cell>,
+    placeholders: HashMap<UInt256, String>,
+    resolver: Option<Resolver>,
+    resolved: HashMap<UInt256, Cell>,
+    linking: Vec<String>,
+    dbg: DbgInfo,
+}
+
+impl Linker {
+    pub fn new(resolver: Option<Resolver>) -> Self {
+        Self {
+            symbols: HashMap::new(),
+            placeholders: HashMap::new(),
+            resolver,
+            resolved: HashMap::new(),
+            linking: Vec::new(),
+            dbg: DbgInfo::new(),
+        }
+    }
+    pub fn add_symbol(&mut self, name: &str, cell: &Cell) -> Result<(), CompileError> {
+        if self.symbols.insert(name.to_string(), cell.clone()).is_some() {
+            return Err(CompileError::syntax(0, 0, format!("Global symbol {} is defined in several objects", name)))
+        }
+        Ok(())
+    }
+    pub fn add_externs(&mut self, names: &[String]) -> Result<(), CompileError> {
+        for name in names {
+            let cell = extern_cell(name).map_err(|e| CompileError::syntax(0, 0, e))?;
+            self.placeholders.insert(cell.repr_hash(), name.clone());
+        }
+        Ok(())
+    }
+    pub fn add_debug_info(&mut self, dbg: &DbgInfo) {
+        for (hash, offsets) in &dbg.map {
+            self.dbg.insert(*hash, offsets.clone());
+        }
+    }
+    /// Cell of the symbol defined by objects or given by the resolver
+    fn symbol(&self, name: &str) -> Result<Cell, CompileError> {
+        if let Some(cell) = self.symbols.get(name) {
+            return Ok(cell.clone())
+        }
+        match self.resolver.as_ref().and_then(|resolver| resolver.resolve(name)) {
+            Some(Symbol::Cell(cell)) => Ok(cell),
+            Some(Symbol::Integer(_)) => Err(CompileError::syntax(0, 0, format!("External symbol {} is not a cell", name))),
+            None => Err(CompileError::syntax(0, 0, format!("External symbol {} is not defined", name)))
+        }
+    }
+    fn relocate(&mut self, cell: &Cell) -> Result<Cell, CompileError> {
+        let hash = cell.repr_hash();
+        if let Some(cell) = self.resolved.get(&hash) {
+            return Ok(cell.clone())
+        }
+        let result = if let Some(name) = self.placeholders.get(&hash).cloned() {
+            if self.linking.contains(&name) {
+                return Err(CompileError::syntax(0, 0, format!("Recursive reference to symbol {}", name)))
+            }
+            let symbol = self.symbol(&name)?;
+            self.linking.push(name);
+            let result = self.relocate(&symbol);
+            self.linking.pop();
+            result?
+        } else {
+            let mut references = Vec::with_capacity(cell.references_count());
+            for i in 0..cell.references_count() {
+                let child = cell.reference(i).map_err(|_| CompileError::unknown(0, 0, "failure while read cell"))?;
+                references.push(self.relocate(&child)?);
+            }
+            if (0..cell.references_count()).all(|i| cell.reference(i).ok().map(|child| child.repr_hash()) == Some(references[i].repr_hash())) {
+                cell.clone()
+            } else {
+                let data = SliceData::from(cell.clone());
+                let mut builder = BuilderData::with_raw_and_refs(data.get_bytestring(0), data.remaining_bits(), references)
+                    .map_err(|_| CompileError::unknown(0, 0, "failure while relocate cell"))?;
+                builder.set_type(cell.cell_type());
+                let relocated = into_cell(builder)?;
+                // commands keep their positions
+                if let Some(offsets) = self.dbg.get(&hash).cloned() {
+                    self.dbg.insert(relocated.repr_hash(), offsets);
+                }
+                relocated
+            }
+        };
+        self.resolved.insert(hash, result.clone());
+        Ok(result)
+    }
+    /// Relocates the code, returns it along with debug info of its cells
+    pub fn link(mut self, code: &Cell) -> Result<(Cell, DbgInfo), CompileError> {
+        let code = self.relocate(code)?;
+        let mut info = DbgInfo::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![code.clone()];
+        while let Some(cell) = stack.pop() {
+            if !visited.insert(cell.repr_hash()) {
+                continue
+            }
+            if let Some(offsets) = self.dbg.get(&cell.repr_hash()) {
+                info.insert(cell.repr_hash(), offsets.clone());
+            }
+            stack.extend((0..cell.references_count()).filter_map(|i| cell.reference(i).ok()));
+        }
+        Ok((code, info))
+    }
+}
+
+/// Links objects into program code: the code of the first object with references to external
+/// labels replaced by the code of global labels of the objects, other objects provide global labels only
+pub fn link(objects: &[Object]) -> Result<(SliceData, DbgInfo), CompileError> {
+    link_objects(objects, None)
+}
+
+/// Links objects, external labels defined by none of them are given by the resolver
+pub fn link_with_resolver(objects: &[Object], resolver: Resolver) -> Result<(SliceData, DbgInfo), CompileError> {
+    link_objects(objects, Some(resolver))
+}
+
+fn link_objects(objects: &[Object], resolver: Option<Resolver>) -> Result<(SliceData, DbgInfo), CompileError> {
+    let main = objects.first().ok_or_else(|| CompileError::syntax(0, 0, "No objects to link"))?;
+    let mut linker = Linker::new(resolver);
+    for object in objects {
+        for (name, cell) in &object.symbols {
+            linker.add_symbol(name, cell)?;
+        }
+        linker.add_externs(&object.externs)?;
+        linker.add_debug_info(&object.dbg);
+    }
+    linker.link(&main.code).map(|(code, dbg)| (code.into(), dbg))
+}
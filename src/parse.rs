@@ -12,8 +12,9 @@
 */
 
 use num::{
-    Num,
+    BigInt, Num, Signed,
 };
+use std::borrow::Cow;
 use std::cmp::PartialOrd;
 use std::ops::Bound;
 use std::ops::{
@@ -21,13 +22,47 @@ use std::ops::{
     RangeBounds,
 };
 use super::errors::ParameterError;
+use super::macros::event;
+
+/// Parses decimal, `0x` hex, `0b` binary integer with optional `_` digit separators
+/// or `'c'` character code
+fn parse_number<T: Num>(par: &str) -> Result<T, ParameterError> {
+    if let Some(ch) = par.strip_prefix('\'').and_then(|rest| rest.strip_suffix('\'')) {
+        let mut chars = ch.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(ch), None) => T::from_str_radix(&(ch as u32).to_string(), 10).map_err(|_| ParameterError::OutOfRange),
+            _ => Err(ParameterError::UnexpectedType)
+        }
+    }
+    let (sign, body) = match par.strip_prefix('-') {
+        Some(body) => ("-", body),
+        None => ("", par)
+    };
+    let (digits, radix) = match body.get(..2) {
+        Some("0x") | Some("0X") => (&body[2..], 16),
+        Some("0b") | Some("0B") => (&body[2..], 2),
+        _ => (body, 10)
+    };
+    // underscores separate digits for readability
+    if digits.starts_with('_') {
+        return Err(ParameterError::UnexpectedType)
+    }
+    let digits = digits.replace('_', "");
+    T::from_str_radix(&format!("{}{}", sign, digits), radix).map_err(|_| {
+        // well-formed number which does not fit the type
+        match !digits.is_empty() && digits.chars().all(|ch| ch.is_digit(radix)) {
+            true => ParameterError::OutOfRange,
+            false => ParameterError::UnexpectedType
+        }
+    })
+}
 
 fn parse_range<T, R>(range: R) -> impl Fn(&str) -> Result<T, ParameterError>
 where
     T: Num + PartialOrd,
     R: RangeBounds<T>,
 {
-    move |p: &str| match T::from_str_radix(p, 10) {
+    move |p: &str| match parse_number::<T>(p) {
         Ok(value) => {
             match range.start_bound() {
                 Bound::Included(min) => {
@@ -57,7 +92,7 @@ where
             }
             Ok(value)
         }
-        _ => Err(ParameterError::UnexpectedType),
+        Err(e) => Err(e),
     }
 }
 
@@ -108,6 +143,11 @@ pub(super) fn parse_const_u11(par: &str) -> Result<u16, ParameterError> {
     parse_range(0u16..2048)(par)
 }
 
+// 12-bit flags of RUNVM
+pub(super) fn parse_const_u12(par: &str) -> Result<u16, ParameterError> {
+    parse_range(0u16..4096)(par)
+}
+
 pub(super) fn parse_const_u14(par: &str) -> Result<u16, ParameterError> {
     parse_range(0u16..16384)(par)
 }
@@ -121,6 +161,10 @@ pub(super) fn parse_const_i8(par: &str) -> Result<u8, ParameterError> {
     parse_range(-128i16..=127)(par).map(|e| e as u8)
 }
 
+pub(super) fn parse_const_u8(par: &str) -> Result<u8, ParameterError> {
+    parse_range(0u16..=255)(par).map(|e| e as u8)
+}
+
 pub(super) fn parse_const_u8_plus_one(par: &str) -> Result<u8, ParameterError> {
     parse_range(1u16..=256)(par).map(|e| (e - 1) as u8)
 }
@@ -155,36 +199,61 @@ pub(super) fn parse_register(
 }
 
 
+/// Fift-style `x{...}` or `b{...}` slice literal written without braces
+pub(super) fn unbrace(slice: &str) -> Cow<'_, str> {
+    match slice.get(1..).and_then(|rest| rest.strip_prefix('{')).and_then(|rest| rest.strip_suffix('}')) {
+        // an empty slice is written as a lone completion tag
+        Some("") if slice.starts_with(['x', 'X']) => Cow::Borrowed("x8_"),
+        Some("") => Cow::Borrowed("b1_"),
+        Some(digits) => Cow::Owned(format!("{}{}", &slice[..1], digits)),
+        None => Cow::Borrowed(slice)
+    }
+}
+
 pub fn parse_slice(slice: &str, bits: usize) -> Result<Vec<u8>, ParameterError> {
+    let slice = unbrace(slice);
     if slice.len() <= 1 {
-        log::error!(target: "compile", "empty string");
-        Err(ParameterError::UnexpectedType)
-    } else if slice.chars().next().unwrap().to_ascii_uppercase() != 'X' {
-        log::error!(target: "compile", "base not set");
+        event!(debug, target: "compile", "empty string");
         Err(ParameterError::UnexpectedType)
     } else {
-        parse_slice_base(&slice[1..], bits, 16)
+        match slice.chars().next().unwrap().to_ascii_uppercase() {
+            'X' => parse_slice_base(&slice[1..], bits, 16),
+            'B' => parse_slice_base(&slice[1..], bits, 2),
+            _ => {
+                event!(debug, target: "compile", slice = %slice, "base not set");
+                Err(ParameterError::UnexpectedType)
+            }
+        }
     }
 }
 
 pub fn parse_slice_base(slice: &str, mut bits: usize, base: u32) -> Result<Vec<u8>, ParameterError> {
-    debug_assert!(bits < 8, "it is offset to get slice parsed");
+    // offset of the slice in its first byte
+    let start = bits;
+    if start >= 8 {
+        return Err(ParameterError::OutOfRange)
+    }
     let mut acc = 0u8;
     let mut data = vec![];
     let mut completion_tag = false;
+    // bits per digit
+    let width = match base {
+        2 => 1,
+        16 => 4,
+        _ => return Err(ParameterError::NotSupported)
+    };
     for ch in slice.chars() {
         if completion_tag {
             return Err(ParameterError::UnexpectedType);
         }
         match ch.to_digit(base) {
-            Some(x) => {
-                if bits < 4 {
-                    acc |= (x << (4 - bits)) as u8;
-                    bits += 4;
-                } else {
-                    data.push(acc | (x as u8 >> (bits - 4)));
-                    acc = (x << (12 - bits)) as u8;
-                    bits -= 4;
+            Some(x) => for shift in (0..width).rev() {
+                acc |= (((x >> shift) & 1) as u8) << (7 - bits);
+                bits += 1;
+                if bits == 8 {
+                    data.push(acc);
+                    acc = 0;
+                    bits = 0;
                 }
             }
             None => {
@@ -196,19 +265,52 @@ pub fn parse_slice_base(slice: &str, mut bits: usize, base: u32) -> Result<Vec<u
             }
         }
     }
-    if bits != 0 {
-        if !completion_tag {
-            acc |= 1 << (7 - bits);
-        }
-        if acc != 0 || data.is_empty() {
+    if completion_tag {
+        // the last one bit is the tag, the slice is empty without any
+        if bits != 0 {
             data.push(acc);
         }
-    } else if !completion_tag {
+        while data.last() == Some(&0) {
+            data.pop();
+        }
+        if data.is_empty() {
+            data.push(0x80 >> start);
+        }
+    } else if bits != 0 {
+        data.push(acc | 1 << (7 - bits));
+    } else {
         data.push(0x80);
     }
     Ok(data)
 }
 
+/// Parses integer of TVM 257-bit signed range, see `parse_number` for its forms
+pub(super) fn parse_integer(par: &str) -> Result<BigInt, ParameterError> {
+    let value = parse_number::<BigInt>(par)?;
+    let limit = BigInt::from(1) << 256;
+    if value >= limit || value < -limit {
+        return Err(ParameterError::OutOfRange)
+    }
+    Ok(value)
+}
+
+/// Parses integer into bits of the width aligned to the most significant one,
+/// negative values are taken in two's complement
+pub(super) fn parse_integer_bits(par: &str, width: usize) -> Result<Vec<u8>, ParameterError> {
+    let limit = BigInt::from(1) << width;
+    let value = match parse_number::<BigInt>(par)? {
+        value if value.is_negative() && value >= -(&limit >> 1) => value + &limit,
+        value if !value.is_negative() && value < limit => value,
+        _ => return Err(ParameterError::OutOfRange)
+    };
+    let bytes = width.div_ceil(8);
+    let (_, digits) = (value << (bytes * 8 - width)).to_bytes_be();
+    let mut data = vec![0; bytes.saturating_sub(digits.len())];
+    data.extend(digits);
+    data.truncate(bytes);
+    Ok(data)
+}
+
 pub(super) fn parse_stack_register_u4(par: &str) -> Result<u8, ParameterError> {
     Ok(parse_register(par, 'S', 0..16)? as u8)
 }
@@ -232,14 +334,112 @@ pub(super) fn parse_plduz_parameter(par: &str) -> Result<u8, ParameterError> {
         })
 }
 
-pub(super) fn parse_string(arg: &str) -> Vec<u8> {
+/// Parses string argument: quoted text with escapes, `base64:` encoded bytes,
+/// `x` prefixed hex or raw text
+pub(super) fn parse_string(arg: &str) -> Result<Vec<u8>, ParameterError> {
+    if let Some(encoded) = arg.strip_prefix("base64:") {
+        return base64::decode(encoded).map_err(|_| ParameterError::UnexpectedType)
+    }
+    if let Some(quoted) = arg.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')).filter(|_| arg.len() > 1) {
+        return unescape(quoted)
+    }
     let mut string = String::from(arg);
     if string.to_ascii_uppercase().starts_with('X') {
         string.remove(0);
-        let res = hex::decode(string);
-        if res.is_ok() {
-            return res.unwrap()
+        if let Ok(bytes) = hex::decode(string) {
+            return Ok(bytes)
+        }
+    }
+    Ok(Vec::from(arg))
+}
+
+/// Resolves escapes `\n`, `\t`, `\r`, `\0`, `\\`, `\"`, `\'`, `\xNN` and `\u{N...}`
+fn unescape(text: &str) -> Result<Vec<u8>, ParameterError> {
+    let mut result = Vec::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut buffer = [0; 4];
+            result.extend_from_slice(ch.encode_utf8(&mut buffer).as_bytes());
+            continue
+        }
+        match chars.next().ok_or(ParameterError::UnexpectedType)? {
+            'n' => result.push(b'\n'),
+            't' => result.push(b'\t'),
+            'r' => result.push(b'\r'),
+            '0' => result.push(0),
+            ch @ ('\\' | '"' | '\'') => result.push(ch as u8),
+            'x' => {
+                let digits = chars.as_str().get(..2).ok_or(ParameterError::UnexpectedType)?;
+                result.push(u8::from_str_radix(digits, 16).map_err(|_| ParameterError::UnexpectedType)?);
+                chars.nth(1);
+            }
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{').ok_or(ParameterError::UnexpectedType)?;
+                let end = rest.find('}').ok_or(ParameterError::UnexpectedType)?;
+                let code = u32::from_str_radix(&rest[..end], 16).map_err(|_| ParameterError::UnexpectedType)?;
+                let ch = std::char::from_u32(code).ok_or(ParameterError::OutOfRange)?;
+                let mut buffer = [0; 4];
+                result.extend_from_slice(ch.encode_utf8(&mut buffer).as_bytes());
+                chars.nth(end + 1);
+            }
+            _ => return Err(ParameterError::UnexpectedType)
+        }
+    }
+    Ok(result)
+}
+
+/// Splits block of inline dictionary into `key => { value }` entries,
+/// returns keys along with values and their offsets in the block
+pub(super) fn parse_dictionary(block: &str) -> Result<Vec<(&str, usize, &str)>, ParameterError> {
+    let mut entries = vec![];
+    let mut offset = 0;
+    loop {
+        // skip whitespace and comments
+        let rest = &block[offset..];
+        let trimmed = rest.trim_start();
+        if let Some(comment) = trimmed.strip_prefix(';') {
+            offset = block.len() - comment.len() + comment.find('\n').unwrap_or(comment.len());
+            continue
+        }
+        if trimmed.is_empty() {
+            return Ok(entries)
+        }
+        let key_len = trimmed.find(|ch: char| ch.is_whitespace() || (ch == '=')).unwrap_or(trimmed.len());
+        let key = &trimmed[..key_len];
+        let value = trimmed[key_len..].trim_start()
+            .strip_prefix("=>")
+            .map(|value| value.trim_start())
+            .and_then(|value| value.strip_prefix('{'))
+            .ok_or(ParameterError::UnexpectedType)?;
+        let start = block.len() - value.len();
+        let end = start + closing_brace(value).ok_or(ParameterError::UnexpectedType)?;
+        entries.push((key, start, &block[start..end]));
+        offset = end + 1;
+    }
+}
+
+/// Offset of the brace closing the block the text starts in, braces of literals are not counted
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut literal = None;
+    let mut escaped = false;
+    for (i, ch) in text.char_indices() {
+        if let Some(closing) = literal {
+            if (ch == '\n') || (!escaped && (ch == closing)) {
+                literal = None
+            }
+            escaped = !escaped && (ch == '\\') && (closing == '"');
+            continue
+        }
+        match ch {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            ';' => literal = Some('\n'),
+            '\'' | '"' => literal = Some(ch),
+            _ => ()
         }
     }
-    Vec::from(arg)
+    None
 }
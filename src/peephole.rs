@@ -0,0 +1,136 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use ton_types::BuilderData;
+
+use crate::OperationError;
+use crate::debug::{DbgNode, DbgPos};
+use crate::writer::{Layout, Writer, Written};
+
+const NOP: u8 = 0x00;
+const SWAP: u8 = 0x01;
+const ADD: u8 = 0xA0;
+const SUB: u8 = 0xA1;
+const INC: u8 = 0xA4;
+const DEC: u8 = 0xA5;
+const ADDCONST: u8 = 0xA6;
+const MULCONST: u8 = 0xA7;
+const MUL: u8 = 0xA8;
+
+/// Writer rewriting adjacent simple commands before passing them on:
+/// drops NOP and SWAP SWAP, folds PUSHINT n followed by ADD, SUB or MUL into
+/// INC, DEC, ADDCONST or MULCONST. PUSHINT 0 is encoded as ZERO already.
+/// A folded command keeps debug position of the PUSHINT.
+/// Commands held back for rewriting are reported as written with no bits.
+pub(crate) struct Peephole<W: Writer> {
+    inner: W,
+    pending: Option<(Vec<u8>, DbgNode)>,
+}
+
+/// Value of PUSHINT with 4 or 8 bit argument
+fn small_int(command: &[u8]) -> Option<i8> {
+    match command {
+        [x] if x & 0xF0 == 0x70 => match x & 0x0F {
+            n @ 0..=10 => Some(n as i8),
+            n => Some(n as i8 - 16),
+        }
+        [0x80, x] => Some(*x as i8),
+        _ => None
+    }
+}
+
+/// Replacement of two adjacent commands: None if there is no rule,
+/// Some(None) if both commands are to be dropped
+fn rewrite(prev: &[u8], next: &[u8]) -> Option<Option<Vec<u8>>> {
+    if prev == [SWAP] && next == [SWAP] {
+        return Some(None)
+    }
+    let value = small_int(prev)?;
+    let addend = match next {
+        [ADD] => value,
+        [SUB] => value.checked_neg()?,
+        [MUL] => return Some(Some(vec![MULCONST, value as u8])),
+        _ => return None
+    };
+    Some(Some(match addend {
+        1 => vec![INC],
+        -1 => vec![DEC],
+        _ => vec![ADDCONST, addend as u8]
+    }))
+}
+
+impl<W: Writer> Peephole<W> {
+    fn flush(&mut self) -> Result<(), OperationError> {
+        if let Some((command, dbg)) = self.pending.take() {
+            self.inner.write_command(&command, dbg)?;
+        }
+        Ok(())
+    }
+    /// Writer with all the commands passed on
+    pub(crate) fn into_inner(mut self) -> Result<W, OperationError> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Writer> Writer for Peephole<W> {
+    fn new() -> Self {
+        Self { inner: W::new(), pending: None }
+    }
+    fn write_command(&mut self, command: &[u8], dbg: DbgNode) -> Result<Written, OperationError> {
+        if command == [NOP] {
+            return Ok(Written::default())
+        }
+        if let Some((prev, prev_dbg)) = self.pending.take() {
+            match rewrite(&prev, command) {
+                Some(Some(folded)) => {
+                    self.pending = Some((folded, prev_dbg));
+                    return Ok(Written::default())
+                }
+                Some(None) => return Ok(Written::default()),
+                None => self.inner.write_command(&prev, prev_dbg)?
+            };
+        }
+        // only short commands take part in rewriting
+        if command.len() <= 2 {
+            self.pending = Some((command.to_vec(), dbg));
+            Ok(Written::default())
+        } else {
+            self.inner.write_command(command, dbg)
+        }
+    }
+    fn write_command_bitstring(&mut self, command: &[u8], bits: usize, dbg: DbgNode) -> Result<Written, OperationError> {
+        self.flush()?;
+        self.inner.write_command_bitstring(command, bits, dbg)
+    }
+    fn write_composite_command(&mut self, code: &[u8], reference: BuilderData, pos: DbgPos, dbg: DbgNode) -> Result<Written, OperationError> {
+        self.flush()?;
+        self.inner.write_composite_command(code, reference, pos, dbg)
+    }
+    fn finalize(mut self) -> Result<(BuilderData, DbgNode), OperationError> {
+        self.flush()?;
+        self.inner.finalize()
+    }
+    fn cells(&self) -> usize {
+        self.inner.cells()
+    }
+    fn depth(&self) -> usize {
+        self.inner.depth()
+    }
+    fn set_layout(&mut self, layout: Layout) {
+        self.inner.set_layout(layout)
+    }
+    fn skip_debug_info(&mut self) {
+        self.inner.skip_debug_info()
+    }
+}
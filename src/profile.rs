@@ -0,0 +1,95 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::{collections::BTreeMap, fmt, time::{Duration, Instant}};
+
+/// Time taken by commands of a mnemonic
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommandProfile {
+    pub count: usize,
+    pub time: Duration,
+}
+
+/// Time spent in phases of compilation, see `compile_code_profiled`. Times of commands
+/// and parsing do not include time of the blocks nested in them, so the phases add up to the total
+/// along with the rest: splitting commands into tokens, looking them up and expanding macros.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompileProfile {
+    /// reading included files
+    pub includes: Duration,
+    /// scanning blocks for labels, macros and constants
+    pub parse: Duration,
+    /// compiling commands
+    pub commands: Duration,
+    /// completing the chain of cells
+    pub finalize: Duration,
+    pub total: Duration,
+    /// time of commands and macro calls by mnemonic in upper case
+    pub by_command: BTreeMap<String, CommandProfile>,
+}
+
+impl fmt::Display for CompileProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let other = self.total.saturating_sub(self.includes + self.parse + self.commands + self.finalize);
+        for (name, time) in [("includes", self.includes), ("parse", self.parse), ("commands", self.commands),
+            ("finalize", self.finalize), ("other", other), ("total", self.total)] {
+            writeln!(f, "{:<12} {:>12.3?}", name, time)?;
+        }
+        let mut commands = self.by_command.iter().collect::<Vec<_>>();
+        commands.sort_by(|(a, x), (b, y)| y.time.cmp(&x.time).then(a.cmp(b)));
+        for (name, command) in commands {
+            writeln!(f, "  {:<16} {:>8} {:>12.3?}", name, command.count, command.time)?;
+        }
+        Ok(())
+    }
+}
+
+/// Measures time of parsing and commands, nested ones are subtracted from the outer ones
+#[derive(Default)]
+pub(crate) struct Profiler {
+    // start of every measure going on and time of measures nested in it
+    started: Vec<(Instant, Duration)>,
+    profile: CompileProfile,
+}
+
+impl Profiler {
+    pub(crate) fn begin(&mut self) {
+        self.started.push((Instant::now(), Duration::ZERO))
+    }
+    /// Time of the measure completed without nested ones
+    fn end(&mut self) -> Duration {
+        let (start, nested) = match self.started.pop() {
+            Some(started) => started,
+            None => return Duration::ZERO
+        };
+        let elapsed = start.elapsed();
+        if let Some((_, outer)) = self.started.last_mut() {
+            *outer += elapsed
+        }
+        elapsed.saturating_sub(nested)
+    }
+    pub(crate) fn end_parse(&mut self) {
+        let time = self.end();
+        self.profile.parse += time
+    }
+    pub(crate) fn end_command(&mut self, mnemonic: &str) {
+        let time = self.end();
+        self.profile.commands += time;
+        let command = self.profile.by_command.entry(mnemonic.to_ascii_uppercase()).or_default();
+        command.count += 1;
+        command.time += time;
+    }
+    pub(crate) fn take(&mut self) -> CompileProfile {
+        std::mem::take(&mut self.profile)
+    }
+}
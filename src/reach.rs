@@ -0,0 +1,129 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::fmt;
+
+use crate::{DbgPos, Position, canonical_mnemonic};
+
+/// Commands following unconditional return, jump or exception in a block,
+/// from the first one up to the next label or the end of the block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeadCode {
+    pub position: Position,
+    /// line of the last command of the region
+    pub end_line: usize,
+    pub commands: usize,
+    /// command making the code unreachable
+    pub after: String,
+}
+
+impl fmt::Display for DeadCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} Unreachable code: {} commands after {} up to line {}",
+            self.position, self.commands, self.after, self.end_line)
+    }
+}
+
+/// Commands never passing control to the next one
+fn terminates(command: &str) -> bool {
+    matches!(canonical_mnemonic(command),
+        "RET" | "RETALT" | "RETBOOL" | "RETARGS" | "RETDATA" | "RETVARARGS" |
+        "JMP" | "JMPX" | "JMPXARGS" | "JMPXDATA" | "JMPXVARARGS" | "JMPREF" | "JMPREFDATA" |
+        "THROW" | "THROWANY" | "THROWARG" | "THROWARGANY"
+    )
+}
+
+/// State of a block: the command terminating it and index of the region following
+#[derive(Default)]
+struct Block {
+    after: Option<String>,
+    region: Option<usize>,
+}
+
+/// Tracks commands following unconditional RET, JMPX or THROW in every block compiled,
+/// they are dropped from the code when eliminating
+pub(crate) struct Reachability {
+    pub eliminate: bool,
+    blocks: Vec<Block>,
+    regions: Vec<DeadCode>,
+}
+
+impl Reachability {
+    pub(crate) fn new(eliminate: bool) -> Self {
+        Self { eliminate, blocks: vec![Block::default()], regions: vec![] }
+    }
+    /// Starts a new (possibly nested) block
+    pub(crate) fn begin(&mut self) {
+        self.blocks.push(Block::default())
+    }
+    /// Completes the current block
+    pub(crate) fn end(&mut self) {
+        if self.blocks.len() > 1 {
+            self.blocks.pop();
+        }
+    }
+    /// Code following a label is reachable by jumps to the label
+    pub(crate) fn label(&mut self) {
+        if let Some(block) = self.blocks.last_mut() {
+            *block = Block::default()
+        }
+    }
+    /// Whether the command to compile next is unreachable
+    pub(crate) fn is_dead(&self) -> bool {
+        self.blocks.last().is_some_and(|block| block.after.is_some())
+    }
+    pub(crate) fn command(&mut self, command: &str, pos: &DbgPos) {
+        let block = match self.blocks.last_mut() {
+            Some(block) => block,
+            None => return
+        };
+        let after = match block.after.as_ref() {
+            Some(after) => after,
+            None => {
+                if terminates(command) {
+                    block.after = Some(command.to_string())
+                }
+                return
+            }
+        };
+        let regions = &mut self.regions;
+        match block.region.and_then(|index| regions.get_mut(index)) {
+            Some(region) => {
+                region.end_line = pos.line_code;
+                region.commands += 1;
+            }
+            None => {
+                let position = Position {
+                    filename: pos.filename.clone(),
+                    line: pos.line_code,
+                    column: pos.column,
+                    span: pos.span.clone(),
+                };
+                block.region = Some(regions.len());
+                regions.push(DeadCode { position, end_line: pos.line_code, commands: 1, after: after.clone() });
+            }
+        }
+    }
+    /// Count of regions found so far
+    pub(crate) fn len(&self) -> usize {
+        self.regions.len()
+    }
+    /// Forgets regions found since the count, blocks may be compiled again
+    /// when command is retried with fewer parameters
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.regions.truncate(len)
+    }
+    pub(crate) fn take(&mut self) -> Vec<DeadCode> {
+        std::mem::take(&mut self.regions)
+    }
+}
@@ -0,0 +1,139 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::{collections::{BTreeMap, HashMap}, rc::Rc};
+
+use crate::{DbgPos, stack::Signature};
+
+/// Code following `name:` definition up to the end of the enclosing block,
+/// identified by the position where it starts
+#[derive(Clone)]
+pub(crate) struct Label {
+    pub line: usize,
+    pub column: usize,
+    pub body: Rc<str>,
+}
+
+/// Macro defined with `.macro NAME param, ...` up to `.endmacro` line
+pub(crate) struct Macro {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Macro {
+    /// Body with every token equal to a parameter name replaced by the argument
+    pub fn expand(&self, args: &[String]) -> String {
+        let mut result = String::with_capacity(self.body.len());
+        let mut token = String::new();
+        for ch in self.body.chars().chain(std::iter::once('\n')) {
+            if is_token_char(ch) {
+                token.push(ch);
+                continue;
+            }
+            match self.params.iter().position(|param| *param == token) {
+                Some(i) => result.push_str(&args[i]),
+                None => result.push_str(&token)
+            }
+            token.clear();
+            result.push(ch);
+        }
+        result
+    }
+}
+
+/// Body repeated in place of `.rep count[, counter]` directive up to `.endr` line,
+/// the counter is replaced by the number of the repetition like a macro parameter
+pub(crate) struct Repetition {
+    /// integer or name of constant
+    pub count: String,
+    pub body: Rc<Macro>,
+}
+
+/// Labels, macros and constants visible in a block: ones of enclosing blocks along with its own
+#[derive(Clone, Default)]
+pub(crate) struct Scope {
+    pub labels: HashMap<String, Label>,
+    pub macros: HashMap<String, Rc<Macro>>,
+    pub constants: HashMap<String, String>,
+    /// Positions in the original source set by `.loc` directives by the line they are on
+    pub locations: BTreeMap<usize, DbgPos>,
+    /// Stack signature of the block itself by the line it is declared on, not inherited by nested blocks
+    pub signature: Option<(usize, Signature)>,
+    /// Repetitions by the line of their `.rep` directive
+    pub repetitions: BTreeMap<usize, Rc<Repetition>>,
+    /// Names declared by `.globl` directives by the line they are on, visible to other files of the program
+    pub globals: BTreeMap<usize, Vec<String>>,
+    /// Names declared by `.extern` directives by the line they are on, defined by other files of the program
+    pub externs: BTreeMap<usize, Vec<String>>,
+}
+
+impl Scope {
+    pub fn extend(&mut self, other: Scope) {
+        self.labels.extend(other.labels);
+        self.macros.extend(other.macros);
+        self.constants.extend(other.constants);
+        self.locations.extend(other.locations);
+        self.repetitions.extend(other.repetitions);
+    }
+    /// Value of the constant, following constants defined via other ones
+    pub fn constant(&self, name: &str) -> Option<&str> {
+        let mut value = self.constants.get(name)?;
+        for _ in 0..self.constants.len() {
+            match self.constants.get(value) {
+                Some(next) => value = next,
+                None => break
+            }
+        }
+        Some(value)
+    }
+    /// Checks if there is a label, macro or constant of the name
+    pub fn defines(&self, name: &str) -> bool {
+        self.labels.contains_key(name) || self.macros.contains_key(&name.to_ascii_uppercase()) || self.constants.contains_key(name)
+    }
+    /// Copies labels, macros and constants of the name into the other scope,
+    /// fails if there are none or the other scope has some of the name already
+    pub fn export(&self, name: &str, other: &mut Scope) -> Result<(), &'static str> {
+        if !self.defines(name) {
+            return Err("is not defined")
+        }
+        let label = self.labels.get(name);
+        let definition = self.macros.get(&name.to_ascii_uppercase());
+        let constant = self.constants.get(name);
+        if (label.is_some() && other.labels.contains_key(name))
+            || (definition.is_some() && other.macros.contains_key(&name.to_ascii_uppercase()))
+            || (constant.is_some() && other.constants.contains_key(name)) {
+            return Err("is defined in several files")
+        }
+        if let Some(label) = label {
+            other.labels.insert(name.to_string(), label.clone());
+        }
+        if let Some(definition) = definition {
+            other.macros.insert(name.to_ascii_uppercase(), definition.clone());
+        }
+        if let Some(constant) = constant {
+            other.constants.insert(name.to_string(), constant.clone());
+        }
+        Ok(())
+    }
+    /// Original source position of the code line set by the last `.loc` directive before it
+    pub fn location(&self, line: usize) -> Option<&DbgPos> {
+        self.locations.range(..line).next_back().map(|(_, pos)| pos)
+    }
+}
+
+pub(crate) fn is_token_char(ch: char) -> bool {
+    ch.is_alphanumeric() || (ch == '-') || (ch == '_') || (ch == '.') || (ch == '@')
+}
@@ -21,6 +21,11 @@ use super::{
 
 use crate::debug::{DbgNode, DbgPos};
 
+/// Zero value of the parameter parsed, encodings of commands are described with it
+fn zero<V: Default, E>(_parser: fn(&str) -> Result<V, E>) -> V {
+    V::default()
+}
+
 // Compilation engine *********************************************************
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
@@ -28,16 +33,16 @@ impl<T: Writer> Engine<T> {
 
     #[cfg_attr(rustfmt, rustfmt_skip)]
     simple_commands! {
-        ABS                                  => 0xB6, 0x0B
+        ABS / QABS                           => 0xB6, 0x0B
         ACCEPT                               => 0xF8, 0x00
-        ADD                                  => 0xA0
-        ADDCONST z = parse_const_i8          => 0xA6, z
+        ADD / QADD                           => 0xA0
+        ADDCONST / QADDCONST z = parse_const_i8 => 0xA6, z
         ADDRAND                              => 0xF8, 0x15
         AGAIN                                => 0xEA
         AGAINBRK                             => 0xE3, 0x1A
         AGAINEND                             => 0xEB
         AGAINENDBRK                          => 0xE3, 0x1B
-        AND                                  => 0xB0
+        AND / QAND                           => 0xB0
         ATEXIT                               => 0xED, 0xF3
         ATEXITALT                            => 0xED, 0xF4
         BALANCE                              => 0xF8, 0x27
@@ -50,7 +55,7 @@ impl<T: Writer> Engine<T> {
         BDEPTH                               => 0xCF, 0x30
         BINDUMP                              => 0xFE, 0x12
         BINPRINT                             => 0xFE, 0x13
-        BITSIZE                              => 0xB6, 0x02
+        BITSIZE / QBITSIZE                   => 0xB6, 0x02
         BLESS                                => 0xED, 0x1E
         BLESSARGS c1 = parse_const_u4;
                   c2 = parse_const_i4        => 0xEE, (c1 << 4) | c2
@@ -65,6 +70,30 @@ impl<T: Writer> Engine<T> {
                 c2 = parse_const_u4_plus_one => 0x55, (c1 << 4) | c2
         BLKSWX                               => 0x63
         BLOCKLT                              => 0xF8, 0x24
+        BLS_VERIFY                           => 0xF9, 0x30, 0x00
+        BLS_AGGREGATE                        => 0xF9, 0x30, 0x01
+        BLS_FASTAGGREGATEVERIFY              => 0xF9, 0x30, 0x02
+        BLS_AGGREGATEVERIFY                  => 0xF9, 0x30, 0x03
+        BLS_G1_ADD                           => 0xF9, 0x30, 0x10
+        BLS_G1_SUB                           => 0xF9, 0x30, 0x11
+        BLS_G1_NEG                           => 0xF9, 0x30, 0x12
+        BLS_G1_MUL                           => 0xF9, 0x30, 0x13
+        BLS_G1_MULTIEXP                      => 0xF9, 0x30, 0x14
+        BLS_G1_ZERO                          => 0xF9, 0x30, 0x15
+        BLS_MAP_TO_G1                        => 0xF9, 0x30, 0x16
+        BLS_G1_INGROUP                       => 0xF9, 0x30, 0x17
+        BLS_G1_ISZERO                        => 0xF9, 0x30, 0x18
+        BLS_G2_ADD                           => 0xF9, 0x30, 0x20
+        BLS_G2_SUB                           => 0xF9, 0x30, 0x21
+        BLS_G2_NEG                           => 0xF9, 0x30, 0x22
+        BLS_G2_MUL                           => 0xF9, 0x30, 0x23
+        BLS_G2_MULTIEXP                      => 0xF9, 0x30, 0x24
+        BLS_G2_ZERO                          => 0xF9, 0x30, 0x25
+        BLS_MAP_TO_G2                        => 0xF9, 0x30, 0x26
+        BLS_G2_INGROUP                       => 0xF9, 0x30, 0x27
+        BLS_G2_ISZERO                        => 0xF9, 0x30, 0x28
+        BLS_PAIRING                          => 0xF9, 0x30, 0x30
+        BLS_PUSHR                            => 0xF9, 0x30, 0x31
         BOOLAND                              => 0xED, 0xF0
         BOOLEVAL                             => 0xED, 0xF9
         BOOLOR                               => 0xED, 0xF1
@@ -85,7 +114,9 @@ impl<T: Writer> Engine<T> {
         CALLX                                => 0xD8
         CALLXVARARGS                         => 0xDB, 0x38
         CAR                                  => 0x6F, 0x10
+        CARQ                                 => 0x6F, 0x60
         CDR                                  => 0x6F, 0x11
+        CDRQ                                 => 0x6F, 0x61
         CDATASIZE                            => 0xF9, 0x41
         CDATASIZEQ                           => 0xF9, 0x40
         CDEPTH                               => 0xD7, 0x65
@@ -97,7 +128,7 @@ impl<T: Writer> Engine<T> {
         CHKSIGNS                             => 0xF9, 0x11
         CHKSIGNU                             => 0xF9, 0x10
         CHKTUPLE                             => 0x6F, 0x30
-        CMP                                  => 0xBF
+        CMP / QCMP                           => 0xBF
         COMMA                                => 0x6F, 0x8C
         COMMIT                               => 0xF8, 0x0F
         COMPOS                               => 0xED, 0xF0
@@ -111,7 +142,7 @@ impl<T: Writer> Engine<T> {
         CONFIGOPTPARAM                       => 0xF8, 0x33
         CONS                                 => 0x6F, 0x02
         CTOS                                 => 0xD0
-        DEC                                  => 0xA5
+        DEC / QDEC                           => 0xA5
         DEBUG z = parse_const_u8_240         => 0xFE, z
         DEBUGOFF                             => 0xFE, 0x1E
         DEBUGON                              => 0xFE, 0x1F
@@ -125,7 +156,6 @@ impl<T: Writer> Engine<T> {
         DICTDEL                              => 0xF4, 0x59
         DICTDELGET                           => 0xF4, 0x62
         DICTDELGETREF                        => 0xF4, 0x63
-        DICTEMPTY                            => 0x6E
         DICTGET                              => 0xF4, 0x0A
         DICTGETNEXT                          => 0xF4, 0x74
         DICTGETNEXTEQ                        => 0xF4, 0x75
@@ -181,7 +211,6 @@ impl<T: Writer> Engine<T> {
         DICTMAXREF                           => 0xF4, 0x8B
         DICTMIN                              => 0xF4, 0x82
         DICTMINREF                           => 0xF4, 0x83
-        DICTPUSHCONST n = parse_const_u10    => 0xF4, 0xA4 | (n >> 8) as u8, n as u8
         DICTREMMAX                           => 0xF4, 0x9A
         DICTREMMAXREF                        => 0xF4, 0x9B
         DICTREMMIN                           => 0xF4, 0x92
@@ -237,12 +266,12 @@ impl<T: Writer> Engine<T> {
         DICTUSETGETB                         => 0xF4, 0x47
         DICTUSETGETREF                       => 0xF4, 0x1F
         DICTUSETREF                          => 0xF4, 0x17
-        DIV                                  => 0xA9, 0x04
-        DIVC                                 => 0xA9, 0x06
-        DIVR                                 => 0xA9, 0x05
-        DIVMOD                               => 0xA9, 0x0C
-        DIVMODC                              => 0xA9, 0x0E
-        DIVMODR                              => 0xA9, 0x0D
+        DIV / QDIV                           => 0xA9, 0x04
+        DIVC / QDIVC                         => 0xA9, 0x06
+        DIVR / QDIVR                         => 0xA9, 0x05
+        DIVMOD / QDIVMOD                     => 0xA9, 0x0C
+        DIVMODC / QDIVMODC                   => 0xA9, 0x0E
+        DIVMODR / QDIVMODR                   => 0xA9, 0x0D
         DROP                                 => 0x30
         DROPX                                => 0x65
         DROP2                                => 0x5B
@@ -255,40 +284,48 @@ impl<T: Writer> Engine<T> {
         ENDCST                               => 0xCD
         ENDXC                                => 0xCF, 0x23
         ENDS                                 => 0xD1
-        EQUAL                                => 0xBA
-        EQINT z = parse_const_i8             => 0xC0, z
+        ECRECOVER                            => 0xF9, 0x12
+        EQUAL / QEQUAL                       => 0xBA
+        EQINT / QEQINT z = parse_const_i8    => 0xC0, z
         EXECUTE                              => 0xD8
         EXPLODE c = parse_const_u4           => 0x6F, 0x40 | c
         EXPLODEVAR                           => 0x6F, 0x84
         FALSE                                => 0x70
         FIRST                                => 0x6F, 0x10
-        FITS z = parse_const_u8_plus_one     => 0xB4, z
-        FITSX                                => 0xB6, 0x00
+        FIRSTQ                               => 0x6F, 0x60
+        FITS / QFITS z = parse_const_u8_plus_one => 0xB4, z
+        FITSX / QFITSX                       => 0xB6, 0x00
+        GASCONSUMED                          => 0xF8, 0x07
         GASTOGRAM                            => 0xF8, 0x05
-        GEQ                                  => 0xBE
+        GEQ / QGEQ                           => 0xBE
+        GETFORWARDFEE                        => 0xF8, 0x38
+        GETFORWARDFEESIMPLE                  => 0xF8, 0x3C
+        GETGASFEE                            => 0xF8, 0x36
+        GETGASFEESIMPLE                      => 0xF8, 0x3B
         GETGLOBVAR                           => 0xF8, 0x40
         GETGLOB k = parse_const_u5           => 0xF8, 0x40 | k
+        GETORIGINALFWDFEE                    => 0xF8, 0x3A
         GETPARAM c = parse_const_u4          => 0xF8, 0x20 | c
+        GETPRECOMPILEDGAS                    => 0xF8, 0x39
+        GETSTORAGEFEE                        => 0xF8, 0x37
         GRAMTOGAS                            => 0xF8, 0x04
-        GREATER                              => 0xBC
-        GTINT z = parse_const_i8             => 0xC2, z
+        GREATER / QGREATER                   => 0xBC
+        GTINT / QGTINT z = parse_const_i8    => 0xC2, z
         HASHCU                               => 0xF9, 0x00
         HASHSU                               => 0xF9, 0x01
         IF                                   => 0xDE
         IFBITJMP n = parse_const_u5          => 0xE3, 0x80 | n
-        IFBITJMPREF n = parse_const_u5       => 0xE3, 0xC0 | n
         IFELSE                               => 0xE2
         IFJMP                                => 0xE0
         IFNBITJMP n = parse_const_u5         => 0xE3, 0xA0 | n
-        IFNBITJMPREF n = parse_const_u5      => 0xE3, 0xE0 | n
         IFNOT                                => 0xDF
         IFNOTJMP                             => 0xE1
         IFNOTRET                             => 0xDD
         IFNOTRETALT                          => 0xE3, 0x09
         IFRET                                => 0xDC
         IFRETALT                             => 0xE3, 0x08
-        INC                                  => 0xA4
-        INTSORT2                             => 0xB6, 0x0A
+        INC / QINC                           => 0xA4
+        INTSORT2 / QINTSORT2                 => 0xB6, 0x0A
         INVERT                               => 0xED, 0xF8
         IFREFELSEREF                         => 0xE3, 0x0F
         INDEX c = parse_const_u4             => 0x6F, 0x10 | c
@@ -302,13 +339,13 @@ impl<T: Writer> Engine<T> {
                k = parse_const_u2            => 0x6F, 0xC0 | (i << 4) | (j << 2) | k
         INITCODEHASH                         => 0xF8, 0x2B
         ISNAN                                => 0xC4
-        ISNEG                                => 0xC1, 0x00
-        ISNPOS                               => 0xC1, 0x01
-        ISNNEG                               => 0xC2, 0xFF
+        ISNEG / QISNEG                       => 0xC1, 0x00
+        ISNPOS / QISNPOS                     => 0xC1, 0x01
+        ISNNEG / QISNNEG                     => 0xC2, 0xFF
         ISNULL                               => 0x6E
-        ISPOS                                => 0xC2, 0x00
+        ISPOS / QISPOS                       => 0xC2, 0x00
         ISTUPLE                              => 0x6F, 0x8A
-        ISZERO                               => 0xC0, 0x00
+        ISZERO / QISZERO                     => 0xC0, 0x00
         JMP n = parse_const_u14              => 0xF1, 0x40 | (((n / 256) as u8)), ((n % 256) as u8)
         JMPX                                 => 0xD9
         JMPXARGS p = parse_const_u4          => 0xDB, 0x10 | p
@@ -356,41 +393,39 @@ impl<T: Writer> Engine<T> {
         LDVARUINT32                          => 0xFA, 0x04
         LDZEROES                             => 0xD7, 0x60
         LDCONT                               => 0xD7, 0x66
-        LEQ                                  => 0xBB
-        LESS                                 => 0xB9
-        LESSINT z = parse_const_i8           => 0xC1, z
+        LEQ / QLEQ                           => 0xBB
+        LESS / QLESS                         => 0xB9
+        LESSINT / QLESSINT z = parse_const_i8 => 0xC1, z
         LOGFLUSH                             => 0xFE, 0xF0, 0x00
         LTIME                                => 0xF8, 0x25
-        MAX                                  => 0xB6, 0x09
-        MIN                                  => 0xB6, 0x08
-        MINMAX                               => 0xB6, 0x0A
-        MOD                                  => 0xA9, 0x08
-        MODC                                 => 0xA9, 0x0A
-        MODR                                 => 0xA9, 0x09
-        MUL                                  => 0xA8
-        MULCONST z = parse_const_i8          => 0xA7, z
-        MULDIV                               => 0xA9, 0x84
-        MULDIVC                              => 0xA9, 0x86
-        MULDIVR                              => 0xA9, 0x85
-        MULDIVMOD                            => 0xA9, 0x8C
-        MULDIVMODC                           => 0xA9, 0x8E
-        MULDIVMODR                           => 0xA9, 0x8D
-        MULMOD                               => 0xA9, 0x88
-        MULMODC                              => 0xA9, 0x8A
-        MULMODR                              => 0xA9, 0x89
+        MAX / QMAX                           => 0xB6, 0x09
+        MIN / QMIN                           => 0xB6, 0x08
+        MINMAX / QMINMAX                     => 0xB6, 0x0A
+        MOD / QMOD                           => 0xA9, 0x08
+        MODC / QMODC                         => 0xA9, 0x0A
+        MODR / QMODR                         => 0xA9, 0x09
+        MUL / QMUL                           => 0xA8
+        MULCONST / QMULCONST z = parse_const_i8 => 0xA7, z
+        MULDIV / QMULDIV                     => 0xA9, 0x84
+        MULDIVC / QMULDIVC                   => 0xA9, 0x86
+        MULDIVR / QMULDIVR                   => 0xA9, 0x85
+        MULDIVMOD / QMULDIVMOD               => 0xA9, 0x8C
+        MULDIVMODC / QMULDIVMODC             => 0xA9, 0x8E
+        MULDIVMODR / QMULDIVMODR             => 0xA9, 0x8D
+        MULMOD / QMULMOD                     => 0xA9, 0x88
+        MULMODC / QMULMODC                   => 0xA9, 0x8A
+        MULMODR / QMULMODR                   => 0xA9, 0x89
         MYADDR                               => 0xF8, 0x28
         MYCODE                               => 0xF8, 0x2A
-        NEGATE                               => 0xA3
-        NEQ                                  => 0xBD
-        NEQINT z = parse_const_i8            => 0xC3, z
+        NEGATE / QNEGATE                     => 0xA3
+        NEQ / QNEQ                           => 0xBD
+        NEQINT / QNEQINT z = parse_const_i8  => 0xC3, z
         NEWC                                 => 0xC8
-        NEWDICT                              => 0x6D
         NIL                                  => 0x6F, 0x00
         NIP                                  => 0x31
         NOP                                  => 0x00
-        NOT                                  => 0xB3
+        NOT / QNOT                           => 0xB3
         NOW                                  => 0xF8, 0x23
-        NULL                                 => 0x6D
         NULLROTRIF                           => 0x6F, 0xA2
         NULLROTRIF2                          => 0x6F, 0xA6
         NULLROTRIFNOT                        => 0x6F, 0xA3
@@ -400,11 +435,13 @@ impl<T: Writer> Engine<T> {
         NULLSWAPIFNOT                        => 0x6F, 0xA1
         NULLSWAPIFNOT2                       => 0x6F, 0xA5
         ONE                                  => 0x71
-        OR                                   => 0xB1
+        OR / QOR                             => 0xB1
         OVER                                 => 0x21
         OVER2                                => 0x5D
         ONLYTOPX                             => 0x6A
         ONLYX                                => 0x6B
+        P256_CHKSIGNS                        => 0xF9, 0x15
+        P256_CHKSIGNU                        => 0xF9, 0x14
         PAIR                                 => 0x6F, 0x02
         PARSEMSGADDR                         => 0xFA, 0x42
         PARSEMSGADDRQ                        => 0xFA, 0x43
@@ -455,7 +492,7 @@ impl<T: Writer> Engine<T> {
         POPCTRX                              => 0xED, 0xE1
         POPROOT                              => 0xED, 0x54
         POPSAVE z = parse_control_register   => 0xED, 0x90 | z
-        POW2                                 => 0xAE
+        POW2 / QPOW2                         => 0xAE
         PREPARE n = parse_const_u14          => 0xF1, 0x80 | ((n / 256) as u8), ((n % 256) as u8)
         PREPAREDICT n = parse_const_u14      => 0xF1, 0x80 | ((n / 256) as u8), ((n % 256) as u8)
         PRINT z = parse_const_u4_14          => 0xFE, 0x30 | z
@@ -496,62 +533,7 @@ impl<T: Writer> Engine<T> {
             s2 = parse_stack_register_u4_minus_one;
             s3 = parse_stack_register_u4_minus_one
                                              => 0x54, 0x50 | s1, (s2 << 4) | s3
-        QABS                                 => 0xB7, 0xB6, 0x0B
-        QADD                                 => 0xB7, 0xA0
-        QADDCONST z = parse_const_i8         => 0xB7, 0xA6, z
-        QAND                                 => 0xB7, 0xB0
-        QBITSIZE                             => 0xB7, 0xB6, 0x02
-        QCMP                                 => 0xB7, 0xBF
-        QDEC                                 => 0xB7, 0xA5
-        QDIV                                 => 0xB7, 0xA9, 0x04
-        QDIVC                                => 0xB7, 0xA9, 0x06
-        QDIVR                                => 0xB7, 0xA9, 0x05
-        QDIVMOD                              => 0xB7, 0xA9, 0x0C
-        QDIVMODC                             => 0xB7, 0xA9, 0x0E
-        QDIVMODR                             => 0xB7, 0xA9, 0x0D
-        QEQINT z = parse_const_i8            => 0xB7, 0xC0, z
-        QEQUAL                               => 0xB7, 0xBA
-        QFITS z = parse_const_u8_plus_one    => 0xB7, 0xB4, z
-        QFITSX                               => 0xB7, 0xB6, 0x00
-        QGEQ                                 => 0xB7, 0xBE
-        QGREATER                             => 0xB7, 0xBC
-        QGTINT z = parse_const_i8            => 0xB7, 0xC2, z
-        QINC                                 => 0xB7, 0xA4
-        QINTSORT2                            => 0xB7, 0xB6, 0x0A
-        QMAX                                 => 0xB7, 0xB6, 0x09
-        QMIN                                 => 0xB7, 0xB6, 0x08
-        QMINMAX                              => 0xB7, 0xB6, 0x0A
-        QMOD                                 => 0xB7, 0xA9, 0x08
-        QMODC                                => 0xB7, 0xA9, 0x0A
-        QMODR                                => 0xB7, 0xA9, 0x09
-        QMUL                                 => 0xB7, 0xA8
-        QMULCONST z = parse_const_i8         => 0xB7, 0xA7, z
-        QMULDIV                              => 0xB7, 0xA9, 0x84
-        QMULDIVC                             => 0xB7, 0xA9, 0x86
-        QMULDIVR                             => 0xB7, 0xA9, 0x85
-        QMULDIVMOD                           => 0xB7, 0xA9, 0x8C
-        QMULDIVMODC                          => 0xB7, 0xA9, 0x8E
-        QMULDIVMODR                          => 0xB7, 0xA9, 0x8D
-        QMULMOD                              => 0xB7, 0xA9, 0x88
-        QMULMODC                             => 0xB7, 0xA9, 0x8A
-        QMULMODR                             => 0xB7, 0xA9, 0x89
-        QLESS                                => 0xB7, 0xB9
-        QLESSINT z = parse_const_i8          => 0xB7, 0xC1, z
-        QLEQ                                 => 0xB7, 0xBB
-        QNEGATE                              => 0xB7, 0xA3
-        QNEQ                                 => 0xB7, 0xBD
-        QNEQINT z = parse_const_i8           => 0xB7, 0xC3, z
-        QNOT                                 => 0xB7, 0xB3
-        QOR                                  => 0xB7, 0xB1
-        QPOW2                                => 0xB7, 0xAE
-        QSGN                                 => 0xB7, 0xB8
-        QSUB                                 => 0xB7, 0xA1
-        QSUBR                                => 0xB7, 0xA2
         QTLEN                                => 0x6F, 0x89
-        QUBITSIZE                            => 0xB7, 0xB6, 0x03
-        QUFITS z = parse_const_u8_plus_one   => 0xB7, 0xB5, z
-        QUFITSX                              => 0xB7, 0xB6, 0x01
-        QXOR                                 => 0xB7, 0xB2
         RAND                                 => 0xF8, 0x11
         RANDSEED                             => 0xF8, 0x26
         RANDU256                             => 0xF8, 0x10
@@ -578,6 +560,13 @@ impl<T: Writer> Engine<T> {
         REWRITESTDADDRQ                      => 0xFA, 0x45
         REWRITEVARADDR                       => 0xFA, 0x46
         REWRITEVARADDRQ                      => 0xFA, 0x47
+        RIST255_ADD / RIST255_QADD           => 0xF9, 0x22
+        RIST255_FROMHASH                     => 0xF9, 0x20
+        RIST255_MUL / RIST255_QMUL           => 0xF9, 0x24
+        RIST255_MULBASE / RIST255_QMULBASE   => 0xF9, 0x25
+        RIST255_PUSHL                        => 0xF9, 0x26
+        RIST255_SUB / RIST255_QSUB           => 0xF9, 0x23
+        RIST255_VALIDATE / RIST255_QVALIDATE => 0xF9, 0x21
         ROT                                  => 0x58
         ROT2                                 => 0x55, 0x13
         ROTREV                               => 0x59
@@ -585,6 +574,8 @@ impl<T: Writer> Engine<T> {
         ROLLREV c = parse_const_u4_plus_one  => 0x55, c << 4
         ROLLX                                => 0x61
         ROLLREVX                             => 0x62
+        RUNVM f = parse_const_u12            => 0xDB, 0x40 | (f >> 8) as u8, f as u8
+        RUNVMX                               => 0xDB, 0x50
         SAMEALT                              => 0xED, 0xFA
         SAMEALTSAV                           => 0xED, 0xFB
         SAVE z = parse_control_register      => 0xED, 0xA0 | z
@@ -630,6 +621,7 @@ impl<T: Writer> Engine<T> {
         SDSKIPLAST                           => 0xD7, 0x23
         SDSUBSTR                             => 0xD7, 0x24
         SECOND                               => 0x6F, 0x11
+        SECONDQ                              => 0x6F, 0x61
         SEMPTY                               => 0xC7, 0x00
         SENDRAWMSG                           => 0xFB, 0x00
         SETALTCTR z = parse_control_register => 0xED, 0x80 | z
@@ -646,6 +638,7 @@ impl<T: Writer> Engine<T> {
         SETGLOBVAR                           => 0xF8, 0x60
         SETGLOB k = parse_const_u5           => 0xF8, 0x60 | k
         SETFIRST                             => 0x6F, 0x50
+        SETFIRSTQ                            => 0x6F, 0x70
         SETINDEX c = parse_const_u4          => 0x6F, 0x50 | c
         SETINDEXQ c = parse_const_u4         => 0x6F, 0x70 | c
         SETINDEXVAR                          => 0x6F, 0x85
@@ -656,8 +649,10 @@ impl<T: Writer> Engine<T> {
         SETRAND                              => 0xF8, 0x14
         SETRETCTR z = parse_control_register => 0xED, 0x70 | z
         SETSECOND                            => 0x6F, 0x51
+        SETSECONDQ                           => 0x6F, 0x71
         SETTHIRD                             => 0x6F, 0x52
-        SGN                                  => 0xB8
+        SETTHIRDQ                            => 0x6F, 0x72
+        SGN / QSGN                           => 0xB8
         SHA256U                              => 0xF9, 0x02
         SINGLE                               => 0x6F, 0x01
         SKIPDICT                             => 0xF4, 0x01
@@ -697,7 +692,6 @@ impl<T: Writer> Engine<T> {
         STREF                                => 0xCC
         STREF2CONST                          => 0xCF, 0x21
         STREF3CONST                          => 0xCF, 0xE2
-        STREFCONST                           => 0xCF, 0x20
         STREFQ                               => 0xCF, 0x18
         STREFR                               => 0xCF, 0x14
         STREFRQ                              => 0xCF, 0x1C
@@ -723,20 +717,21 @@ impl<T: Writer> Engine<T> {
         STZERO                               => 0xCF, 0x81
         STZEROES                             => 0xCF, 0x40
         STCONT                               => 0xCF, 0x43
-        SUB                                  => 0xA1
+        SUB / QSUB                           => 0xA1
         SUBDICTGET                           => 0xF4, 0xB1
         SUBDICTIGET                          => 0xF4, 0xB2
         SUBDICTUGET                          => 0xF4, 0xB3
         SUBDICTIRPGET                        => 0xF4, 0xB6
         SUBDICTRPGET                         => 0xF4, 0xB5
         SUBDICTURPGET                        => 0xF4, 0xB7
-        SUBR                                 => 0xA2
+        SUBR / QSUBR                         => 0xA2
         SUBSLICE                             => 0xD7, 0x34
         SWAP2                                => 0x5A
         TEN                                  => 0x7A
         THENRET                              => 0xED, 0xF6
         THENRETALT                           => 0xED, 0xF7
         THIRD                                => 0x6F, 0x12
+        THIRDQ                               => 0x6F, 0x62
         THROWANY                             => 0xF2, 0xF0
         THROWANYIF                           => 0xF2, 0xF2
         THROWANYIFNOT                        => 0xF2, 0xF4
@@ -758,9 +753,9 @@ impl<T: Writer> Engine<T> {
         TUPLE s = parse_const_u4             => 0x6F, s
         TUPLEVAR                             => 0x6F, 0x80
         TWO                                  => 0x72
-        UBITSIZE                             => 0xB6, 0x03
-        UFITS z = parse_const_u8_plus_one    => 0xB5, z
-        UFITSX                               => 0xB6, 0x01
+        UBITSIZE / QUBITSIZE                 => 0xB6, 0x03
+        UFITS / QUFITS z = parse_const_u8_plus_one => 0xB5, z
+        UFITSX / QUFITSX                     => 0xB6, 0x01
         UNCONS                               => 0x6F, 0x22
         UNPACKFIRST c = parse_const_u4       => 0x6F, 0x30 | c
         UNPACKFIRSTVAR                       => 0x6F, 0x83
@@ -804,8 +799,7 @@ impl<T: Writer> Engine<T> {
         XCTOS                                => 0xD7, 0x39
         XLOAD                                => 0xD7, 0x3A
         XLOADQ                               => 0xD7, 0x3B
-        XOR                                  => 0xB2
-        ZERO                                 => 0x70
+        XOR / QXOR                           => 0xB2
         ZEROROTRIF                           => 0x6F, 0x92
         ZEROROTRIF2                          => 0x6F, 0x96
         ZEROROTRIFNOT                        => 0x6F, 0x93
@@ -819,7 +813,7 @@ impl<T: Writer> Engine<T> {
     pub fn add_simple_commands(&mut self) {
         // Add automatic commands
         for (command, handler) in Self::enumerate_simple_commands() {
-            if self.COMPILE_ROOT.insert(command, *handler).is_some() {
+            if self.COMPILE_ROOT.insert(command, handler).is_some() {
                 panic!("Token {} was already registered.", command);
             }
         }
@@ -0,0 +1,58 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::HashMap;
+use ton_types::{Cell, CellType, SliceData, UInt256};
+
+use crate::{CompileError, CompileOptions, DbgInfo, Lines, compile_code_debuggable_with_options, listing::format_pos};
+
+fn dump_cell(result: &mut String, path: String, cell: &Cell, dbg: &DbgInfo, seen: &mut HashMap<UInt256, String>) {
+    let hash = cell.repr_hash();
+    if let Some(first) = seen.get(&hash) {
+        result.push_str(&format!("cell {}: same as {}\n", path, first));
+        return
+    }
+    seen.insert(hash, path.clone());
+    let data = SliceData::from(cell.clone());
+    let kind = match cell.cell_type() {
+        CellType::Ordinary => String::new(),
+        cell_type => format!(" {},", cell_type)
+    };
+    result.push_str(&format!("cell {}:{} {} bits, {} refs, x{{{}}}\n",
+        path, kind, data.remaining_bits(), cell.references_count(), data.to_hex_string()));
+    if let Some(offsets) = dbg.get(&hash) {
+        for (offset, pos) in offsets {
+            result.push_str(&format!("  {:>4} {}\n", offset, format_pos(Some(pos))));
+        }
+    }
+    for i in 0..cell.references_count() {
+        if let Ok(child) = cell.reference(i) {
+            dump_cell(result, format!("{}.{}", path, i), &child, dbg, seen);
+        }
+    }
+}
+
+/// Textual dump of the code for snapshot tests: hash of the code, then every cell in depth-first
+/// order with its path of reference indexes, size, data in hex and positions of commands by bit offset.
+/// Cells met again are referred to by the path of the first one.
+pub fn snapshot(code: &Cell, dbg: &DbgInfo) -> String {
+    let mut result = format!("code {}\n", code.repr_hash().to_hex_string());
+    dump_cell(&mut result, "0".to_string(), code, dbg, &mut HashMap::new());
+    result
+}
+
+/// Compiles the code into its snapshot, see `snapshot`
+pub fn compile_snapshot(code: Lines, options: &CompileOptions) -> Result<String, CompileError> {
+    let (code, dbg) = compile_code_debuggable_with_options(code, options)?;
+    Ok(snapshot(code.cell(), &dbg))
+}
@@ -0,0 +1,226 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::fmt;
+
+use crate::{DbgPos, Position};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackWarningKind {
+    /// command takes more values than there are on the stack for sure
+    Underflow,
+    /// block leaves other number of values than its signature declares
+    Signature,
+}
+
+/// Command taking more values than there are on the stack for sure, or block
+/// breaking its signature, the command is `.sig` then and `needed` is the number declared
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackWarning {
+    pub kind: StackWarningKind,
+    pub position: Position,
+    pub command: String,
+    pub needed: usize,
+    pub available: usize,
+}
+
+impl fmt::Display for StackWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            StackWarningKind::Underflow => write!(f, "{} Stack underflow: {} takes {} values while {} available",
+                self.position, self.command, self.needed, self.available),
+            StackWarningKind::Signature => write!(f, "{} Stack signature mismatch: block leaves {} values while {} declared",
+                self.position, self.available, self.needed),
+        }
+    }
+}
+
+/// Values taken and left by a block, declared by `.sig (inputs -> outputs)` directive
+/// or by `; in: a b -> out: c` comment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Signature {
+    pub inputs: usize,
+    pub outputs: usize,
+}
+
+impl Signature {
+    /// Parses arguments of `.sig` directive: `(2 -> 1)`, parentheses are optional
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text.strip_prefix('(').and_then(|text| text.strip_suffix(')')).unwrap_or(text);
+        let (inputs, outputs) = text.split_once("->")?;
+        Some(Self { inputs: inputs.trim().parse().ok()?, outputs: outputs.trim().parse().ok()? })
+    }
+    /// Parses text of comment like `in: a b -> out: c` naming values taken and left,
+    /// or of `.sig` directive turned into comment `;sig (2 -> 1)`
+    pub(crate) fn parse_comment(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.get(..3).is_some_and(|head| head.eq_ignore_ascii_case("sig")) {
+            return Self::parse(&text[3..])
+        }
+        let (inputs, outputs) = text.strip_prefix("in:")?.split_once("->")?;
+        let outputs = outputs.trim_start().strip_prefix("out:")?;
+        let count = |names: &str| names.split(|ch: char| ch == ',' || ch.is_whitespace()).filter(|name| !name.is_empty()).count();
+        Some(Self { inputs: count(inputs), outputs: count(outputs) })
+    }
+}
+
+/// Values taken by the command from the stack and pushed back,
+/// None if unknown or command transfers control
+fn effect(command: &str, par: &[&str]) -> Option<(usize, usize)> {
+    let register = |i: usize| par.get(i)
+        .and_then(|p| p.get(1..))
+        .and_then(|index| index.parse::<usize>().ok())
+        .map(|index| index + 1);
+    let effect = match command {
+        "NOP" | "ACCEPT" | "THROW" | "COMMIT" | "SETCP" | "SETCP0" => (0, 0),
+        "PUSHINT" | "PUSHPOW2" | "PUSHNAN" | "ZERO" | "ONE" | "TWO" | "TEN" | "TRUE" | "FALSE" |
+        "PUSHSLICE" | "PUSHREF" | "PUSHREFSLICE" | "PUSHREFCONT" | "PUSHCONT" | "NEWC" | "NEWDICT" |
+        "NOW" | "BLOCKLT" | "LTIME" | "RANDSEED" | "BALANCE" | "MYADDR" | "CONFIGROOT" |
+        "GETGLOB" | "PUSHCTR" | "PUSHROOT" | "DEPTH" | "NIL" | "NULL" | "PUSHNULL" | "DICTPUSHCONST" => (0, 1),
+        "DUP" => (1, 2),
+        "OVER" => (2, 3),
+        "TUCK" => (2, 3),
+        "2DUP" => (2, 4),
+        "2OVER" => (4, 6),
+        "DROP" | "ENDS" | "IFRET" | "IFNOTRET" | "THROWIF" | "THROWIFNOT" | "THROWANY" |
+        "SETCODE" | "POPCTR" | "SETGLOB" | "POPROOT" => (1, 0),
+        "NIP" => (2, 1),
+        "2DROP" => (2, 0),
+        "SWAP" => (2, 2),
+        "ROT" | "-ROT" | "ROTREV" => (3, 3),
+        "2SWAP" => (4, 4),
+        "PUSH" => (register(0)?, register(0)? + 1),
+        "POP" => (register(0)?, register(0)? - 1),
+        "XCHG" => match par.len() {
+            0 => (2, 2),
+            1 => (register(0)?, register(0)?),
+            _ => {
+                let depth = register(0)?.max(register(1)?);
+                (depth, depth)
+            }
+        }
+        "ADD" | "SUB" | "SUBR" | "MUL" | "DIV" | "MOD" | "AND" | "OR" | "XOR" | "LESS" | "LEQ" |
+        "GREATER" | "GEQ" | "EQUAL" | "NEQ" | "CMP" | "MIN" | "MAX" | "STREF" | "STSLICE" |
+        "STU" | "STI" | "STGRAMS" | "STVARUINT16" => (2, 1),
+        "LSHIFT" | "RSHIFT" => if par.is_empty() { (2, 1) } else { (1, 1) },
+        "INC" | "DEC" | "NEGATE" | "NOT" | "ABS" | "ISZERO" | "ISNEG" | "ISPOS" | "ISNPOS" | "ISNNEG" |
+        "ADDCONST" | "MULCONST" | "EQINT" | "LESSINT" | "GTINT" | "NEQINT" | "CTOS" | "ENDC" |
+        "HASHCU" | "HASHSU" | "STREFCONST" | "PLDU" | "PLDI" | "PLDREF" | "SBITS" | "SREFS" | "BBITS" | "BREFS" => (1, 1),
+        "LDU" | "LDI" | "LDREF" | "LDMSGADDR" | "LDGRAMS" | "LDVARUINT16" => (1, 2),
+        _ => return None
+    };
+    Some(effect)
+}
+
+/// Commands which transfer control, after them depth is unknown, e.g. `IF`
+fn transfers_control(command: &str) -> bool {
+    !matches!(command, "THROWIF" | "THROWIFNOT" | "IFRET" | "IFNOTRET") &&
+        (command.starts_with("IF") || command.starts_with("CALL") || command.starts_with("JMP") ||
+        command.starts_with("THROW") || command.starts_with("RET") || matches!(command,
+            "EXECUTE" | "REPEAT" | "UNTIL" | "WHILE" | "AGAIN" | "REPEATEND" | "UNTILEND" | "WHILEEND" | "AGAINEND"
+        ))
+}
+
+/// Tracks stack depth of straight-line code of every block compiled.
+/// Code of a block is supposed to run on the stack the block is pushed onto.
+pub(crate) struct StackCheck {
+    depths: Vec<Option<usize>>,
+    warnings: Vec<StackWarning>,
+}
+
+impl StackCheck {
+    /// Starts checking with the given depth at the beginning of the code
+    pub(crate) fn new(depth: usize) -> Self {
+        Self { depths: vec![Some(depth)], warnings: vec![] }
+    }
+    /// Starts a new (possibly nested) block
+    pub(crate) fn begin(&mut self) {
+        let depth = self.depths.last().copied().flatten();
+        self.depths.push(depth)
+    }
+    /// Completes the current block
+    pub(crate) fn end(&mut self) {
+        if self.depths.len() > 1 {
+            self.depths.pop();
+        }
+    }
+    pub(crate) fn command(&mut self, command: &str, par: &[&str], pos: &DbgPos) {
+        let depth = match self.depths.last_mut() {
+            Some(depth) => depth,
+            None => return
+        };
+        let available = match *depth {
+            Some(available) => available,
+            None => return
+        };
+        *depth = match effect(command, par) {
+            Some((needed, _)) if needed > available => {
+                let position = Position {
+                    filename: pos.filename.clone(),
+                    line: pos.line_code,
+                    column: pos.column,
+                    span: pos.span.clone(),
+                };
+                let warning = StackWarning { kind: StackWarningKind::Underflow, position, command: command.to_string(), needed, available };
+                // blocks may be compiled again when command is retried with fewer parameters
+                if !self.warnings.contains(&warning) {
+                    self.warnings.push(warning)
+                }
+                None
+            }
+            Some((needed, pushed)) if !transfers_control(command) => Some(available - needed + pushed),
+            _ => None
+        }
+    }
+    /// Starts code of the signature: its inputs are the values available,
+    /// returns the depth before to pass to `leave`
+    pub(crate) fn enter(&mut self, signature: &Signature) -> Option<usize> {
+        let depth = self.depths.last_mut()?;
+        let before = *depth;
+        *depth = Some(signature.inputs);
+        before
+    }
+    /// Completes code of the signature checking the values it leaves,
+    /// values below its inputs are left as they were before
+    pub(crate) fn leave(&mut self, signature: &Signature, before: Option<usize>, pos: &DbgPos) {
+        let depth = match self.depths.last_mut() {
+            Some(depth) => depth,
+            None => return
+        };
+        if let Some(available) = *depth {
+            if available != signature.outputs {
+                let position = Position {
+                    filename: pos.filename.clone(),
+                    line: pos.line_code,
+                    column: pos.column,
+                    span: pos.span.clone(),
+                };
+                let warning = StackWarning {
+                    kind: StackWarningKind::Signature,
+                    position,
+                    command: ".sig".to_string(),
+                    needed: signature.outputs,
+                    available,
+                };
+                if !self.warnings.contains(&warning) {
+                    self.warnings.push(warning)
+                }
+            }
+        }
+        *depth = before.and_then(|before| before.checked_sub(signature.inputs)).map(|rest| rest + signature.outputs);
+    }
+    pub(crate) fn take(&mut self) -> Vec<StackWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+}
@@ -0,0 +1,55 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+use ton_types::{BuilderData, Cell};
+
+use super::{CompileError, compile_code_to_cell};
+
+/// StateInit cell with the code, optional data and root of libraries dictionary:
+/// `_ split_depth:(Maybe (## 5)) special:(Maybe TickTock) code:(Maybe ^Cell)
+/// data:(Maybe ^Cell) library:(HashmapE 256 SimpleLib) = StateInit`
+pub fn state_init(code: Cell, data: Option<Cell>, library: Option<Cell>) -> Result<Cell, CompileError> {
+    let failure = |_| CompileError::unknown(0, 0, "failure while build StateInit cell");
+    let mut builder = BuilderData::new();
+    // no split_depth and special
+    builder.append_raw(&[0x00], 2).map_err(failure)?;
+    for cell in [Some(code), data, library].iter().cloned() {
+        match cell {
+            Some(cell) => {
+                builder.append_bit_one().map_err(failure)?;
+                builder.checked_append_reference(cell).map_err(failure)?;
+            }
+            None => {
+                builder.append_bit_zero().map_err(failure)?;
+            }
+        }
+    }
+    builder.into_cell().map_err(failure)
+}
+
+/// Compiles code into serialized StateInit, the contents of .tvc file
+pub fn compile_code_to_tvc(code: &str, data: Option<Cell>, library: Option<Cell>) -> Result<Vec<u8>, CompileError> {
+    let cell = state_init(compile_code_to_cell(code)?, data, library)?;
+    ton_types::serialize_toc(&cell).map_err(|_| CompileError::unknown(0, 0, "failure while serialize cell"))
+}
+
+/// Compiles code into .tvc file
+#[cfg(feature = "fs")]
+pub fn write_tvc<P: AsRef<Path>>(path: P, code: &str, data: Option<Cell>, library: Option<Cell>) -> Result<(), CompileError> {
+    let tvc = compile_code_to_tvc(code, data, library)?;
+    std::fs::write(path.as_ref(), tvc).map_err(|e| {
+        CompileError::unknown(0, 0, format!("failure while write {}: {}", path.as_ref().display(), e))
+    })
+}
@@ -0,0 +1,53 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::HashSet;
+use ton_types::Cell;
+
+/// Size of compiled code as stored in a bag of cells, where equal cells are kept once
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodeStats {
+    /// count of distinct cells
+    pub cells: usize,
+    /// data bits of distinct cells
+    pub bits: usize,
+    /// references of distinct cells
+    pub refs: usize,
+    /// depth of the tree, 0 for a single cell
+    pub depth: usize,
+    /// count of cells by number of their references
+    pub refs_per_cell: [usize; 5],
+    /// bits of the largest cell, every cell keeps a continuation or its part
+    pub largest_continuation: usize,
+}
+
+impl CodeStats {
+    pub fn from_cell(code: &Cell) -> Self {
+        let mut stats = Self { depth: code.repr_depth() as usize, ..Self::default() };
+        let mut visited = HashSet::new();
+        let mut stack = vec![code.clone()];
+        while let Some(cell) = stack.pop() {
+            if !visited.insert(cell.repr_hash()) {
+                continue
+            }
+            let refs = cell.references_count();
+            stats.cells += 1;
+            stats.bits += cell.bit_length();
+            stats.refs += refs;
+            stats.refs_per_cell[refs.min(4)] += 1;
+            stats.largest_continuation = stats.largest_continuation.max(cell.bit_length());
+            stack.extend((0..refs).filter_map(|i| cell.reference(i).ok()));
+        }
+        stats
+    }
+}
@@ -0,0 +1,243 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::{collections::HashSet, ops::Range};
+
+use crate::{CodePage0, Engine};
+
+/// Directives taking names or text rather than operands
+pub(crate) const DECLARATIONS: &[&str] = &[".macro", ".equ", ".globl", ".extern", ".loc", ".include", ".sig", ".rep"];
+/// Directives closing bodies of `.macro` and `.rep`
+pub(crate) const CLOSINGS: &[&str] = &[".endmacro", ".endr"];
+
+/// Piece of a source line found by `scan_line`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Piece {
+    Word,
+    Comma,
+    Open,
+    Close,
+    Comment,
+}
+
+/// Offset of the end of the block comment going on at the offset, nesting level is updated
+fn skip_comment(line: &str, mut i: usize, comment: &mut usize) -> usize {
+    while i < line.len() && *comment > 0 {
+        if line[i..].starts_with("*/") {
+            *comment -= 1;
+            i += 2
+        } else if line[i..].starts_with("/*") {
+            *comment += 1;
+            i += 2
+        } else {
+            i += line[i..].chars().next().map_or(1, char::len_utf8)
+        }
+    }
+    i
+}
+
+/// Offset of the end of the word starting at the offset, literals are parts of words
+fn skip_word(line: &str, start: usize) -> usize {
+    let mut closing = None;
+    let mut escaped = false;
+    for (i, ch) in line[start..].char_indices().map(|(i, ch)| (start + i, ch)) {
+        if let Some(close) = closing {
+            if close == ' ' && (ch.is_whitespace() || ch == ',' || ch == ';') {
+                return i
+            }
+            if escaped {
+                escaped = false
+            } else if ch == '\\' && close == '"' {
+                escaped = true
+            } else if ch == close {
+                closing = None
+            }
+            continue
+        }
+        let word = &line[start..i];
+        match ch {
+            '{' if matches!(word, "x" | "X" | "b" | "B") || word.ends_with('$') => closing = Some('}'),
+            '(' if matches!(word.to_ascii_lowercase().as_str(), "boc" | "library") => closing = Some(')'),
+            ':' if word == "base64" => closing = Some(' '),
+            ':' => return i + 1,
+            '\'' | '"' => closing = Some(ch),
+            '/' if line[i..].starts_with("/*") => return i,
+            _ if ch.is_whitespace() || matches!(ch, ',' | ';' | '{' | '}') => return i,
+            _ => ()
+        }
+    }
+    line.len()
+}
+
+/// Splits the line into pieces with their byte ranges in the line. The nesting level
+/// of `/* ... */` comment going on at the line start is updated to the one at its end.
+pub(crate) fn scan_line(line: &str, comment: &mut usize) -> Vec<(Piece, Range<usize>)> {
+    let mut pieces = Vec::new();
+    let mut i = 0;
+    while i < line.len() {
+        let ch = line[i..].chars().next().unwrap_or_default();
+        if ch.is_whitespace() {
+            i += ch.len_utf8();
+            continue
+        }
+        // comments do not take trailing whitespace
+        let (piece, end) = match ch {
+            _ if *comment > 0 => {
+                let end = skip_comment(line, i, comment);
+                (Piece::Comment, i + line[i..end].trim_end().len())
+            }
+            ';' => (Piece::Comment, line.trim_end().len()),
+            '/' if line[i..].starts_with("/*") => {
+                *comment = 1;
+                let end = skip_comment(line, i + 2, comment);
+                (Piece::Comment, i + line[i..end].trim_end().len())
+            }
+            ',' => (Piece::Comma, i + 1),
+            '{' => (Piece::Open, i + 1),
+            '}' => (Piece::Close, i + 1),
+            _ => (Piece::Word, skip_word(line, i))
+        };
+        pieces.push((piece, i..end));
+        i = end;
+    }
+    pieces
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// built-in command, e.g. `PUSHINT`
+    Mnemonic,
+    /// directive, e.g. `.blob` or `.macro`
+    Directive,
+    /// name of macro defined in the code
+    Macro,
+    /// label definition `name:` or reference `@name`
+    Label,
+    /// decimal, `0x` hex or `0b` binary integer, or `'c'` character code
+    Number,
+    /// `x{...}` or `b{...}` slice, `base64:...`, `boc(...)` or `library(...)` cell
+    Slice,
+    /// text in double quotes
+    String,
+    /// stack register `s1` or control register `c4`
+    Register,
+    /// name of constant, macro parameter or other operand
+    Identifier,
+    Comment,
+    OpenBrace,
+    CloseBrace,
+    Comma,
+}
+
+/// Classified token: its position and bytes within the code
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize,
+    pub span: Range<usize>,
+}
+
+fn is_number(word: &str) -> bool {
+    let body = word.strip_prefix('-').unwrap_or(word);
+    let (digits, radix) = match body.get(..2) {
+        Some("0x") | Some("0X") => (&body[2..], 16),
+        Some("0b") | Some("0B") => (&body[2..], 2),
+        _ => (body, 10)
+    };
+    digits.starts_with(|ch: char| ch.is_digit(radix)) && digits.chars().all(|ch| ch.is_digit(radix) || ch == '_')
+}
+
+fn is_register(word: &str) -> bool {
+    let index = match word.get(1..) {
+        Some(index) if word.starts_with(['s', 'S', 'c', 'C']) => index,
+        _ => return false
+    };
+    let index = index.strip_prefix('(').and_then(|index| index.strip_suffix(')')).map_or(index, |index| index.strip_prefix('-').unwrap_or(index));
+    !index.is_empty() && index.chars().all(|ch| ch.is_ascii_digit())
+}
+
+fn is_slice(word: &str) -> bool {
+    let lower = word.to_ascii_lowercase();
+    let digits = match word.get(1..) {
+        Some(digits) if word.starts_with(['x', 'X', 'b', 'B']) => digits,
+        _ => return lower.starts_with("base64:") || lower.starts_with("boc(") || lower.starts_with("library(")
+    };
+    let radix = if word.starts_with(['x', 'X']) { 16 } else { 2 };
+    let digits = digits.strip_prefix('{').and_then(|digits| digits.strip_suffix('}')).unwrap_or(digits);
+    !digits.is_empty() && digits.chars().all(|ch| ch.is_digit(radix) || ch == '_')
+}
+
+fn classify(word: &str, commands: &HashSet<&str>, macros: &HashSet<String>) -> TokenKind {
+    let upper = word.to_ascii_uppercase();
+    if word.ends_with(':') && !upper.starts_with("BASE64:") || word.starts_with('@') {
+        TokenKind::Label
+    } else if word.starts_with('.') {
+        TokenKind::Directive
+    } else if commands.contains(upper.as_str()) {
+        TokenKind::Mnemonic
+    } else if macros.contains(&upper) {
+        TokenKind::Macro
+    } else if word.starts_with('"') {
+        TokenKind::String
+    } else if word.starts_with('\'') || is_number(word) {
+        TokenKind::Number
+    } else if is_register(word) {
+        TokenKind::Register
+    } else if is_slice(word) {
+        TokenKind::Slice
+    } else {
+        TokenKind::Identifier
+    }
+}
+
+/// Tokens of the lines scanned, line numbers and offsets are counted from the first one
+pub(crate) fn classify_lines<'a>(lines: impl Iterator<Item = (&'a str, &'a [(Piece, Range<usize>)])> + Clone) -> Vec<Token> {
+    let commands = Engine::<CodePage0>::new(vec![]).COMPILE_ROOT.keys().copied().collect::<HashSet<_>>();
+    // macros may be used before they are defined
+    let mut macros = HashSet::new();
+    for (line, pieces) in lines.clone() {
+        let mut words = pieces.iter().filter(|(piece, _)| *piece == Piece::Word).map(|(_, range)| &line[range.clone()]);
+        if let (Some(directive), Some(name)) = (words.next(), words.next()) {
+            if directive.eq_ignore_ascii_case(".macro") {
+                macros.insert(name.to_ascii_uppercase());
+            }
+        }
+    }
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    for (i, (line, pieces)) in lines.enumerate() {
+        for (piece, range) in pieces {
+            let kind = match piece {
+                Piece::Word => classify(&line[range.clone()], &commands, &macros),
+                Piece::Comma => TokenKind::Comma,
+                Piece::Open => TokenKind::OpenBrace,
+                Piece::Close => TokenKind::CloseBrace,
+                Piece::Comment => TokenKind::Comment,
+            };
+            let column = line[..range.start].chars().count() + 1;
+            tokens.push(Token { kind, line: i + 1, column, span: offset + range.start..offset + range.end });
+        }
+        offset += line.len();
+    }
+    tokens
+}
+
+/// Splits code into classified tokens without compiling it, e.g. for syntax highlighting.
+/// Unterminated literals and comments last to the end of their line, lines of block comments
+/// are separate tokens.
+pub fn tokenize(code: &str) -> Vec<Token> {
+    let mut comment = 0;
+    let lines = code.split_inclusive('\n').map(|line| (line, scan_line(line, &mut comment))).collect::<Vec<_>>();
+    classify_lines(lines.iter().map(|(line, pieces)| (*line, pieces.as_slice())))
+}
@@ -0,0 +1,86 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::fmt;
+
+/// Version of TVM the code is compiled for, instructions introduced
+/// by later versions are rejected, see `CompileOptions::target_version`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TargetVersion(pub u32);
+
+impl fmt::Display for TargetVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// instructions missing from the original TVM along with the version introducing them
+const VERSIONS: &[(&str, u32)] = &[
+    ("MYCODE", 4),
+    ("BLS_VERIFY", 4),
+    ("BLS_AGGREGATE", 4),
+    ("BLS_FASTAGGREGATEVERIFY", 4),
+    ("BLS_AGGREGATEVERIFY", 4),
+    ("BLS_G1_ADD", 4),
+    ("BLS_G1_SUB", 4),
+    ("BLS_G1_NEG", 4),
+    ("BLS_G1_MUL", 4),
+    ("BLS_G1_MULTIEXP", 4),
+    ("BLS_G1_ZERO", 4),
+    ("BLS_MAP_TO_G1", 4),
+    ("BLS_G1_INGROUP", 4),
+    ("BLS_G1_ISZERO", 4),
+    ("BLS_G2_ADD", 4),
+    ("BLS_G2_SUB", 4),
+    ("BLS_G2_NEG", 4),
+    ("BLS_G2_MUL", 4),
+    ("BLS_G2_MULTIEXP", 4),
+    ("BLS_G2_ZERO", 4),
+    ("BLS_MAP_TO_G2", 4),
+    ("BLS_G2_INGROUP", 4),
+    ("BLS_G2_ISZERO", 4),
+    ("BLS_PAIRING", 4),
+    ("BLS_PUSHR", 4),
+    ("RUNVM", 4),
+    ("RUNVMX", 4),
+    ("GASCONSUMED", 4),
+    ("ECRECOVER", 4),
+    ("P256_CHKSIGNS", 4),
+    ("P256_CHKSIGNU", 4),
+    ("RIST255_FROMHASH", 4),
+    ("RIST255_VALIDATE", 4),
+    ("RIST255_QVALIDATE", 4),
+    ("RIST255_ADD", 4),
+    ("RIST255_QADD", 4),
+    ("RIST255_SUB", 4),
+    ("RIST255_QSUB", 4),
+    ("RIST255_MUL", 4),
+    ("RIST255_QMUL", 4),
+    ("RIST255_MULBASE", 4),
+    ("RIST255_QMULBASE", 4),
+    ("RIST255_PUSHL", 4),
+    ("GETGASFEE", 6),
+    ("GETSTORAGEFEE", 6),
+    ("GETFORWARDFEE", 6),
+    ("GETPRECOMPILEDGAS", 6),
+    ("GETORIGINALFWDFEE", 6),
+    ("GETGASFEESIMPLE", 6),
+    ("GETFORWARDFEESIMPLE", 6),
+];
+
+/// Version of TVM introducing the instruction, None for ones of the original TVM
+pub fn required_version(mnemonic: &str) -> Option<TargetVersion> {
+    VERSIONS.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(mnemonic))
+        .map(|(_, version)| TargetVersion(*version))
+}
@@ -11,22 +11,159 @@
 * limitations under the License.
 */
 
-use crate::OperationError;
-use ton_types::{BuilderData, SliceData};
+use crate::{CodeStats, OperationError};
+use ton_types::{BuilderData, Cell, SliceData};
 
-use crate::debug::{DbgNode, DbgPos};
+use crate::debug::{DbgNode, DbgPos, MAX_CHILDREN};
+
+/// Place of written command: index of the cell in the chain, bit offset in the cell
+/// and length in bits not counting the reference. Cells may be merged on finalization.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Written {
+    pub cell: usize,
+    pub offset: usize,
+    pub bits: usize,
+}
+
+/// How `finalize` joins the chain of cells written. The code compiled is the same on every run
+/// and platform for the same source, options and layout; a layout other than `Latest` also keeps it
+/// the same across versions of the assembler.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// The current choice, which may change in later versions, now `Greedy`
+    #[default]
+    Latest,
+    /// Every cell is inlined into the previous one if its bits and references fit there,
+    /// otherwise it is kept in a reference
+    Greedy,
+    /// Every cell is kept in a reference of the previous one as it was written
+    Chained,
+}
+
+impl Layout {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Latest => "latest",
+            Layout::Greedy => "greedy",
+            Layout::Chained => "chained",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Layout::Latest, Layout::Greedy, Layout::Chained].iter().copied().find(|layout| layout.name() == name)
+    }
+}
 
 pub trait Writer : 'static {
     fn new() -> Self;
-    fn write_command(&mut self, command: &[u8], dbg: DbgNode) -> Result<(), OperationError>;
-    fn write_command_bitstring(&mut self, command: &[u8], bits: usize, dbg: DbgNode) -> Result<(), OperationError>;
-    fn write_composite_command(&mut self, code: &[u8], reference: BuilderData, pos: DbgPos, dbg: DbgNode) -> Result<(), OperationError>;
-    fn finalize(self) -> (BuilderData, DbgNode);
+    /// Writes an opcode, raw data goes through `write_command_bitstring` which is never rewritten
+    fn write_command(&mut self, command: &[u8], dbg: DbgNode) -> Result<Written, OperationError>;
+    fn write_command_bitstring(&mut self, command: &[u8], bits: usize, dbg: DbgNode) -> Result<Written, OperationError>;
+    fn write_composite_command(&mut self, code: &[u8], reference: BuilderData, pos: DbgPos, dbg: DbgNode) -> Result<Written, OperationError>;
+    fn finalize(self) -> Result<(BuilderData, DbgNode), OperationError>;
+    /// Upper bound of count of cells written so far, 0 if not tracked
+    fn cells(&self) -> usize {
+        0
+    }
+    /// Upper bound of depth of cells written so far, 0 if not tracked
+    fn depth(&self) -> usize {
+        0
+    }
+    /// Starts keeping commands written, see `take_written`
+    fn record(&mut self) {}
+    /// Encodings of commands written since `record` and their places, recording stops.
+    /// Empty if not tracked.
+    fn take_written(&mut self) -> Vec<(SliceData, Written)> {
+        Vec::new()
+    }
+    /// Sets the way `finalize` joins cells, ignored if the writer does not split code
+    fn set_layout(&mut self, _layout: Layout) {}
+    /// Stops keeping debug positions of commands, `finalize` returns an empty node then.
+    /// Ignored if the writer does not keep them.
+    fn skip_debug_info(&mut self) {}
 }
 
-pub(crate) struct CodePage0 {
+/// Writer splitting code into a chain of cells
+pub struct CodePage0 {
     cells: Vec<BuilderData>,
+    // empty if debug info is skipped
     dbg: Vec<DbgNode>,
+    debug: bool,
+    // offset in the last cell bound by a command without bits, the next command takes it over
+    empty: Option<usize>,
+    // cells kept in references and max depth of the chain with them
+    ref_cells: usize,
+    ref_depth: usize,
+    written: Option<Vec<(SliceData, Written)>>,
+    layout: Layout,
+}
+
+impl CodePage0 {
+    /// Binds the node in the last cell along with the node of the reference if any,
+    /// failing without changes if the offset is bound by another command
+    fn bind(&mut self, offset: usize, bits: usize, dbg: DbgNode, child: Option<DbgNode>) -> Result<(), OperationError> {
+        if !self.debug {
+            return Ok(())
+        }
+        let node = self.dbg.last_mut().unwrap();
+        if child.is_some() && node.children.len() + dbg.children.len() >= MAX_CHILDREN {
+            return Err(OperationError::Internal("debug node has more children than cell references"))
+        }
+        // the command without bits at the offset is taken over
+        let taken = self.empty.filter(|empty| *empty == offset).and_then(|offset| node.offsets.remove(&offset));
+        if let Err(e) = node.checked_inline_node(offset, dbg) {
+            if let Some(pos) = taken {
+                node.offsets.insert(offset, pos);
+            }
+            return Err(e)
+        }
+        if let Some(child) = child {
+            node.checked_append_node(child)?;
+        }
+        self.empty = if bits == 0 { Some(offset) } else { None };
+        Ok(())
+    }
+    /// Starts the next cell with the command bound to its node, nothing is changed on failure
+    fn push_cell(&mut self, code: BuilderData, bits: usize, dbg: DbgNode, child: Option<DbgNode>) -> Result<(), OperationError> {
+        if !self.debug {
+            self.cells.push(code);
+            return Ok(())
+        }
+        let empty = self.empty.take();
+        self.cells.push(code);
+        self.dbg.push(DbgNode::new());
+        if let Err(e) = self.bind(0, bits, dbg, child) {
+            self.cells.pop();
+            self.dbg.pop();
+            self.empty = empty;
+            return Err(e)
+        }
+        Ok(())
+    }
+    /// Appends the command with the reference, the builder is restored on failure
+    fn append_composite(builder: &mut BuilderData, command: &[u8], cell: &Cell) -> bool {
+        let bits = builder.bits_used();
+        if builder.append_raw(command, command.len() * 8).is_err() {
+            return false
+        }
+        if builder.checked_append_reference(cell.clone()).is_ok() {
+            return true
+        }
+        if let Ok(restored) = BuilderData::with_raw_and_refs(builder.data().to_vec(), bits, builder.references().to_vec()) {
+            *builder = restored
+        }
+        false
+    }
+    /// Keeps the command written if recording
+    fn note(&mut self, command: &[u8], written: Written) -> Written {
+        if let Some(commands) = self.written.as_mut() {
+            commands.push((SliceData::from_raw(command.to_vec(), written.bits), written))
+        }
+        written
+    }
+    fn add_reference(&mut self, cell: &Cell) {
+        self.ref_cells += CodeStats::from_cell(cell).cells;
+        self.ref_depth = self.ref_depth.max(self.cells.len() + cell.repr_depth() as usize);
+    }
 }
 
 impl Writer for CodePage0 {
@@ -35,25 +172,34 @@ impl Writer for CodePage0 {
         Self {
             cells: vec![BuilderData::new()],
             dbg: vec![DbgNode::new()],
+            debug: true,
+            empty: None,
+            ref_cells: 0,
+            ref_depth: 0,
+            written: None,
+            layout: Layout::Latest,
         }
     }
     /// writes simple command
-    fn write_command(&mut self, command: &[u8], dbg: DbgNode) -> Result<(), OperationError> {
+    fn write_command(&mut self, command: &[u8], dbg: DbgNode) -> Result<Written, OperationError> {
         self.write_command_bitstring(command, command.len() * 8, dbg)
     }
-    fn write_command_bitstring(&mut self, command: &[u8], bits: usize, dbg: DbgNode) -> Result<(), OperationError> {
-        if !self.cells.is_empty() {
-            let offset = self.cells.last().unwrap().bits_used();
-            if self.cells.last_mut().unwrap().append_raw(command, bits).is_ok() {
-                self.dbg.last_mut().unwrap().inline_node(offset, dbg);
-                return Ok(());
+    fn write_command_bitstring(&mut self, command: &[u8], bits: usize, dbg: DbgNode) -> Result<Written, OperationError> {
+        // the command is bound before its bits are appended, so the cell is kept on failure
+        let fits = bits <= command.len() * 8;
+        if let Some(last) = self.cells.last() {
+            let offset = last.bits_used();
+            if fits && last.bits_free() >= bits {
+                self.bind(offset, bits, dbg, None)?;
+                self.cells.last_mut().unwrap().append_raw(command, bits)
+                    .map_err(|_| OperationError::Internal("command does not fit in the cell checked"))?;
+                return Ok(self.note(command, Written { cell: self.cells.len() - 1, offset, bits }));
             }
         }
         let mut code = BuilderData::new();
-        if code.append_raw(command, bits).is_ok() {
-            self.cells.push(code);
-            self.dbg.push(dbg);
-            return Ok(());
+        if fits && code.append_raw(command, bits).is_ok() {
+            self.push_cell(code, bits, dbg, None)?;
+            return Ok(self.note(command, Written { cell: self.cells.len() - 1, offset: 0, bits }));
         }
         Err(OperationError::NotFitInSlice)
     }
@@ -64,59 +210,125 @@ impl Writer for CodePage0 {
         reference: BuilderData,
         pos: DbgPos, 
         dbg: DbgNode,
-    ) -> Result<(), OperationError> {
-        if !self.cells.is_empty() {
-            let mut last = self.cells.last().unwrap().clone();
+    ) -> Result<Written, OperationError> {
+        let bits = command.len() * 8;
+        let cell = reference.into_cell().map_err(|_| OperationError::NotFitInSlice)?;
+        if let Some(last) = self.cells.last_mut() {
             let offset = last.bits_used();
-            if last.references_free() > 1 // one cell remains reserved for finalization
-                && last.append_raw(command, command.len() * 8).is_ok()
-                && last.checked_append_reference(reference.clone().into_cell().map_err(|_| OperationError::NotFitInSlice)?).is_ok() {
-
-                *self.cells.last_mut().unwrap() = last;
-
-                let node = self.dbg.last_mut().unwrap();
-                node.append(offset, pos);
-                node.append_node(dbg);
-                return Ok(());
+            // the last cell is changed in place, so the command is checked to fit beforehand,
+            // one reference remains reserved for finalization
+            if last.references_free() > 1 && last.bits_free() >= bits {
+                self.bind(offset, bits, DbgNode::from(pos), Some(dbg))?;
+                if !Self::append_composite(self.cells.last_mut().unwrap(), command, &cell) {
+                    return Err(OperationError::Internal("command does not fit in the cell checked"))
+                }
+                self.add_reference(&cell);
+                return Ok(self.note(command, Written { cell: self.cells.len() - 1, offset, bits }));
             }
         }
         let mut code = BuilderData::new();
-        let cell = reference.into_cell().map_err(|_| OperationError::NotFitInSlice)?;
         if code.append_raw(command, command.len() * 8).is_ok()
-            && code.checked_append_reference(cell).is_ok() {
-            self.cells.push(code);
-
-            let mut node = DbgNode::new();
-            node.append(0, pos);
-            node.append_node(dbg);
-            self.dbg.push(node);
-
-            return Ok(());
+            && code.checked_append_reference(cell.clone()).is_ok() {
+            self.push_cell(code, bits, DbgNode::from(pos), Some(dbg))?;
+            self.add_reference(&cell);
+            return Ok(self.note(command, Written { cell: self.cells.len() - 1, offset: 0, bits }));
         }
         Err(OperationError::NotFitInSlice)
     }
-    /// puts every cell as a reference to the previous one
-    fn finalize(mut self) -> (BuilderData, DbgNode) {
-        let mut cursor = self.cells.pop().expect("cells can't be empty");
-        let mut dbg = self.dbg.pop().expect("dbgs can't be empty");
-        while !self.cells.is_empty() {
-            let mut destination = self.cells.pop()
-                .expect("vector is not empty");
+    /// puts every cell as a reference to the previous one, or inlines it there as the layout allows
+    fn finalize(mut self) -> Result<(BuilderData, DbgNode), OperationError> {
+        let inline = match self.layout {
+            Layout::Latest | Layout::Greedy => true,
+            Layout::Chained => false,
+        };
+        let mut cursor = self.cells.pop().ok_or(OperationError::Internal("no cells to finalize"))?;
+        let mut dbg = match self.debug {
+            true => Some(self.dbg.pop().ok_or(OperationError::Internal("no debug nodes to finalize"))?),
+            false => None
+        };
+        while let Some(mut destination) = self.cells.pop() {
             let offset = destination.bits_used();
-            let slice = SliceData::from(cursor.clone().into_cell().expect("failure while convert BuilderData to cell"));
-            let mut next = self.dbg.pop().expect("dbg vector is not empty");
+            let mut next = match dbg {
+                Some(_) => Some(self.dbg.pop().ok_or(OperationError::Internal("debug nodes do not match cells"))?),
+                None => None
+            };
+            let cell = cursor.into_cell().map_err(|_| OperationError::NotFitInSlice)?;
             // try to inline cursor into destination
-            if destination.references_free() >= cursor.references_used()
-                && destination.checked_append_references_and_data(&slice).is_ok() {
-                next.inline_node(offset, dbg);
+            if inline && destination.references_free() >= cell.references_count()
+                && destination.checked_append_references_and_data(&SliceData::from(&cell)).is_ok() {
+                if let (Some(next), Some(dbg)) = (next.as_mut(), dbg) {
+                    next.checked_inline_node(offset, dbg)?;
+                }
             // otherwise just attach cursor to destination as a reference
             } else {
-                destination.append_reference_cell(cursor.into_cell().expect("failure while convert BuilderData to cell"));
-                next.append_node(dbg);
+                destination.append_reference_cell(cell);
+                if let (Some(next), Some(dbg)) = (next.as_mut(), dbg) {
+                    next.checked_append_node(dbg)?;
+                }
             }
             cursor = destination;
             dbg = next;
         }
-        (cursor, dbg)
+        Ok((cursor, dbg.unwrap_or_default()))
+    }
+    fn cells(&self) -> usize {
+        self.cells.len() + self.ref_cells
+    }
+    fn depth(&self) -> usize {
+        self.ref_depth.max(self.cells.len() - 1)
+    }
+    fn record(&mut self) {
+        self.written = Some(Vec::new())
+    }
+    fn take_written(&mut self) -> Vec<(SliceData, Written)> {
+        self.written.take().unwrap_or_default()
+    }
+    fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout
+    }
+    fn skip_debug_info(&mut self) {
+        self.debug = false;
+        self.dbg.clear();
+    }
+}
+
+/// Writer dropping debug positions of commands, for code compiled without debug info.
+/// The inner writer skips debug info as well, so no debug nodes are chained.
+pub struct NullDebug<W: Writer = CodePage0> {
+    inner: W,
+}
+
+impl<W: Writer> Writer for NullDebug<W> {
+    fn new() -> Self {
+        let mut inner = W::new();
+        inner.skip_debug_info();
+        Self { inner }
+    }
+    fn write_command(&mut self, command: &[u8], _dbg: DbgNode) -> Result<Written, OperationError> {
+        self.inner.write_command(command, DbgNode::new())
+    }
+    fn write_command_bitstring(&mut self, command: &[u8], bits: usize, _dbg: DbgNode) -> Result<Written, OperationError> {
+        self.inner.write_command_bitstring(command, bits, DbgNode::new())
+    }
+    fn write_composite_command(&mut self, code: &[u8], reference: BuilderData, _pos: DbgPos, _dbg: DbgNode) -> Result<Written, OperationError> {
+        self.inner.write_composite_command(code, reference, DbgPos::default(), DbgNode::new())
+    }
+    fn finalize(self) -> Result<(BuilderData, DbgNode), OperationError> {
+        self.inner.finalize().map(|(code, _)| (code, DbgNode::new()))
+    }
+    fn cells(&self) -> usize {
+        self.inner.cells()
+    }
+    fn depth(&self) -> usize {
+        self.inner.depth()
+    }
+    fn record(&mut self) {
+        self.inner.record()
+    }
+    fn take_written(&mut self) -> Vec<(SliceData, Written)> {
+        self.inner.take_written()
+    }
+    fn set_layout(&mut self, layout: Layout) {
+        self.inner.set_layout(layout)
     }
 }
@@ -0,0 +1,32 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+// every test crate takes the helpers it needs
+#![allow(dead_code)]
+
+use ton_labs_assembler::{CompileOptions, Line, Lines, compile_code_to_cell_with_options};
+
+/// Lines of the code numbered from 1, each one ended with a new line
+pub fn lines(code: &[&str]) -> Lines {
+    code.iter().enumerate().map(|(i, text)| Line::new(&format!("{}\n", text), "test.code", i + 1)).collect()
+}
+
+/// Bits of the root cell of the code
+pub fn encoding(code: &str) -> Vec<u8> {
+    encoding_with(code, &CompileOptions::default())
+}
+
+pub fn encoding_with(code: &str, options: &CompileOptions) -> Vec<u8> {
+    let cell = compile_code_to_cell_with_options(code, options).unwrap_or_else(|e| panic!("{}: {}", code, e));
+    cell.data().to_vec()
+}
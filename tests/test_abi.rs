@@ -0,0 +1,104 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+#![cfg(feature = "serde")]
+
+use std::{collections::BTreeMap, sync::{Arc, Mutex}};
+use ton_labs_assembler::{
+    CompileError, CompileOptions, Line, Visitor, compile_abi_dispatch, compile_abi_dispatch_with_options,
+    compile_dispatch, compile_dispatch_with_options,
+};
+
+fn position(error: CompileError) -> (usize, usize) {
+    match error {
+        CompileError::Syntax(position, _) => (position.line, position.column),
+        error => panic!("unexpected error {}", error)
+    }
+}
+
+fn code() -> Vec<Line> {
+    vec![
+        Line::new(".pubproc foo\n", "test.code", 1),
+        Line::new("  NOP\n", "test.code", 2),
+    ]
+}
+
+#[test]
+fn test_missing_pubproc_points_to_abi_entry() {
+    let abi = "{\n  \"functions\": [\n    { \"name\": \"foo\", \"id\": \"0x1\" },\n    { \"name\": \"bar\", \"id\": \"0x2\" }\n  ]\n}";
+    let error = compile_abi_dispatch(abi, code()).unwrap_err();
+    assert_eq!(position(error), (4, 5));
+}
+
+#[test]
+fn test_invalid_id_points_to_abi_entry() {
+    let abi = "{\"functions\": [\n  {\"name\": \"foo\", \"id\": []}]}";
+    let error = compile_abi_dispatch(abi, code()).unwrap_err();
+    assert_eq!(position(error), (2, 3));
+}
+
+#[test]
+fn test_invalid_json_points_to_error() {
+    let abi = "{\n  \"functions\": [,]\n}";
+    let error = compile_abi_dispatch(abi, code()).unwrap_err();
+    assert_eq!(position(error).0, 2);
+}
+
+fn options() -> CompileOptions {
+    let mut defines = BTreeMap::new();
+    defines.insert("VALUE".to_string(), "5".to_string());
+    CompileOptions { defines, skip_debug_info: true, ..CompileOptions::default() }
+}
+
+fn procedures() -> Vec<Line> {
+    vec![
+        Line::new("PUSHINT VALUE\n", "test.code", 1),
+        Line::new(".internal bar, 1\n", "test.code", 2),
+        Line::new("  PUSHINT VALUE\n", "test.code", 3),
+        Line::new(".internal baz, 2\n", "test.code", 4),
+        Line::new("  PUSHINT VALUE\n", "test.code", 5),
+        Line::new(".public foo, 3\n", "test.code", 6),
+        Line::new("  PUSHINT VALUE\n", "test.code", 7),
+    ]
+}
+
+#[test]
+fn test_dispatch_takes_options() {
+    assert!(compile_dispatch(procedures()).is_err());
+    let (_, dbg) = compile_dispatch_with_options(procedures(), &options()).unwrap();
+    assert!(dbg.is_empty());
+}
+
+#[test]
+fn test_dispatch_visits_every_body() {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let visited = lines.clone();
+    let visitor = Visitor::new(move |command| {
+        visited.lock().unwrap().push(command.position.line);
+        Ok(())
+    });
+    let options = CompileOptions { visitor: Some(visitor), ..options() };
+    compile_dispatch_with_options(procedures(), &options).unwrap();
+    let mut lines = lines.lock().unwrap().clone();
+    lines.sort();
+    assert_eq!(lines, [1, 3, 5, 7]);
+}
+
+#[test]
+fn test_abi_dispatch_takes_options() {
+    let abi = "{\"functions\": [{\"name\": \"foo\", \"id\": \"0x1\"}]}";
+    let code = vec![Line::new(".pubproc foo\n", "test.code", 1), Line::new("  PUSHINT VALUE\n", "test.code", 2)];
+    assert!(compile_abi_dispatch(abi, code.clone()).is_err());
+    let (_, dbg) = compile_abi_dispatch_with_options(abi, code, &options()).unwrap();
+    assert!(dbg.is_empty());
+}
@@ -0,0 +1,39 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use ton_labs_assembler::compile_code_to_cell;
+
+#[test]
+fn test_ifbitjmpref_takes_block_first() {
+    let code = compile_code_to_cell("IFBITJMPREF {\n  NOP\n}, 3\nIFNBITJMPREF { NOP }, 31").unwrap();
+    assert_eq!(code.data(), &[0xE3, 0xC3, 0xE3, 0xFF]);
+    assert_eq!(code.references_count(), 2);
+}
+
+#[test]
+fn test_block_after_parameter_is_rejected() {
+    assert!(compile_code_to_cell("IFBITJMPREF 3, { NOP }").is_err());
+    assert!(compile_code_to_cell("IFBITJMPREF 3 { NOP }").is_err());
+}
+
+#[test]
+fn test_dictpushconst_takes_block_first() {
+    let code = compile_code_to_cell("DICTPUSHCONST { 0 => { NOP } }, 4").unwrap();
+    assert_eq!(code.data(), &[0xF4, 0xA4, 0x04]);
+    assert_eq!(code.references_count(), 1);
+}
+
+#[test]
+fn test_parameter_after_block_requires_comma() {
+    assert!(compile_code_to_cell("IFBITJMPREF { NOP } 3").is_err());
+}
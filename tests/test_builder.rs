@@ -0,0 +1,52 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use num::BigInt;
+use ton_labs_assembler::{CodeBuilder, Instruction, Operand, compile_code};
+
+#[test]
+fn test_nested_blocks_match_source() {
+    let inner = vec![Instruction::with_operands("PUSHINT", vec![Operand::Integer(BigInt::from(1))])];
+    let outer = vec![
+        Instruction::with_operands("PUSHCONT", vec![Operand::Block(inner)]),
+        Instruction::new("EXECUTE"),
+    ];
+    let mut builder = CodeBuilder::new();
+    builder.pushcont(outer).unwrap();
+    let (code, _) = builder.finalize().unwrap();
+    assert_eq!(code, compile_code("PUSHCONT { PUSHCONT { PUSHINT 1 } EXECUTE }").unwrap());
+}
+
+#[test]
+fn test_ref_block_is_encoded() {
+    let mut builder = CodeBuilder::new();
+    builder.command("IFREF", vec![Operand::Block(vec![Instruction::new("NOP")])]).unwrap();
+    let (code, _) = builder.finalize().unwrap();
+    assert_eq!(code, compile_code("IFREF { NOP }").unwrap());
+}
+
+#[test]
+fn test_big_integer_is_encoded() {
+    let value = BigInt::from(1) << 200;
+    let mut builder = CodeBuilder::new();
+    builder.pushint(value.clone()).unwrap().pushint(-5).unwrap();
+    let (code, _) = builder.finalize().unwrap();
+    assert_eq!(code, compile_code(&format!("PUSHINT {}\nPUSHINT -5", value)).unwrap());
+}
+
+#[test]
+fn test_nested_error_is_reported() {
+    let mut builder = CodeBuilder::new();
+    assert!(builder.pushcont(vec![Instruction::new("NOSUCHCOMMAND")]).is_err());
+}
@@ -0,0 +1,84 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use ton_labs_assembler::{
+    CachedCode, CodeCache, CompileOptions, Line, Lines, MemoryCache, compile_dispatch_cached, compile_dispatch_with_options,
+};
+use ton_types::UInt256;
+
+/// Memory cache counting loads found and missed
+#[derive(Default)]
+struct CountingCache {
+    cache: MemoryCache,
+    hits: usize,
+    misses: usize,
+}
+
+impl CodeCache for CountingCache {
+    fn load(&mut self, key: &UInt256) -> Option<CachedCode> {
+        let code = self.cache.load(key);
+        match code {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        code
+    }
+    fn store(&mut self, key: UInt256, code: CachedCode) {
+        self.cache.store(key, code)
+    }
+}
+
+fn procedures(bar: &str) -> Lines {
+    vec![
+        Line::new("NOP\n", "test.code", 1),
+        Line::new(".internal bar, 1\n", "test.code", 2),
+        Line::new(&format!("  {}\n", bar), "test.code", 3),
+        Line::new(".public foo, 3\n", "test.code", 4),
+        Line::new("  PUSHINT 1 ADD\n", "test.code", 5),
+    ]
+}
+
+#[test]
+fn test_cache_hit() {
+    let options = CompileOptions::default();
+    let mut cache = CountingCache::default();
+    let first = compile_dispatch_cached(procedures("INC"), &mut cache, &options).unwrap();
+    assert_eq!((cache.hits, cache.misses, cache.cache.len()), (0, 2, 2));
+    let second = compile_dispatch_cached(procedures("INC"), &mut cache, &options).unwrap();
+    assert_eq!((cache.hits, cache.misses, cache.cache.len()), (2, 2, 2));
+    assert_eq!(first.0, second.0);
+    assert_eq!(first.1.len(), second.1.len());
+    assert_eq!(first.0, compile_dispatch_with_options(procedures("INC"), &options).unwrap().0);
+}
+
+#[test]
+fn test_cache_miss_on_changed_body() {
+    let options = CompileOptions::default();
+    let mut cache = CountingCache::default();
+    compile_dispatch_cached(procedures("INC"), &mut cache, &options).unwrap();
+    let code = compile_dispatch_cached(procedures("DEC"), &mut cache, &options).unwrap();
+    assert_eq!((cache.hits, cache.misses, cache.cache.len()), (1, 3, 3));
+    assert_eq!(code.0, compile_dispatch_with_options(procedures("DEC"), &options).unwrap().0);
+}
+
+#[test]
+fn test_cache_miss_on_changed_options() {
+    let mut cache = CountingCache::default();
+    compile_dispatch_cached(procedures("INC"), &mut cache, &CompileOptions::default()).unwrap();
+    let options = CompileOptions { optimize: true, ..CompileOptions::default() };
+    let code = compile_dispatch_cached(procedures("INC"), &mut cache, &options).unwrap();
+    assert_eq!((cache.hits, cache.misses, cache.cache.len()), (0, 4, 4));
+    assert_eq!(code.0, compile_dispatch_with_options(procedures("INC"), &options).unwrap().0);
+    assert_ne!(code.0, compile_dispatch_with_options(procedures("INC"), &CompileOptions::default()).unwrap().0);
+}
@@ -0,0 +1,75 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use ton_labs_assembler::{ControlFlowGraph, EdgeKind::{self, *}, control_flow_graph, parse_code};
+
+fn build(code: &str) -> ControlFlowGraph {
+    control_flow_graph(&parse_code(code).unwrap())
+}
+
+fn edges(graph: &ControlFlowGraph) -> Vec<(usize, usize, EdgeKind)> {
+    graph.edges.iter().map(|edge| (edge.from, edge.to, edge.kind)).collect()
+}
+
+#[test]
+fn test_straight_code_is_one_block() {
+    let graph = build("INC DEC");
+    assert_eq!(graph.blocks.len(), 1);
+    assert!(graph.edges.is_empty());
+    assert_eq!(graph.exits, [0]);
+}
+
+#[test]
+fn test_conditional_edges() {
+    let graph = build("PUSHCONT { INC } IF DEC");
+    assert_eq!(graph.blocks[1].path, [(0, 0)]);
+    assert_eq!(edges(&graph), [(0, 1, Branch), (1, 2, Return), (0, 2, Next)]);
+    assert_eq!(graph.exits, [2]);
+    let graph = build("PUSHCONT { INC } PUSHCONT { DEC } IFELSE SWAP");
+    assert_eq!(edges(&graph), [(0, 1, Branch), (1, 3, Return), (0, 2, Branch), (2, 3, Return)]);
+}
+
+#[test]
+fn test_call_and_jump_edges() {
+    let graph = build("PUSHCONT { INC } CALLX DEC");
+    assert_eq!(edges(&graph), [(0, 1, Call), (1, 2, Return)]);
+    let graph = build("CALLREF { INC } DEC");
+    assert_eq!(edges(&graph), [(0, 1, Call), (1, 2, Return)]);
+    // the jump does not return to the code following it
+    let graph = build("PUSHCONT { INC } JMPX DEC");
+    assert_eq!(edges(&graph), [(0, 1, Jump)]);
+    assert!(graph.exits.contains(&1));
+}
+
+#[test]
+fn test_loop_edges() {
+    let graph = build("PUSHCONT { INC } PUSHCONT { DEC } WHILE NOP");
+    assert_eq!(edges(&graph), [(0, 1, Call), (1, 2, Branch), (1, 3, Return), (2, 1, Loop)]);
+    assert_eq!(graph.successors(1).count(), 2);
+    assert_eq!(graph.predecessors(1).count(), 2);
+}
+
+#[test]
+fn test_throw_has_no_successors() {
+    let graph = build("INC THROW 5");
+    assert!(graph.edges.is_empty());
+    assert!(graph.exits.is_empty());
+}
+
+#[test]
+fn test_unlinked_continuations_are_entries() {
+    let graph = build("PUSHCONT { DEC } WHILE SWAP");
+    assert_eq!(edges(&graph), [(0, 2, Next)]);
+    assert_eq!(graph.entries(), [0, 1]);
+}
@@ -0,0 +1,96 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+mod common;
+use common::{encoding_with, lines};
+use ton_labs_assembler::{
+    CompileOptions, compile_code_debuggable_with_options, compile_code_listing, compile_code_recovering,
+    compile_code_debuggable_str, compile_reader,
+};
+
+fn options(optimize: bool, skip_debug_info: bool, dedup_blocks: bool) -> CompileOptions {
+    CompileOptions { optimize, skip_debug_info, dedup_blocks, ..CompileOptions::default() }
+}
+
+const CODE: &[&str] = &["PUSHINT 1", "PUSHINT 2", "ADD", "PUSHREF { NOP NOP }", "PUSHREF { NOP NOP }"];
+
+#[test]
+fn test_recovering_follows_options() {
+    for &(optimize, skip_debug_info, dedup_blocks) in &[
+        (false, false, false), (true, false, false), (false, true, false), (true, true, true), (false, false, true)
+    ] {
+        let options = options(optimize, skip_debug_info, dedup_blocks);
+        let expected = compile_code_debuggable_with_options(lines(CODE), &options).unwrap();
+        let (code, dbg) = compile_code_recovering(lines(CODE), &options).unwrap();
+        assert_eq!(code, expected.0);
+        assert_eq!(dbg.len(), expected.1.len());
+        assert_eq!(dbg.is_empty(), skip_debug_info);
+    }
+}
+
+#[test]
+fn test_recovering_reports_all_errors() {
+    for &optimize in &[false, true] {
+        let errors = compile_code_recovering(lines(&["NOSUCH", "NOP", "PUSHINT x"]), &options(optimize, false, true)).unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+}
+
+#[test]
+fn test_listing_follows_options() {
+    for &(optimize, skip_debug_info, dedup_blocks) in &[
+        (false, false, false), (true, false, false), (false, true, false), (true, true, true), (false, false, true)
+    ] {
+        let options = options(optimize, skip_debug_info, dedup_blocks);
+        let expected = compile_code_debuggable_with_options(lines(CODE), &options).unwrap();
+        let (code, dbg, listing) = compile_code_listing(lines(CODE), &options).unwrap();
+        assert_eq!(code, expected.0);
+        assert_eq!(dbg.len(), expected.1.len());
+        assert_eq!(dbg.is_empty(), skip_debug_info);
+        assert!(!listing.is_empty());
+    }
+}
+
+#[cfg(feature = "profile")]
+#[test]
+fn test_profiled_follows_options() {
+    for &(optimize, skip_debug_info, dedup_blocks) in &[
+        (false, false, false), (true, false, false), (false, true, false), (true, true, true), (false, false, true)
+    ] {
+        let options = options(optimize, skip_debug_info, dedup_blocks);
+        let expected = compile_code_debuggable_with_options(lines(CODE), &options).unwrap();
+        let (code, dbg, _) = ton_labs_assembler::compile_code_profiled(lines(CODE), &options).unwrap();
+        assert_eq!(code, expected.0);
+        assert_eq!(dbg.len(), expected.1.len());
+        assert_eq!(dbg.is_empty(), skip_debug_info);
+    }
+}
+
+#[test]
+fn test_byte_data_is_not_optimized() {
+    for &(code, bytes) in &[(".byte 0", &[0x00][..]), (".byte 1, 1", &[0x01, 0x01]), (".byte 0x71, 0xA0", &[0x71, 0xA0])] {
+        assert_eq!(encoding_with(code, &options(true, false, false)), bytes, "{}", code);
+    }
+}
+
+#[test]
+fn test_reader_matches_source() {
+    let code = "PUSHINT 1\n; комментарий\nlabel:\n  PRINTSTR \"ок\" INC\nPUSHREF { NOP }\n";
+    let (expected, expected_dbg) = compile_code_debuggable_str(code, "test.code").unwrap();
+    let (code, dbg) = compile_reader(code.as_bytes(), "test.code").unwrap();
+    assert_eq!(code, expected);
+    assert_eq!(dbg.len(), expected_dbg.len());
+    let error = compile_reader("NOP\n\"ок\" NOSUCH\n".as_bytes(), "test.code").unwrap_err();
+    assert_eq!(error, compile_code_debuggable_str("NOP\n\"ок\" NOSUCH\n", "test.code").unwrap_err());
+}
@@ -0,0 +1,45 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use ton_labs_assembler::{CompileOptions, compile_code_to_boc, compile_code_to_cell, compile_code_to_cell_with_options};
+
+const BODY: &str = "PUSHINT 1000 PUSHINT 2000 ADD PUSHINT 3000 MUL PUSHINT 4000 SUB";
+
+fn dedup() -> CompileOptions {
+    CompileOptions { dedup_blocks: true, ..CompileOptions::default() }
+}
+
+#[test]
+fn test_identical_blocks_share_cell() {
+    let code = format!("PUSHCONT {{ {b} }}\nPUSHCONT {{ {b} }}\n", b = BODY);
+    let cell = compile_code_to_cell_with_options(&code, &dedup()).unwrap();
+    assert_eq!(cell.references_count(), 2);
+    assert_eq!(cell.reference(0).unwrap().repr_hash(), cell.reference(1).unwrap().repr_hash());
+    let expected = compile_code_to_cell(&format!("PUSHREFCONT {{ {b} }}\nPUSHREFCONT {{ {b} }}\n", b = BODY)).unwrap();
+    assert_eq!(cell, expected);
+    let plain = compile_code_to_boc(&code).unwrap();
+    let shared = ton_types::serialize_toc(&cell).unwrap();
+    assert!(shared.len() < plain.len());
+}
+
+#[test]
+fn test_small_and_single_blocks_stay_inline() {
+    for code in &[
+        "PUSHCONT { INC }\nPUSHCONT { INC }\n".to_string(),
+        format!("PUSHCONT {{ {} }}\nPUSHCONT {{ INC }}\n", BODY),
+    ] {
+        let expected = compile_code_to_cell(code).unwrap();
+        assert_eq!(compile_code_to_cell_with_options(code, &dedup()).unwrap(), expected, "{}", code);
+    }
+}
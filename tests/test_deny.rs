@@ -0,0 +1,74 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+mod common;
+use common::lines;
+use ton_labs_assembler::{
+    CompileError, CompileOptions, DiagnosticKind, compile_code_debuggable_with_options,
+    compile_code_listing, compile_code_recovering, compile_code_to_cell_with_options, compile_code_with_diagnostics,
+    compile_dispatch_with_options,
+};
+
+fn denying(eliminate_dead_code: bool) -> CompileOptions {
+    let deny = std::iter::once(DiagnosticKind::UnreachableCode).collect();
+    CompileOptions { deny, eliminate_dead_code, ..CompileOptions::default() }
+}
+
+const DEAD: &[&str] = &["INC", "RET", "DEC"];
+
+fn assert_denied(error: CompileError, line: usize, column: usize) {
+    match error {
+        CompileError::Denied(position, kind, _) => {
+            assert_eq!(kind, DiagnosticKind::UnreachableCode);
+            assert_eq!((&*position.filename, position.line, position.column), ("test.code", line, column));
+        }
+        error => panic!("not denied: {}", error)
+    }
+}
+
+#[test]
+fn test_denied_warning_fails_compilation() {
+    for &eliminate in &[false, true] {
+        let options = denying(eliminate);
+        assert_denied(compile_code_debuggable_with_options(lines(DEAD), &options).unwrap_err(), 3, 1);
+        assert_denied(compile_code_with_diagnostics(lines(DEAD), &options).unwrap_err(), 3, 1);
+        let errors = compile_code_recovering(lines(DEAD), &options).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_denied(errors.into_iter().next().unwrap(), 3, 1);
+        assert_denied(compile_code_listing(lines(DEAD), &options).unwrap_err(), 3, 1);
+    }
+}
+
+#[test]
+fn test_denied_warning_of_source_has_position() {
+    match compile_code_to_cell_with_options("INC\nRET\nDEC\n", &denying(false)).unwrap_err() {
+        CompileError::Denied(position, _, _) => assert_eq!((position.line, position.column), (3, 1)),
+        error => panic!("not denied: {}", error)
+    }
+}
+
+#[test]
+fn test_denied_warning_of_dispatcher() {
+    let preamble = &["INC", "RET", "DEC", ".internal foo, 1", "  NOP"];
+    assert_denied(compile_dispatch_with_options(lines(preamble), &denying(false)).unwrap_err(), 3, 1);
+    let body = &["NOP", ".internal foo, 1", "  INC", "  RET", "  DEC"];
+    assert_denied(compile_dispatch_with_options(lines(body), &denying(false)).unwrap_err(), 5, 3);
+}
+
+#[test]
+fn test_clean_code_passes() {
+    let code = &["INC", "PUSHCONT { RET }", "IF", "DEC"];
+    compile_code_debuggable_with_options(lines(code), &denying(false)).unwrap();
+    compile_code_with_diagnostics(lines(DEAD), &CompileOptions::default()).unwrap();
+}
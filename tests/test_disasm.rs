@@ -0,0 +1,88 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use ton_labs_assembler::{
+    Disassembler, OperationError, compile_code_to_cell, disassemble, disassemble_to_string, instructions_to_string,
+};
+use ton_types::{BuilderData, SliceData};
+
+/// Disassembled code compiles back to the same cell
+fn round_trip(source: &str) {
+    let cell = compile_code_to_cell(source).unwrap_or_else(|e| panic!("{}: {}", source, e));
+    let text = disassemble_to_string(&SliceData::from(&cell)).unwrap();
+    let again = compile_code_to_cell(&text).unwrap_or_else(|e| panic!("{}: {}", text, e));
+    assert_eq!(cell.repr_hash(), again.repr_hash(), "{}\n=>\n{}", source, text);
+}
+
+#[test]
+fn test_round_trip_simple_commands() {
+    round_trip("NOP\nSWAP\nXCHG s1, s5\nPUSH s200\nADDCONST -7\nQDIV\nINDEX3 1, 2, 3\nRET");
+}
+
+#[test]
+fn test_round_trip_integers_and_slices() {
+    round_trip("PUSHINT 5\nPUSHINT 1000\nPUSHINT -100000\nPUSHINT 123456789012345678901234567890");
+    round_trip("PUSHSLICE x4_\nPUSHSLICE xABCDEF0123456789ABCDEF\nSTSLICECONST x8_\nSDBEGINS x6C");
+}
+
+#[test]
+fn test_round_trip_blocks() {
+    round_trip("PUSHCONT {\n  INC\n  PUSHCONT { DEC }\n}\nIFREF { NOP }\nCALLREF { SWAP }");
+    round_trip("IFBITJMPREF { INC }, 3\nIFNBITJMPREF { DEC }, 31");
+}
+
+#[test]
+fn test_round_trip_strings() {
+    round_trip("DEBUGSTR hello\nLOGSTR x0102");
+}
+
+#[test]
+fn test_disassembler_reuses_table() {
+    let cell = compile_code_to_cell("SWAP\nDROP").unwrap();
+    let first = Disassembler::new().disassemble(&SliceData::from(&cell)).unwrap();
+    let second = Disassembler::new().disassemble(&SliceData::from(&cell)).unwrap();
+    assert_eq!(instructions_to_string(&first), "SWAP\nDROP\n");
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_pushslice_with_references_is_unsupported() {
+    let mut reference = BuilderData::new();
+    reference.append_raw(&[0xAA], 8).unwrap();
+    let mut code = BuilderData::new();
+    // 8D, r = 1, xx = 0, then the completion tag in 6 bits
+    code.append_raw(&[0x8D, 0x20, 0x20], 16 + 6).unwrap();
+    code.checked_append_reference(reference.into_cell().unwrap()).unwrap();
+    let slice = SliceData::from(code.into_cell().unwrap());
+    assert_eq!(disassemble(&slice), Err(OperationError::Unsupported("PUSHSLICE")));
+}
+
+#[test]
+fn test_ifrefelseref_is_unsupported() {
+    let mut code = BuilderData::new();
+    code.append_raw(&[0xE3, 0x0F], 16).unwrap();
+    for _ in 0..2 {
+        code.checked_append_reference(BuilderData::new().into_cell().unwrap()).unwrap();
+    }
+    let slice = SliceData::from(code.into_cell().unwrap());
+    assert_eq!(disassemble(&slice), Err(OperationError::Unsupported("IFREFELSEREF")));
+}
+
+#[test]
+fn test_truncated_ref_command_is_not_skipped() {
+    // PUSHREF without its reference, then NOP
+    let mut code = BuilderData::new();
+    code.append_raw(&[0x88, 0x00], 16).unwrap();
+    let slice = SliceData::from(code.into_cell().unwrap());
+    assert_eq!(disassemble_to_string(&slice).unwrap(), ".BLOB x8800\n");
+}
@@ -0,0 +1,71 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use ton_labs_assembler::{CompileOptions, DiagnosticKind, Document, Severity};
+
+fn document(text: &str) -> Document {
+    Document::new("doc.code", text, CompileOptions::default())
+}
+
+#[test]
+fn test_edits_update_text_and_diagnostics() {
+    let mut document = document("INC\nRET\nDEC\n");
+    assert_eq!(document.diagnostics().len(), 1);
+    assert_eq!(document.diagnostics()[0].kind, DiagnosticKind::UnreachableCode);
+    assert_eq!(document.diagnostics()[0].position.line, 3);
+    let offset = document.offset(3, 1).unwrap();
+    assert!(document.edit(offset..offset + 3, "label:\nDEC").unwrap().is_empty());
+    assert_eq!(document.text(), "INC\nRET\nlabel:\nDEC\n");
+    let diagnostics = document.edit(0..3, "NOSUCH").unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(&*diagnostics[0].position.filename, "doc.code");
+    assert_eq!((diagnostics[0].position.line, diagnostics[0].position.span.clone()), (1, 0..6));
+    assert!(document.edit(0..6, "INC").unwrap().is_empty());
+}
+
+#[test]
+fn test_block_comment_edits_rescan_following_lines() {
+    let mut document = document("NOSUCH\nRET\n");
+    assert_eq!(document.edit(0..0, "/* ").unwrap()[0].message, "Syntax error: Missing end of block comment");
+    assert_eq!(document.text(), "/* NOSUCH\nRET\n");
+    let end = document.text().len();
+    assert!(document.edit(end..end, "*/\n").unwrap().is_empty());
+    assert!(document.edit(0..3, "").unwrap()[0].message.contains("NOSUCH"));
+}
+
+#[test]
+fn test_offsets_count_chars() {
+    let document = document("ок NOP\nINC\n");
+    assert_eq!(document.offset(1, 1), Some(0));
+    assert_eq!(document.offset(1, 2), Some(2));
+    assert_eq!(document.offset(1, 4), Some(5));
+    assert_eq!(document.offset(1, 7), Some(8));
+    assert_eq!(document.offset(1, 8), None);
+    assert_eq!(document.offset(2, 1), Some(9));
+    assert_eq!(document.offset(3, 1), Some(13));
+    assert_eq!(document.offset(0, 1), None);
+}
+
+#[test]
+fn test_bad_edits_are_errors() {
+    let mut document = document("INC\n");
+    assert!(document.edit(100..101, "x").is_err());
+    let (start, end) = (3, 1);
+    assert!(document.edit(start..end, "x").is_err());
+    // inside of a multibyte char
+    let mut document = Document::new("doc.code", "ок\n", CompileOptions::default());
+    assert!(document.edit(1..1, "x").is_err());
+    assert_eq!(document.text(), "ок\n");
+}
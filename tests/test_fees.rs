@@ -0,0 +1,61 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use ton_labs_assembler::{
+    CompileError, CompileOptions, OperationError, TargetVersion, compile_code_to_cell,
+    compile_code_to_cell_with_options, disassemble_to_string, required_version,
+};
+use ton_types::SliceData;
+
+const FEES: &[(&str, [u8; 2], u32)] = &[
+    ("GASCONSUMED", [0xF8, 0x07], 4),
+    ("GETGASFEE", [0xF8, 0x36], 6),
+    ("GETSTORAGEFEE", [0xF8, 0x37], 6),
+    ("GETFORWARDFEE", [0xF8, 0x38], 6),
+    ("GETPRECOMPILEDGAS", [0xF8, 0x39], 6),
+    ("GETORIGINALFWDFEE", [0xF8, 0x3A], 6),
+    ("GETGASFEESIMPLE", [0xF8, 0x3B], 6),
+    ("GETFORWARDFEESIMPLE", [0xF8, 0x3C], 6),
+];
+
+#[test]
+fn test_fee_encodings() {
+    for (mnemonic, opcode, _) in FEES {
+        let cell = compile_code_to_cell(mnemonic).unwrap_or_else(|e| panic!("{}: {}", mnemonic, e));
+        assert_eq!(cell.data(), opcode, "{}", mnemonic);
+        let text = disassemble_to_string(&SliceData::from(&cell)).unwrap();
+        assert_eq!(text.trim(), *mnemonic);
+    }
+}
+
+#[test]
+fn test_fee_versions() {
+    for (mnemonic, _, version) in FEES {
+        assert_eq!(required_version(mnemonic), Some(TargetVersion(*version)), "{}", mnemonic);
+        let options = CompileOptions { target_version: Some(TargetVersion(version - 1)), ..CompileOptions::default() };
+        match compile_code_to_cell_with_options(mnemonic, &options) {
+            Err(CompileError::Operation(_, _, OperationError::VersionRequired(required))) => assert_eq!(required.0, *version),
+            result => panic!("{}: unexpected {:?}", mnemonic, result)
+        }
+        let options = CompileOptions { target_version: Some(TargetVersion(*version)), ..CompileOptions::default() };
+        assert!(compile_code_to_cell_with_options(mnemonic, &options).is_ok(), "{}", mnemonic);
+    }
+}
+
+#[test]
+fn test_fee_operands_are_rejected() {
+    for (mnemonic, _, _) in FEES {
+        assert!(compile_code_to_cell(&format!("{} 1", mnemonic)).is_err(), "{}", mnemonic);
+    }
+}
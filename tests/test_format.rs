@@ -0,0 +1,45 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use ton_labs_assembler::{compile_code, format_code};
+
+const CODE: &str = "pushint 1 ; one\n  add\npushcont {\ninc\n   pushcont { dec }\n}\n.MACRO foo\npush s1\nxchg s0,s2\n.endmacro\n/* block\n   comment */\nlabel:\n  swap\n";
+
+const FORMATTED: &str = "PUSHINT 1 ; one\nADD\nPUSHCONT {\n  INC\n  PUSHCONT { DEC }\n}\n.macro foo\n  PUSH s1\n  XCHG s0, s2\n.endmacro\n/* block\n   comment */\nlabel:\nSWAP\n";
+
+#[test]
+fn test_format_code() {
+    assert_eq!(format_code(CODE).unwrap(), FORMATTED);
+}
+
+#[test]
+fn test_formatted_code_is_stable() {
+    assert_eq!(format_code(FORMATTED).unwrap(), FORMATTED);
+    assert_eq!(compile_code(FORMATTED).unwrap(), compile_code(CODE).unwrap());
+}
+
+#[test]
+fn test_operands_of_adjacent_lines_are_aligned() {
+    let code = "push s1\npushint 1\nxchg s1,s2\n\nblkdrop 2\n";
+    assert_eq!(format_code(code).unwrap(), "PUSH    s1\nPUSHINT 1\nXCHG    s1, s2\n\nBLKDROP 2\n");
+}
+
+#[test]
+fn test_unbalanced_braces_are_errors() {
+    for (code, line, column) in &[("NOP }", 1, 5), ("PUSHCONT {\nNOP", 1, 10)] {
+        let error = format_code(code).unwrap_err();
+        let position = error.position();
+        assert_eq!((position.line, position.column), (*line, *column), "{}", code);
+    }
+}
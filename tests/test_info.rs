@@ -0,0 +1,90 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+use std::collections::BTreeSet;
+use ton_labs_assembler::{
+    ALIASES, CodePage0, Engine, Writer, canonical_mnemonic, compile_code, compile_code_to_cell, instruction_gas,
+    instruction_info, instructions_info,
+};
+
+#[test]
+fn test_every_handler_has_info() {
+    let info = instructions_info().into_iter().map(|info| info.mnemonic).collect::<BTreeSet<_>>();
+    let missing = Engine::<CodePage0>::new(vec![]).mnemonics().into_iter()
+        .filter(|name| !name.starts_with('.') && !info.contains(name))
+        .collect::<Vec<_>>();
+    assert!(missing.is_empty(), "no info for {:?}", missing);
+}
+
+#[test]
+fn test_every_info_has_handler() {
+    let handlers = Engine::<CodePage0>::new(vec![]).mnemonics().into_iter().collect::<BTreeSet<_>>();
+    let unknown = instructions_info().into_iter()
+        .map(|info| info.mnemonic)
+        .filter(|name| !handlers.contains(name))
+        .collect::<BTreeSet<_>>();
+    assert!(unknown.is_empty(), "no handler for {:?}", unknown);
+}
+
+#[test]
+fn test_info_of_simple_command() {
+    let info = instruction_info("swap2");
+    assert_eq!(info.len(), 1);
+    assert_eq!(info[0].opcode, vec![0x5A]);
+    assert_eq!(info[0].bits, 8..=8);
+    assert_eq!(info[0].gas(), 18..=18);
+}
+
+#[test]
+fn test_gas_models_agree() {
+    let cases: &[(&str, &str, u64)] = &[
+        ("SWAP2", "SWAP2", 0),
+        ("PUSHINT 5", "PUSHINT", 0),
+        ("PUSHINT 1000", "PUSHINT", 0),
+        ("XCHG s1, s10", "XCHG", 0),
+        ("PUSHREF { NOP }", "PUSHREF", 0),
+        ("CALLREF { NOP }", "CALLREF", 100),
+        ("CTOS", "CTOS", 100),
+        ("ENDC", "ENDC", 500),
+    ];
+    for (code, mnemonic, dynamic) in cases {
+        let gas = instruction_gas(&compile_code(code).unwrap()) - dynamic;
+        assert!(instruction_info(mnemonic).iter().any(|info| info.gas().contains(&gas)), "{} takes {}", code, gas);
+    }
+}
+
+#[test]
+fn test_aliases_compile_as_canonical() {
+    let mnemonics = Engine::<CodePage0>::new(vec![]).mnemonics().into_iter().collect::<BTreeSet<_>>();
+    let mut aliases = BTreeSet::new();
+    for (alias, canonical) in ALIASES {
+        assert!(aliases.insert(alias), "duplicate alias {}", alias);
+        assert!(mnemonics.contains(alias) && mnemonics.contains(canonical), "alias {} of {}", alias, canonical);
+        assert_eq!(canonical_mnemonic(alias), *canonical);
+        assert_eq!(canonical_mnemonic(canonical), *canonical);
+        for operands in &["", " 1"] {
+            let code = compile_code_to_cell(&format!("{}{}", alias, operands)).ok();
+            assert_eq!(code, compile_code_to_cell(&format!("{}{}", canonical, operands)).ok(), "alias {}", alias);
+        }
+    }
+}
+
+#[test]
+fn test_alias_keeps_command() {
+    let mut engine = Engine::<CodePage0>::new(vec![]);
+    assert!(engine.register_alias("NOP", "SWAP").is_err());
+    assert!(engine.register_alias("MYSWAP", "NOSUCH").is_err());
+    engine.register_alias("MYSWAP", "SWAP").unwrap();
+    let (code, _) = engine.compile("NOP MYSWAP").unwrap().finalize().unwrap();
+    assert_eq!(code.data(), &[0x00, 0x01]);
+}
@@ -0,0 +1,54 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use ton_labs_assembler::{CompileOptions, Layout, compile_code_to_cell_with_options};
+use ton_types::Cell;
+
+// the fourth reference is left for the chain, so the last commands go to the next cell
+const CODE: &str = "PUSHREF { NOP } PUSHREF { INC } PUSHREF { DEC } PUSHREF { SWAP } INC";
+
+fn compile(code: &str, layout: Layout) -> Cell {
+    compile_code_to_cell_with_options(code, &CompileOptions { layout, ..CompileOptions::default() }).unwrap()
+}
+
+#[test]
+fn test_greedy_inlines_next_cell() {
+    let cell = compile(CODE, Layout::Greedy);
+    assert_eq!((cell.bit_length(), cell.references_count()), (40, 4));
+    assert_eq!(cell, compile(CODE, Layout::Latest));
+}
+
+#[test]
+fn test_chained_keeps_next_cell_in_reference() {
+    let cell = compile(CODE, Layout::Chained);
+    assert_eq!((cell.bit_length(), cell.references_count()), (24, 4));
+    let next = cell.reference(3).unwrap();
+    assert_eq!(next.data(), [0x88, 0xA4]);
+    assert_eq!(next.references_count(), 1);
+    assert_ne!(cell, compile(CODE, Layout::Greedy));
+}
+
+#[test]
+fn test_layouts_agree_on_single_cell() {
+    let code = "PUSHREF { NOP } INC DEC";
+    assert_eq!(compile(code, Layout::Chained), compile(code, Layout::Greedy));
+}
+
+#[test]
+fn test_layout_is_deterministic() {
+    for &layout in &[Layout::Latest, Layout::Greedy, Layout::Chained] {
+        assert_eq!(compile(CODE, layout).repr_hash(), compile(CODE, layout).repr_hash());
+        assert_eq!(Layout::from_name(layout.name()), Some(layout));
+    }
+}
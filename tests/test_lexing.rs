@@ -0,0 +1,57 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use ton_labs_assembler::{CompileError, compile_code, compile_code_debuggable_str};
+
+#[test]
+fn test_non_ascii_comments() {
+    let code = compile_code("NOP ; комментарий ✓\nNOP ;; 注释\nNOP").unwrap();
+    assert_eq!(code, compile_code("NOP\nNOP\nNOP").unwrap());
+}
+
+#[test]
+fn test_non_ascii_identifiers() {
+    let code = compile_code("PUSHCONT @тело\nRET\nтело:\n  INC\n").unwrap();
+    assert_eq!(code, compile_code("PUSHCONT @body\nRET\nbody:\n  INC\n").unwrap());
+    let code = compile_code(".macro дважды x\nPUSHCONT x\nPUSHCONT x\n.endmacro\nдважды { NOP }\n").unwrap();
+    assert_eq!(code, compile_code("PUSHCONT { NOP }\nPUSHCONT { NOP }").unwrap());
+    let code = compile_code(".equ ЧИСЛО, 5\nPUSHINT ЧИСЛО\n").unwrap();
+    assert_eq!(code, compile_code("PUSHINT 5").unwrap());
+    match compile_code("ПУСК") {
+        Err(CompileError::UnknownOperation(_, name, _)) => assert_eq!(name, "ПУСК"),
+        result => panic!("unexpected {:?}", result)
+    }
+}
+
+#[test]
+fn test_non_ascii_strings() {
+    let code = compile_code("PRINTSTR \"ок, да\"").unwrap();
+    assert_eq!(code.get_bytestring(0), [&[0xFE, 0xFA, 0x01][..], "ок, да".as_bytes()].concat());
+    let code = compile_code("LOGSTR 日本").unwrap();
+    assert_eq!(code.get_bytestring(0), [&[0xFE, 0xF6, 0x00][..], "日本".as_bytes()].concat());
+    // too long in bytes though short in chars
+    assert!(compile_code("PRINTSTR \"привет, мир\"").is_err());
+}
+
+#[test]
+fn test_error_column_counts_chars() {
+    match compile_code_debuggable_str("PRINTSTR \"ок\" ✓", "test.code") {
+        Err(CompileError::Syntax(pos, _)) => {
+            // columns count chars, spans are in bytes
+            assert_eq!(pos.column, 15);
+            assert_eq!(pos.span, 16..19);
+        }
+        result => panic!("unexpected {:?}", result)
+    }
+}
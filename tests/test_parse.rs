@@ -0,0 +1,90 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+mod common;
+use common::encoding;
+use ton_labs_assembler::{
+    CompileError, OperationError, ParameterError, compile_code_to_cell, compile_code_to_fift,
+    instructions_to_string, parse_code
+};
+
+const NESTED: &str = "PUSHCONT {\n  PUSHCONT { NOP }\n  NOP\n}\nPUSHCONT { NOP }\nIFREF { INC }";
+
+#[test]
+fn test_parse_nested_blocks() {
+    let code = instructions_to_string(&parse_code(NESTED).unwrap());
+    assert_eq!(code, "PUSHCONT {\n  PUSHCONT {\n    NOP\n  }\n  NOP\n}\nPUSHCONT {\n  NOP\n}\nIFREF {\n  INC\n}\n");
+}
+
+#[test]
+fn test_fift_nested_blocks() {
+    let code = compile_code_to_fift(NESTED).unwrap();
+    assert_eq!(code, "<{\n  <{\n    NOP\n  }> PUSHCONT\n  NOP\n}> PUSHCONT\n<{\n  NOP\n}> PUSHCONT\n<{\n  INC\n}> IFREF");
+}
+
+#[test]
+fn test_blocks_of_label_and_macro() {
+    let code = parse_code("PUSHCONT @body\nRET\nbody:\n  INC\n").unwrap();
+    assert_eq!(instructions_to_string(&code), "PUSHCONT {\n  INC\n}\nRET\nINC\n");
+    let code = parse_code(".macro twice x\nPUSHCONT x\nPUSHCONT x\n.endmacro\ntwice { NOP }\n").unwrap();
+    assert_eq!(instructions_to_string(&code), "PUSHCONT {\n  NOP\n}\nPUSHCONT {\n  NOP\n}\n");
+}
+
+fn blob(slice: &str) -> (Vec<u8>, usize) {
+    let cell = compile_code_to_cell(&format!(".blob {}", slice)).unwrap();
+    (cell.data().to_vec(), cell.bit_length())
+}
+
+#[test]
+fn test_slice_completion_tag() {
+    for slice in ["x_", "x8_", "x0_", "x00_", "x80_", "b_", "b1_", "b10_", "b0000_"].iter() {
+        assert_eq!(blob(slice), (vec![], 0), "{}", slice);
+    }
+    assert_eq!(blob("x4_"), (vec![0x00], 1));
+    assert_eq!(blob("xC_"), (vec![0x80], 1));
+    assert_eq!(blob("xAB_"), (vec![0xAA], 7));
+    assert_eq!(blob("xAB4_"), (vec![0xAB, 0x00], 9));
+    for slice in ["xA_B", "x__", "xA_0", "x_1"].iter() {
+        match compile_code_to_cell(&format!(".blob {}", slice)) {
+            Err(CompileError::Operation(_, _, OperationError::Parameter(_, ParameterError::UnexpectedType))) => (),
+            result => panic!("{}: {:?}", slice, result.map(|_| ()))
+        }
+    }
+}
+
+#[test]
+fn test_slice_odd_length() {
+    assert_eq!(blob("xA"), (vec![0xA0], 4));
+    assert_eq!(blob("xAB"), (vec![0xAB], 8));
+    assert_eq!(blob("xABC"), (vec![0xAB, 0xC0], 12));
+    assert_eq!(blob("b1"), (vec![0x80], 1));
+    assert_eq!(blob("b101"), (vec![0xA0], 3));
+    assert_eq!(blob("b1111111"), (vec![0xFE], 7));
+    assert_eq!(blob("b10000000"), (vec![0x80], 8));
+    match compile_code_to_cell(".blob xG") {
+        Err(CompileError::Operation(_, _, OperationError::Parameter(_, ParameterError::UnexpectedType))) => (),
+        result => panic!("{:?}", result.map(|_| ()))
+    }
+}
+
+#[test]
+fn test_slice_offsets() {
+    // the slice continues the byte of the opcode at a 4, 5 and 6 bit offset
+    assert_eq!(encoding("PUSHSLICE xF"), [0x8B, 0x1F, 0x80]);
+    assert_eq!(encoding("PUSHSLICE xFF"), [0x8B, 0x1F, 0xF8]);
+    assert_eq!(encoding("PUSHSLICE x_"), [0x8B, 0x08]);
+    assert_eq!(encoding("STSLICECONST xF"), [0xCF, 0x87, 0xE0]);
+    assert_eq!(encoding("STSLICECONST b1"), [0xCF, 0x83]);
+    assert_eq!(encoding("SDBEGINS xFF"), [0xD7, 0x28, 0x0F, 0xFC]);
+    assert_eq!(encoding("SDBEGINS b1"), [0xD7, 0x28, 0x06]);
+}
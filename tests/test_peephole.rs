@@ -0,0 +1,59 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+mod common;
+use common::encoding_with;
+use ton_labs_assembler::CompileOptions;
+
+fn optimized(code: &str) -> Vec<u8> {
+    encoding_with(code, &CompileOptions { optimize: true, ..CompileOptions::default() })
+}
+
+#[test]
+fn test_nop_is_dropped() {
+    assert_eq!(optimized("NOP"), Vec::<u8>::new());
+    assert_eq!(optimized("INC NOP DEC"), [0xA4, 0xA5]);
+}
+
+#[test]
+fn test_swap_swap_is_dropped() {
+    assert_eq!(optimized("SWAP SWAP"), Vec::<u8>::new());
+    assert_eq!(optimized("SWAP SWAP SWAP"), [0x01]);
+    assert_eq!(optimized("SWAP INC SWAP"), [0x01, 0xA4, 0x01]);
+}
+
+#[test]
+fn test_pushint_is_folded() {
+    let cases: &[(&str, &[u8])] = &[
+        ("PUSHINT 1 ADD", &[0xA4]),
+        ("PUSHINT -1 ADD", &[0xA5]),
+        ("PUSHINT 1 SUB", &[0xA5]),
+        ("PUSHINT -1 SUB", &[0xA4]),
+        ("PUSHINT 5 ADD", &[0xA6, 0x05]),
+        ("PUSHINT 100 SUB", &[0xA6, 0x9C]),
+        ("PUSHINT 3 MUL", &[0xA7, 0x03]),
+        ("PUSHINT 1 INC", &[0x71, 0xA4]),
+        ("PUSHINT 1000 ADD", &[0x81, 0x03, 0xE8, 0xA0]),
+    ];
+    for (code, bytes) in cases {
+        assert_eq!(optimized(code), *bytes, "{}", code);
+    }
+}
+
+#[test]
+fn test_sub_of_min_value_is_not_folded() {
+    // -(-128) does not fit ADDCONST
+    assert_eq!(optimized("PUSHINT -128 SUB"), [0x80, 0x80, 0xA1]);
+    assert_eq!(optimized("PUSHINT -128 ADD"), [0xA6, 0x80]);
+}
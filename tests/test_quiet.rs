@@ -0,0 +1,114 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+mod common;
+use common::encoding;
+use ton_labs_assembler::{compile_code_to_cell, instruction_info, instructions_info};
+
+#[test]
+fn test_quiet_variants_of_simple_commands() {
+    for info in instructions_info() {
+        let name = match info.mnemonic.strip_prefix('Q') {
+            Some(name) if !info.opcode.is_empty() => name,
+            _ => continue
+        };
+        // quiet arithmetic and comparisons are prefixed with 0xB7, others like QTLEN are not
+        let base = instruction_info(name).into_iter()
+            .find(|base| base.operands == info.operands && matches!(base.opcode.first(), Some(0xA0..=0xB6 | 0xB8..=0xC3)));
+        if let Some(base) = base {
+            let mut opcode = vec![0xB7];
+            opcode.extend_from_slice(&base.opcode);
+            assert_eq!(info.opcode, opcode, "{}", info.mnemonic);
+            assert_eq!(*info.bits.start(), base.bits.start() + 8, "{}", info.mnemonic);
+        }
+    }
+}
+
+#[test]
+fn test_every_arithmetic_command_has_quiet_variant() {
+    let missing = instructions_info().into_iter()
+        .filter(|info| matches!(info.opcode.first(), Some(0xA0..=0xB6 | 0xB8..=0xC3)))
+        // aliases of FITS 1 and UFITS 1 have no quiet mnemonics
+        .filter(|info| !matches!(info.mnemonic, "CHKBOOL" | "CHKBIT"))
+        .filter(|info| instruction_info(&format!("Q{}", info.mnemonic)).is_empty())
+        .map(|info| info.mnemonic)
+        .collect::<Vec<_>>();
+    assert!(missing.is_empty(), "no quiet variant of {:?}", missing);
+}
+
+#[test]
+fn test_quiet_arithmetic_encodings() {
+    let cases: &[(&str, &[u8])] = &[
+        ("QADD",            &[0xB7, 0xA0]),
+        ("QSUB",            &[0xB7, 0xA1]),
+        ("QSUBR",           &[0xB7, 0xA2]),
+        ("QNEGATE",         &[0xB7, 0xA3]),
+        ("QINC",            &[0xB7, 0xA4]),
+        ("QDEC",            &[0xB7, 0xA5]),
+        ("QADDCONST -5",    &[0xB7, 0xA6, 0xFB]),
+        ("QMULCONST 3",     &[0xB7, 0xA7, 0x03]),
+        ("QMUL",            &[0xB7, 0xA8]),
+        ("QDIV",            &[0xB7, 0xA9, 0x04]),
+        ("QMODR",           &[0xB7, 0xA9, 0x09]),
+        ("QMULDIVMOD",      &[0xB7, 0xA9, 0x8C]),
+        ("QLSHIFT 3",       &[0xB7, 0xAA, 0x02]),
+        ("QRSHIFT 256",     &[0xB7, 0xAB, 0xFF]),
+        ("QLSHIFT",         &[0xB7, 0xAC]),
+        ("QRSHIFT",         &[0xB7, 0xAD]),
+        ("QPOW2",           &[0xB7, 0xAE]),
+        ("QAND",            &[0xB7, 0xB0]),
+        ("QOR",             &[0xB7, 0xB1]),
+        ("QXOR",            &[0xB7, 0xB2]),
+        ("QNOT",            &[0xB7, 0xB3]),
+        ("QFITS 8",         &[0xB7, 0xB4, 0x07]),
+        ("QUFITS 256",      &[0xB7, 0xB5, 0xFF]),
+        ("QFITSX",          &[0xB7, 0xB6, 0x00]),
+        ("QUBITSIZE",       &[0xB7, 0xB6, 0x03]),
+        ("QMINMAX",         &[0xB7, 0xB6, 0x0A]),
+        ("QABS",            &[0xB7, 0xB6, 0x0B]),
+    ];
+    for (code, bytes) in cases {
+        assert_eq!(encoding(code), *bytes, "{}", code);
+    }
+}
+
+#[test]
+fn test_quiet_comparison_encodings() {
+    let cases: &[(&str, &[u8])] = &[
+        ("QSGN",            &[0xB7, 0xB8]),
+        ("QLESS",           &[0xB7, 0xB9]),
+        ("QEQUAL",          &[0xB7, 0xBA]),
+        ("QLEQ",            &[0xB7, 0xBB]),
+        ("QGREATER",        &[0xB7, 0xBC]),
+        ("QNEQ",            &[0xB7, 0xBD]),
+        ("QGEQ",            &[0xB7, 0xBE]),
+        ("QCMP",            &[0xB7, 0xBF]),
+        ("QEQINT -1",       &[0xB7, 0xC0, 0xFF]),
+        ("QLESSINT 10",     &[0xB7, 0xC1, 0x0A]),
+        ("QGTINT 0",        &[0xB7, 0xC2, 0x00]),
+        ("QNEQINT 127",     &[0xB7, 0xC3, 0x7F]),
+        ("QISZERO",         &[0xB7, 0xC0, 0x00]),
+        ("QISNEG",          &[0xB7, 0xC1, 0x00]),
+    ];
+    for (code, bytes) in cases {
+        assert_eq!(encoding(code), *bytes, "{}", code);
+    }
+}
+
+#[test]
+fn test_quiet_operands_are_validated() {
+    assert!(compile_code_to_cell("QADDCONST 128").is_err());
+    assert!(compile_code_to_cell("QFITS 0").is_err());
+    assert!(compile_code_to_cell("QRSHIFT 257").is_err());
+    assert!(compile_code_to_cell("QEQINT -129").is_err());
+}
@@ -0,0 +1,65 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+mod common;
+use common::lines;
+use ton_labs_assembler::{
+    CompileOptions, DiagnosticKind, Severity, compile_code_debuggable_with_options,
+    compile_code_with_diagnostics, find_dead_code,
+};
+
+fn eliminating() -> CompileOptions {
+    CompileOptions { eliminate_dead_code: true, ..CompileOptions::default() }
+}
+
+#[test]
+fn test_code_after_terminators_is_removed() {
+    let code = &["PUSHCONT { INC RET DEC }", "PUSHCONT { JMPX INC }", "THROW 5", "NOP"];
+    let live = &["PUSHCONT { INC RET }", "PUSHCONT { JMPX }", "THROW 5"];
+    let (expected, _) = compile_code_debuggable_with_options(lines(live), &CompileOptions::default()).unwrap();
+    let (code, _, diagnostics) = compile_code_with_diagnostics(lines(code), &eliminating()).unwrap();
+    assert_eq!(code, expected);
+    assert_eq!(diagnostics.len(), 3);
+    let kept = compile_code_with_diagnostics(lines(&["INC RET DEC"]), &CompileOptions::default()).unwrap();
+    assert_eq!(kept.0.remaining_bits(), 32);
+    assert_eq!(kept.2.len(), 1);
+}
+
+#[test]
+fn test_labels_after_terminator_are_kept() {
+    let code = &["PUSHCONT {", "  JMPX", "  DEC", "label:", "  INC", "}"];
+    let dead = find_dead_code(lines(code), &CompileOptions::default()).unwrap();
+    assert_eq!(dead.len(), 1);
+    assert_eq!((dead[0].position.line, dead[0].end_line, dead[0].commands), (3, 3, 1));
+    let live = &["PUSHCONT {", "  JMPX", "label:", "  INC", "}"];
+    let (expected, _) = compile_code_debuggable_with_options(lines(live), &CompileOptions::default()).unwrap();
+    let (code, _, _) = compile_code_with_diagnostics(lines(code), &eliminating()).unwrap();
+    assert_eq!(code, expected);
+}
+
+#[test]
+fn test_warnings_carry_positions() {
+    let code = &["PUSHCONT {", "  INC", "  RET", "  DEC", "  DEC", "}", "THROW 5", "NOP"];
+    for options in &[CompileOptions::default(), eliminating()] {
+        let (_, _, diagnostics) = compile_code_with_diagnostics(lines(code), options).unwrap();
+        let positions = diagnostics.iter()
+            .map(|d| {
+                assert_eq!((d.severity, d.kind), (Severity::Warning, DiagnosticKind::UnreachableCode));
+                (d.position.filename.to_string(), d.position.line, d.position.column, d.position.span.clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(positions, [("test.code".to_string(), 4, 3, 2..5), ("test.code".to_string(), 8, 1, 0..3)]);
+        assert_eq!(diagnostics[1].message.ends_with("removed"), options.eliminate_dead_code);
+    }
+}
@@ -0,0 +1,107 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+mod common;
+use common::encoding;
+use ton_labs_assembler::{CompileError, OperationError, ParameterError, compile_code_to_cell};
+
+fn out_of_range(code: &str) -> bool {
+    matches!(
+        compile_code_to_cell(code),
+        Err(CompileError::Operation(_, _, OperationError::Parameter(_, ParameterError::OutOfRange)))
+    )
+}
+
+#[test]
+fn test_tuple_encodings() {
+    let cases: &[(&str, &[u8])] = &[
+        ("TUPLE 0",         &[0x6F, 0x00]),
+        ("TUPLE 15",        &[0x6F, 0x0F]),
+        ("UNTUPLE 15",      &[0x6F, 0x2F]),
+        ("UNPACKFIRST 15",  &[0x6F, 0x3F]),
+        ("EXPLODE 15",      &[0x6F, 0x4F]),
+        ("INDEX 15",        &[0x6F, 0x1F]),
+        ("INDEXQ 15",       &[0x6F, 0x6F]),
+        ("SETINDEX 15",     &[0x6F, 0x5F]),
+        ("SETINDEXQ 15",    &[0x6F, 0x7F]),
+        ("INDEX2 3, 3",     &[0x6F, 0xBF]),
+        ("INDEX3 3, 3, 3",  &[0x6F, 0xFF]),
+        ("TUPLEVAR",        &[0x6F, 0x80]),
+        ("INDEXVAR",        &[0x6F, 0x81]),
+        ("UNTUPLEVAR",      &[0x6F, 0x82]),
+        ("UNPACKFIRSTVAR",  &[0x6F, 0x83]),
+        ("EXPLODEVAR",      &[0x6F, 0x84]),
+        ("SETINDEXVAR",     &[0x6F, 0x85]),
+        ("INDEXVARQ",       &[0x6F, 0x86]),
+        ("SETINDEXVARQ",    &[0x6F, 0x87]),
+        ("TLEN",            &[0x6F, 0x88]),
+        ("QTLEN",           &[0x6F, 0x89]),
+        ("ISTUPLE",         &[0x6F, 0x8A]),
+        ("LAST",            &[0x6F, 0x8B]),
+        ("TPUSH",           &[0x6F, 0x8C]),
+        ("TPOP",            &[0x6F, 0x8D]),
+    ];
+    for (code, bytes) in cases {
+        assert_eq!(encoding(code), *bytes, "{}", code);
+    }
+}
+
+#[test]
+fn test_tuple_aliases() {
+    let cases: &[(&str, &str)] = &[
+        ("NIL",         "TUPLE 0"),
+        ("SINGLE",      "TUPLE 1"),
+        ("PAIR",        "TUPLE 2"),
+        ("CONS",        "TUPLE 2"),
+        ("TRIPLE",      "TUPLE 3"),
+        ("UNSINGLE",    "UNTUPLE 1"),
+        ("UNPAIR",      "UNTUPLE 2"),
+        ("UNCONS",      "UNTUPLE 2"),
+        ("UNTRIPLE",    "UNTUPLE 3"),
+        ("CHKTUPLE",    "UNPACKFIRST 0"),
+        ("FIRST",       "INDEX 0"),
+        ("CAR",         "INDEX 0"),
+        ("SECOND",      "INDEX 1"),
+        ("CDR",         "INDEX 1"),
+        ("THIRD",       "INDEX 2"),
+        ("FIRSTQ",      "INDEXQ 0"),
+        ("CARQ",        "INDEXQ 0"),
+        ("SECONDQ",     "INDEXQ 1"),
+        ("CDRQ",        "INDEXQ 1"),
+        ("THIRDQ",      "INDEXQ 2"),
+        ("SETFIRST",    "SETINDEX 0"),
+        ("SETSECOND",   "SETINDEX 1"),
+        ("SETTHIRD",    "SETINDEX 2"),
+        ("SETFIRSTQ",   "SETINDEXQ 0"),
+        ("SETSECONDQ",  "SETINDEXQ 1"),
+        ("SETTHIRDQ",   "SETINDEXQ 2"),
+        ("CADR",        "INDEX2 1, 0"),
+        ("CDDR",        "INDEX2 1, 1"),
+        ("CADDR",       "INDEX3 1, 1, 0"),
+        ("CDDDR",       "INDEX3 1, 1, 1"),
+        ("COMMA",       "TPUSH"),
+    ];
+    for (alias, code) in cases {
+        assert_eq!(encoding(alias), encoding(code), "{}", alias);
+    }
+}
+
+#[test]
+fn test_tuple_operand_ranges() {
+    for code in &[
+        "TUPLE 16", "UNTUPLE 16", "UNPACKFIRST 16", "EXPLODE 16", "INDEX 16", "INDEXQ 16",
+        "SETINDEX 16", "SETINDEXQ 16", "INDEX2 4, 0", "INDEX2 0, 4", "INDEX3 0, 0, 4", "TUPLE -1", "TUPLE 300",
+    ] {
+        assert!(out_of_range(code), "{}", code);
+    }
+}
@@ -0,0 +1,65 @@
+/*
+* Copyright 2021 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+
+use ton_labs_assembler::{CodePage0, DbgNode, DbgPos, NullDebug, OperationError, Writer};
+use ton_types::BuilderData;
+
+fn pos(line: usize) -> DbgPos {
+    DbgPos { line, ..DbgPos::default() }
+}
+
+#[test]
+fn test_clash_keeps_cell() {
+    let mut code = CodePage0::new();
+    let mut dbg = DbgNode::from(pos(1));
+    dbg.append(16, pos(2));
+    code.write_command(&[0x00], dbg).unwrap();
+    let mut dbg = DbgNode::from(pos(3));
+    dbg.append(8, pos(4));
+    assert_eq!(code.write_command(&[0x01], dbg), Err(OperationError::DebugOffsetClash(16)));
+    code.write_command(&[0x02], DbgNode::from(pos(5))).unwrap();
+    let (builder, dbg) = code.finalize().unwrap();
+    assert_eq!(builder.data(), &[0x00, 0x02]);
+    assert_eq!(dbg.offsets.len(), 3);
+}
+
+#[test]
+fn test_children_are_limited() {
+    let mut node = DbgNode::new();
+    for _ in 0..4 {
+        node.checked_append_node(DbgNode::new()).unwrap();
+    }
+    assert!(node.checked_append_node(DbgNode::new()).is_err());
+    assert!(node.checked_inline_node(0, DbgNode { children: vec![DbgNode::new()], ..DbgNode::new() }).is_err());
+    assert_eq!(node.children.len(), 4);
+}
+
+fn write<W: Writer>() -> (BuilderData, DbgNode) {
+    let mut code = W::new();
+    for i in 0..200u8 {
+        code.write_command(&[0x71, i], DbgNode::from(pos(i as usize + 1))).unwrap();
+    }
+    let reference = BuilderData::with_raw(vec![0x00], 8).unwrap();
+    code.write_composite_command(&[0x8A], reference, pos(201), DbgNode::from(pos(202))).unwrap();
+    code.finalize().unwrap()
+}
+
+#[test]
+fn test_null_debug_keeps_no_nodes() {
+    let (code, dbg) = write::<CodePage0>();
+    assert!(!dbg.offsets.is_empty());
+    let (null_code, null_dbg) = write::<NullDebug>();
+    assert_eq!(null_code, code);
+    assert!(null_dbg.offsets.is_empty() && null_dbg.children.is_empty());
+}